@@ -1,9 +1,10 @@
 use super::parse::CallDef;
+use crate::utils::type_string;
 use quote::quote;
 
 /// See the `fn call` docs at the `lib.rs` of this crate for a high level definition.
 pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
-	let CallDef { pallet_struct, methods } = def;
+	let CallDef { pallet_struct, methods, errors } = def;
 
 	// This is a vector of all the callable function names.
 	let fn_name = methods.iter().map(|method| &method.name).collect::<Vec<_>>();
@@ -23,6 +24,78 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 		.map(|method| method.args.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>())
 		.collect::<Vec<_>>();
 
+	// This is a vector of the weight of each function in `fn_name`, falling back to the default
+	// weight when no `#[weight(..)]` attribute is given.
+	let weight = methods
+		.iter()
+		.map(|method| match &method.weight {
+			Some(weight) => quote! { #weight },
+			None => quote! { crate::support::DEFAULT_WEIGHT },
+		})
+		.collect::<Vec<_>>();
+
+	// This is a vector of how each function in `fn_name` is dispatched. Unsigned functions have no
+	// caller, so they cannot be dispatched on behalf of one; the runtime calls them directly.
+	let dispatch_fn = methods
+		.iter()
+		.zip(&args_name)
+		.map(|(method, args_name)| {
+			let fn_name = &method.name;
+			if method.unsigned {
+				quote! { return Err("call can only be dispatched unsigned") }
+			} else {
+				quote! {
+					self.#fn_name(
+						// Note that we assume the first argument of every call is the `caller`.
+						caller,
+						#( #args_name ),*
+					)?;
+				}
+			}
+		})
+		.collect::<Vec<_>>();
+
+	// This is a vector of the index of each function in `fn_name`, given with `#[call_index(..)]`
+	// and used to encode the `Call`.
+	let call_index = methods.iter().map(|method| method.index).collect::<Vec<_>>();
+
+	// This is a vector of the metadata of each function in `fn_name`. The types of the arguments
+	// are given both as they are written, and as the concrete types of the runtime along with how
+	// they are encoded.
+	let call_metadata = methods
+		.iter()
+		.zip(&call_index)
+		.map(|(method, call_index)| {
+			let name = method.name.to_string();
+			let arg_name = method.args.iter().map(|(name, _)| name.to_string());
+			let arg_type = method.args.iter().map(|(_, type_)| type_.clone());
+			let arg_type_string = method.args.iter().map(|(_, type_)| type_string(type_));
+			let docs = &method.docs;
+			let unsigned = method.unsigned;
+			quote! {
+				crate::metadata::CallMetadata {
+					name: #name.into(),
+					index: #call_index,
+					args: vec![
+						#(
+							crate::metadata::FieldMetadata {
+								name: #arg_name.into(),
+								ty: #arg_type_string.into(),
+								type_name: core::any::type_name::<#arg_type>().into(),
+								type_def: <#arg_type as crate::metadata::TypeInfo>::type_def(),
+							}
+						),*
+					],
+					docs: vec![ #( #docs.into() ),* ],
+					unsigned: #unsigned,
+				}
+			}
+		})
+		.collect::<Vec<_>>();
+
+	// This is a flat vector of the types of all arguments, used to bound the `Clone` impl below.
+	let all_args_type = args_type.iter().flatten().collect::<Vec<_>>();
+
 	// This quote block creates an `enum Call` which contains all the calls exposed by our pallet,
 	// and the `Dispatch` trait logic to route a `caller` to access those functions.
 	let dispatch_impl = quote! {
@@ -30,27 +103,102 @@ pub fn expand_call(def: CallDef) -> proc_macro2::TokenStream {
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
 		#[allow(non_camel_case_types)]
+		#[derive(Debug)]
 		pub enum Call<T: Config> {
 			#(
 				#fn_name { #( #args_name: #args_type),* },
 			)*
 		}
 
+		impl<T: Config> Call<T> {
+			// The metadata of the callable functions, in the order they are declared.
+			pub fn metadata() -> Vec<crate::metadata::CallMetadata>
+			where
+				#( #all_args_type: crate::metadata::TypeInfo ),*
+			{
+				vec![ #( #call_metadata ),* ]
+			}
+
+			// The errors returned by the callable functions, as far as the macro can see them.
+			pub fn errors() -> Vec<String> {
+				vec![ #( #errors.into() ),* ]
+			}
+		}
+
+		// We cannot derive `Clone`, since that would require `T: Clone`.
+		impl<T: Config> Clone for Call<T>
+		where
+			#( #all_args_type: Clone ),*
+		{
+			fn clone(&self) -> Self {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => Call::#fn_name {
+							#( #args_name: #args_name.clone() ),*
+						},
+					)*
+				}
+			}
+		}
+
+		// Calls are encoded as the index of the function, followed by each of its arguments.
+		impl<T: Config> crate::codec::Encode for Call<T>
+		where
+			#( #all_args_type: crate::codec::Encode ),*
+		{
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => {
+							crate::codec::Encode::encode_to(&#call_index, dest);
+							#( crate::codec::Encode::encode_to(#args_name, dest); )*
+						},
+					)*
+				}
+			}
+		}
+
+		// Calls are decoded from the index of the function, followed by each of its arguments.
+		impl<T: Config> crate::codec::Decode for Call<T>
+		where
+			#( #all_args_type: crate::codec::Decode ),*
+		{
+			fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+				match <u8 as crate::codec::Decode>::decode_from(input)? {
+					#(
+						#call_index => Ok(Call::#fn_name {
+							#( #args_name: crate::codec::Decode::decode_from(input)? ),*
+						}),
+					)*
+					_ => Err("invalid call index"),
+				}
+			}
+		}
+
+		// The weight of each call, which can depend on the arguments of the call.
+		impl<T: Config> crate::support::GetWeight for Call<T> {
+			#[allow(unused_variables)]
+			fn weight(&self) -> crate::support::Weight {
+				match self {
+					#(
+						Call::#fn_name { #( #args_name ),* } => #weight,
+					)*
+				}
+			}
+		}
+
 		// Dispatch logic at the pallet level, mapping each of the items in the `Call` enum to the
 		// appropriate function call with all arguments, including the `caller`.
 		impl<T: Config> crate::support::Dispatch for #pallet_struct<T> {
 			type Caller = T::AccountId;
 			type Call = Call<T>;
 
+			#[allow(unused_variables, unreachable_code)]
 			fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> crate::support::DispatchResult {
 				match call {
 					#(
 						Call::#fn_name { #( #args_name ),* } => {
-							self.#fn_name(
-								// Note that we assume the first argument of every call is the `caller`.
-								caller,
-								#( #args_name ),*
-							)?;
+							#dispatch_fn
 						},
 					)*
 				}
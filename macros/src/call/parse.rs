@@ -1,5 +1,17 @@
+use crate::utils::{doc_lines, parse_index_attr, ErrorCollector};
 use quote::ToTokens;
-use syn::spanned::Spanned;
+use syn::{spanned::Spanned, visit::Visit};
+
+/// The attribute used to declare the weight of a callable function.
+pub const WEIGHT_ATTR: &str = "weight";
+
+/// The attribute used to declare the index of a callable function, which identifies it in the
+/// encoding of its calls.
+pub const CALL_INDEX_ATTR: &str = "call_index";
+
+/// The attribute used to mark a callable function which takes no caller, and can only be dispatched
+/// in an unsigned extrinsic.
+pub const UNSIGNED_ATTR: &str = "unsigned";
 
 // Custom keywords we match to when parsing the calls in a pallet.
 mod keyword {
@@ -16,6 +28,8 @@ pub struct CallDef {
 	pub pallet_struct: syn::Ident,
 	/// This is a list of the callable functions exposed by this pallet. See `CallVariantDef`.
 	pub methods: Vec<CallVariantDef>,
+	/// The string literals the callable functions return as errors, for the metadata.
+	pub errors: Vec<String>,
 }
 
 /// This is the metadata we keep about each callable function in our pallet.
@@ -23,8 +37,17 @@ pub struct CallDef {
 pub struct CallVariantDef {
 	/// The function name.
 	pub name: syn::Ident,
+	/// The index given in the `#[call_index(..)]` attribute of the function.
+	pub index: u8,
 	/// Information on args of the function: `(name, type)`.
 	pub args: Vec<(syn::Ident, Box<syn::Type>)>,
+	/// The expression given in the `#[weight(..)]` attribute of the function, if any. It can refer
+	/// to the args of the function by name.
+	pub weight: Option<syn::Expr>,
+	/// Whether the function is marked `#[unsigned]`, in which case it has no `caller` argument.
+	pub unsigned: bool,
+	/// The lines of the doc comments of the function.
+	pub docs: Vec<String>,
 }
 
 impl CallDef {
@@ -43,8 +66,9 @@ impl CallDef {
 			_ => panic!("not supported tokens"),
 		};
 
-		// Here is where we will store all the callable functions.
+		// Here is where we will store all the callable functions, and the errors they return.
 		let mut methods = vec![];
+		let mut errors = ErrorCollector::default();
 		for item in item_impl.items {
 			if let syn::ImplItem::Fn(method) = item {
 				// Here is where we will store all the args for each callable functions.
@@ -59,8 +83,12 @@ impl CallDef {
 					},
 				}
 
-				// The second argument should be the `caller: T::AccountId` argument.
+				// Unsigned functions are dispatched without a caller.
+				let unsigned = method.attrs.iter().any(|attr| attr.path().is_ident(UNSIGNED_ATTR));
+
+				// Otherwise the second argument should be the `caller: T::AccountId` argument.
 				match method.sig.inputs.iter().skip(1).next() {
+					_ if unsigned => {},
 					Some(syn::FnArg::Typed(arg)) => {
 						// Here we specifically check that this argument is as we expect for
 						// `caller: T::AccountId`.
@@ -74,8 +102,30 @@ impl CallDef {
 
 				let fn_name = method.sig.ident.clone();
 
-				// Parsing the rest of the args. Skipping 2 for `self` and `caller`.
-				for arg in method.sig.inputs.iter().skip(2) {
+				// Parse the required `#[call_index(..)]` attribute, whose index must be unique, so
+				// reordering the functions does not change the meaning of encoded calls.
+				let (index, index_attr) =
+					parse_index_attr(&method.attrs, CALL_INDEX_ATTR, &method.sig.ident)?;
+				let used_by = methods.iter().find(|other: &&CallVariantDef| other.index == index);
+				if let Some(other) = used_by {
+					let name = &other.name;
+					let msg = format!("Invalid call, call index {index} is used by `{name}`");
+					return Err(syn::Error::new(index_attr.span(), msg))
+				}
+
+				// Parse the optional `#[weight(..)]` attribute.
+				let mut weight = None;
+				for attr in method.attrs.iter().filter(|attr| attr.path().is_ident(WEIGHT_ATTR)) {
+					if weight.is_some() {
+						let msg = "Invalid call, duplicate `#[weight]` attribute";
+						return Err(syn::Error::new(attr.span(), msg))
+					}
+					weight = Some(attr.parse_args::<syn::Expr>()?);
+				}
+
+				// Parsing the rest of the args. Skipping 2 for `self` and `caller`, or only `self`
+				// for unsigned functions.
+				for arg in method.sig.inputs.iter().skip(if unsigned { 1 } else { 2 }) {
 					// All arguments should be typed.
 					let arg = if let syn::FnArg::Typed(arg) = arg {
 						arg
@@ -95,13 +145,32 @@ impl CallDef {
 					args.push((arg_ident, arg.ty.clone()));
 				}
 
+				errors.visit_block(&method.block);
+
 				// Store all the function name and the arg data for the function.
-				methods.push(CallVariantDef { name: fn_name, args });
+				let docs = doc_lines(&method.attrs);
+				methods.push(CallVariantDef { name: fn_name, index, args, weight, unsigned, docs });
 			}
 		}
 
 		// Return all callable functions for this pallet.
-		Ok(Self { pallet_struct, methods })
+		Ok(Self { pallet_struct, methods, errors: errors.errors })
+	}
+}
+
+/// Remove the attributes we parse on the callable functions, since they are not real attributes and
+/// would otherwise fail to compile when we return the original impl.
+pub fn strip_fn_attrs(item: &mut syn::Item) {
+	if let syn::Item::Impl(item_impl) = item {
+		for item in item_impl.items.iter_mut() {
+			if let syn::ImplItem::Fn(method) = item {
+				method.attrs.retain(|attr| {
+					![WEIGHT_ATTR, UNSIGNED_ATTR, CALL_INDEX_ATTR]
+						.iter()
+						.any(|name| attr.path().is_ident(name))
+				});
+			}
+		}
 	}
 }
 
@@ -0,0 +1,56 @@
+use super::parse::EventDef;
+use crate::utils::type_string;
+use quote::quote;
+
+/// See the `fn event` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_event(def: EventDef) -> proc_macro2::TokenStream {
+	let EventDef { event_enum, generics, variants } = def;
+	let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+	// This is a vector of the metadata of each event. The types of the fields are given both as
+	// they are written, and as the concrete types of the runtime along with how they are encoded.
+	let event_metadata = variants
+		.iter()
+		.map(|variant| {
+			let name = variant.name.to_string();
+			let field_name = variant.fields.iter().map(|(name, _)| name);
+			let field_type = variant.fields.iter().map(|(_, type_)| type_);
+			let field_type_string = variant.fields.iter().map(|(_, type_)| type_string(type_));
+			let docs = &variant.docs;
+			quote! {
+				crate::metadata::EventMetadata {
+					name: #name.into(),
+					fields: vec![
+						#(
+							crate::metadata::FieldMetadata {
+								name: #field_name.into(),
+								ty: #field_type_string.into(),
+								type_name: core::any::type_name::<#field_type>().into(),
+								type_def: <#field_type as crate::metadata::TypeInfo>::type_def(),
+							}
+						),*
+					],
+					docs: vec![ #( #docs.into() ),* ],
+				}
+			}
+		})
+		.collect::<Vec<_>>();
+
+	// This is a flat vector of the types of all fields, used to bound the metadata below.
+	let all_field_type = variants
+		.iter()
+		.flat_map(|variant| variant.fields.iter().map(|(_, type_)| type_))
+		.collect::<Vec<_>>();
+
+	quote! {
+		impl #impl_generics #event_enum #type_generics #where_clause {
+			// The metadata of the events, in the order they are declared.
+			pub fn metadata() -> Vec<crate::metadata::EventMetadata>
+			where
+				#( #all_field_type: crate::metadata::TypeInfo ),*
+			{
+				vec![ #( #event_metadata ),* ]
+			}
+		}
+	}
+}
@@ -0,0 +1,27 @@
+use quote::ToTokens;
+
+pub mod expand;
+pub mod parse;
+
+/// See the `fn event` docs at the `lib.rs` of this crate for a high level definition.
+pub fn event(
+	_attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	let item_enum = syn::parse_macro_input!(item as syn::Item);
+
+	// The final expanded code will be placed here.
+	// Since our macro only adds new code, our final product will contain all of our old code too.
+	let mut finished: proc_macro::TokenStream = item_enum.to_token_stream().into();
+
+	// First we parse the event enum...
+	let generated: proc_macro::TokenStream = match parse::EventDef::try_from(item_enum) {
+		// ..then we generate our new code.
+		Ok(def) => expand::expand_event(def).into(),
+		Err(e) => e.to_compile_error().into(),
+	};
+
+	// Add our generated code to the end, and return the final result.
+	finished.extend(generated);
+	return finished;
+}
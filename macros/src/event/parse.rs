@@ -0,0 +1,57 @@
+use crate::utils::doc_lines;
+use syn::spanned::Spanned;
+
+/// This object will collect all the information we need to keep while parsing the events of a
+/// pallet.
+#[derive(Debug)]
+pub struct EventDef {
+	/// This is the name of the event enum. We mostly assume it is `Event`.
+	pub event_enum: syn::Ident,
+	/// The generics of the event enum, usually `<T: Config>`.
+	pub generics: syn::Generics,
+	/// This is a list of the events of the pallet. See `EventVariantDef`.
+	pub variants: Vec<EventVariantDef>,
+}
+
+/// This is the metadata we keep about each event.
+#[derive(Debug)]
+pub struct EventVariantDef {
+	/// The name of the variant.
+	pub name: syn::Ident,
+	/// Information on the fields of the variant: `(name, type)`. Fields of tuple variants are named
+	/// by their position.
+	pub fields: Vec<(String, syn::Type)>,
+	/// The lines of the doc comments of the variant.
+	pub docs: Vec<String>,
+}
+
+impl EventDef {
+	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing an `enum`.
+		let item_enum = if let syn::Item::Enum(item) = item {
+			item
+		} else {
+			return Err(syn::Error::new(item.span(), "Invalid pallet::event, expected item enum"))
+		};
+
+		let variants = item_enum
+			.variants
+			.iter()
+			.map(|variant| {
+				let fields = variant
+					.fields
+					.iter()
+					.enumerate()
+					.map(|(index, field)| match &field.ident {
+						Some(ident) => (ident.to_string(), field.ty.clone()),
+						None => (index.to_string(), field.ty.clone()),
+					})
+					.collect();
+				let docs = doc_lines(&variant.attrs);
+				EventVariantDef { name: variant.ident.clone(), fields, docs }
+			})
+			.collect();
+
+		Ok(Self { event_enum: item_enum.ident, generics: item_enum.generics, variants })
+	}
+}
@@ -1,6 +1,32 @@
 mod call;
+mod event;
 mod runtime;
+mod storage;
+mod utils;
 
+/// Expand the callable functions of a pallet.
+///
+/// This generates:
+/// - `enum Call` - an enum with a variant for each callable function, containing its arguments.
+/// - implements the trait `support::Dispatch` for the pallet, routing each `Call` to its function.
+/// - implements the traits `Clone`, `codec::Encode` and `codec::Decode` for `Call`, when all the
+///   arguments do.
+/// - implements the trait `support::GetWeight` for `Call`. The weight of a function is given with
+///   the `#[weight(..)]` attribute, which can refer to the arguments of the function by name. If
+///   no weight is given, `support::DEFAULT_WEIGHT` is used.
+/// - `fn Call::metadata()` - which returns the `metadata::CallMetadata` of each function: its
+///   index, its arguments with their types, and its doc comments. The types of the arguments must
+///   implement `metadata::TypeInfo`.
+/// - `fn Call::errors()` - which returns the string literals the functions return as errors, with
+///   `Err(..)`, `ok_or(..)` or `ok_or_else(..)`. Errors returned by other functions they call are
+///   not included.
+///
+/// Each function must be given a unique index with `#[call_index(n)]`, which identifies it in the
+/// encoding of `Call`, so reordering the functions does not change the meaning of encoded calls.
+///
+/// Functions marked `#[unsigned]` take no `caller` argument. They can only be dispatched in
+/// unsigned extrinsics, by the runtime through `support::DispatchUnsigned`, and
+/// `support::Dispatch` returns an error for them.
 #[proc_macro_attribute]
 pub fn call(
 	attr: proc_macro::TokenStream,
@@ -9,14 +35,69 @@ pub fn call(
 	call::call(attr, item)
 }
 
+/// Expand the events of a pallet.
+///
+/// This generates `fn Event::metadata()`, which returns the `metadata::EventMetadata` of each
+/// variant: its fields with their types, and its doc comments. The types of the fields must
+/// implement `metadata::TypeInfo`.
+#[proc_macro_attribute]
+pub fn event(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	event::event(attr, item)
+}
+
+/// Expand the storage of a pallet.
+///
+/// This implements the trait `storage::PalletStorage` for the pallet struct, listing its fields
+/// whose type is `StorageValue`, `StorageMap` or `StorageDoubleMap`. The name of each field is part
+/// of the prefix of the keys of the item, so renaming a field moves its storage. Other fields are
+/// not part of the state, and are left alone.
+#[proc_macro_attribute]
+pub fn storage(
+	attr: proc_macro::TokenStream,
+	item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	storage::storage(attr, item)
+}
+
 /// Expand the `Runtime` definition.
 ///
 /// This generates function implementations on `Runtime`:
 /// - `fn new()` - which generates a new instance of the runtime, by instantiating all the pallets
-///   included in the runtime.
-/// - `fn execute_block()` - which handles basic logic for executing a block of extrinsics. It does
-///   basic actions like incrementing the block number and checking the block to be executed has a
-///   valid block number.
+///   included in the runtime and attaching their storage items to one `storage::SharedStorage`.
+/// - `fn metadata()` - which returns the `metadata::RuntimeMetadata` of the runtime, with the
+///   index, calls and errors of each pallet except system.
+/// - `fn finalize_genesis()` - which commits to the genesis state in the genesis header, whose hash
+///   extrinsics are signed for.
+/// - `fn validate_transaction()` - which checks a signed extrinsic against the current state: its
+///   weight, its signature with `system::Config::Signature`, its nonce, and whether the caller can
+///   pay the fee from `support::TransactionPayment`, which the runtime must implement.
+/// - `fn apply_extrinsic()` - which validates an extrinsic, withdraws its fee, increments the nonce
+///   of the caller and dispatches the call. The call is dispatched in a transaction of the shared
+///   storage, which is rolled back if the call fails. Unsigned extrinsics are handled by
+///   `support::DispatchUnsigned`, which the runtime must implement.
+/// - `fn extrinsic_fee()` - which returns the fee an extrinsic pays, including its tip.
+/// - `fn dry_run()` - which applies an extrinsic to a clone of the runtime, and returns its result,
+///   fee and the events it emits from `support::Events`, which the runtime must implement.
+/// - `fn initialize_block()`, `fn finalize_block()` and `fn note_finished_block()` - the steps of
+///   executing a block around its extrinsics. Initializing increments the block number, checks the
+///   header builds on the last block, and calls `support::Hooks::on_initialize` on every pallet
+///   except system, in the order they are declared. Finalizing calls `support::Hooks::on_finalize`.
+/// - `fn storage()` - which returns the `storage::Storage` of the runtime: the storage items of
+///   every pallet, including system, under the prefix of the name of its field. Every pallet must
+///   implement `storage::PalletStorage`, see `fn storage`.
+/// - `fn restore()` - which replaces the storage of the runtime with the state after a given
+///   header, along with the hashes of that header and of the genesis header, which are not part of
+///   the state.
+/// - `fn state_root()` - which returns the root of the `trie::Trie` of the storage, committed to in
+///   the header, so the values in storage can be proven against the header.
+/// - `fn execute_block()` - which handles basic logic for executing a block of extrinsics, using the
+///   functions above. It checks the seal of the block with `support::CheckSeal`, its weight, the
+///   inherents with `support::DispatchUnsigned::check_inherents` and the roots in its header, and
+///   returns a `support::BlockReport` with the result, weight, fee and events of each extrinsic,
+///   and the result, weight and events of each scheduled call.
 ///
 /// This also generates code needed for dispatching calls to the pallets:
 /// - Note: For simplicity, we assume that the system pallet is not callable.
@@ -25,6 +106,29 @@ pub fn call(
 /// - implements the trait `support::Dispatch` to dispatch calls to the appropriate pallet. Basic
 ///   logic like incrementing the nonce of the user is included in the generated code. The system
 ///   pallet is not included.
+/// - `fn RuntimeCall::pallet_name()` - which returns the name of the pallet a call is routed to, so
+///   calls can be filtered by pallet.
+/// - implements the traits `codec::Encode`, `codec::Decode`, `metadata::TypeInfo` and
+///   `support::GetWeight` for `RuntimeCall`.
+/// - implements `Clone` for the runtime, which attaches the clone to a copy of the storage, so the
+///   two can change independently. Every pallet must implement `Clone`, and the runtime must not
+///   derive it.
+///
+/// Each pallet field except system must be given a unique index with `#[pallet_index(n)]`, which
+/// identifies the pallet in the encoding of `RuntimeCall`, so reordering the fields does not change
+/// the meaning of encoded calls.
+///
+/// Pallet fields can be marked with `#[runtime_dispatch]`, in which case their calls are routed
+/// through `support::RuntimeDispatch` on the runtime instead of `support::Dispatch` on the pallet.
+/// This is needed by pallets which dispatch other calls, like `proxy`.
+///
+/// Pallet fields can be marked with `#[events]`, in which case the events of the pallet, from its
+/// `Event` enum expanded by `#[macros::event]`, are included in the metadata.
+///
+/// One pallet field can be marked with `#[scheduler]`, in which case `execute_block` dispatches
+/// the calls returned by its `take_due` function before any of the extrinsics in the block. Like
+/// extrinsics, a scheduled call which fails is rolled back, and `fn initialize_block()` returns a
+/// `support::ScheduledReceipt` for each scheduled call.
 #[proc_macro_attribute]
 pub fn runtime(
 	attr: proc_macro::TokenStream,
@@ -3,44 +3,380 @@ use quote::quote;
 
 /// See the `fn runtime` docs at the `lib.rs` of this crate for a high level definition.
 pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
-	let RuntimeDef { runtime_struct, pallets } = def;
+	let RuntimeDef { runtime_struct, pallets, scheduler } = def;
 
 	// This is a vector of all the pallet names, not including system.
-	let pallet_names = pallets.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+	let pallet_names = pallets.iter().map(|pallet| pallet.name.clone()).collect::<Vec<_>>();
 	// This is a vector of all the pallet types, not including system.
-	let pallet_types = pallets.iter().map(|(_, type_)| type_.clone()).collect::<Vec<_>>();
+	let pallet_types = pallets.iter().map(|pallet| pallet.type_.clone()).collect::<Vec<_>>();
+
+	// This is a vector of the index of each pallet, given with `#[pallet_index(..)]` and used to
+	// encode the `RuntimeCall`.
+	let pallet_index = pallets.iter().map(|pallet| pallet.index).collect::<Vec<_>>();
+
+	// This is a vector of how each pallet call is dispatched. Most pallets only need access to
+	// their own state, but pallets marked `#[runtime_dispatch]` are given the whole runtime.
+	let dispatch_pallet = pallets
+		.iter()
+		.map(|pallet| {
+			let name = &pallet.name;
+			if pallet.runtime_dispatch {
+				quote! {
+					crate::support::RuntimeDispatch::<#name::Call<Self>>::runtime_dispatch(
+						self, caller, call,
+					)?;
+				}
+			} else {
+				quote! { self.#name.dispatch(caller, call)?; }
+			}
+		})
+		.collect::<Vec<_>>();
+
+	// This is a vector of the metadata of each pallet, with the events of the pallets marked
+	// `#[events]`.
+	let pallet_metadata = pallets
+		.iter()
+		.zip(&pallet_index)
+		.map(|(pallet, index)| {
+			let name = &pallet.name;
+			let events = if pallet.events {
+				quote! { #name::Event::<Self>::metadata() }
+			} else {
+				quote! { Vec::new() }
+			};
+			quote! {
+				crate::metadata::PalletMetadata {
+					name: stringify!(#name).into(),
+					index: #index,
+					calls: #name::Call::<Self>::metadata(),
+					errors: #name::Call::<Self>::errors(),
+					events: #events,
+				}
+			}
+		})
+		.collect::<Vec<_>>();
+
+	// If the runtime includes a scheduler, the calls which are due are dispatched at the start of
+	// each block, before any of the extrinsics.
+	let dispatch_scheduled = match scheduler {
+		Some(scheduler) => quote! {{
+			use crate::support::{Events, GetWeight};
+			self.#scheduler
+				.take_due(block_number)
+				.into_iter()
+				.map(|(caller, call)| {
+					let weight = call.weight();
+					let events_before = self.events().len();
+					let result = self.dispatch_transactional(caller, call);
+					let events = self.events().split_off(events_before);
+					crate::support::ScheduledReceipt { result, weight, events }
+				})
+				.collect()
+		}},
+		None => quote! { Vec::new() },
+	};
 
 	// This quote block implements functions on the `Runtime` struct.
 	let runtime_impl = quote! {
 		impl #runtime_struct {
-			// Create a new instance of the main Runtime, by creating a new instance of each pallet.
+			// Create a new instance of the main Runtime, by creating a new instance of each pallet
+			// and attaching their storage items to one shared storage.
 			fn new() -> Self {
-				Self {
+				let mut runtime = Self {
 					// Since system is not included in the list of pallets, we manually add it here.
 					system: <system::Pallet::<Self>>::new(),
 					#(
 						#pallet_names: <#pallet_types>::new()
 					),*
+				};
+				runtime.attach_storage(&crate::storage::SharedStorage::default());
+				runtime
+			}
+
+			// Attach the storage items of every pallet to `storage`, under the name of its field.
+			fn attach_storage(&mut self, storage: &crate::storage::SharedStorage) {
+				use crate::storage::PalletStorage;
+				self.system.attach_storage("system", storage);
+				#(
+					self.#pallet_names.attach_storage(stringify!(#pallet_names), storage);
+				)*
+			}
+
+			// The storage shared by all the pallets.
+			fn shared_storage(&self) -> crate::storage::SharedStorage {
+				crate::storage::PalletStorage::shared_storage(&self.system)
+					.expect("the system pallet has storage items")
+			}
+
+			// The metadata of the runtime, describing the calls, errors and events of each pallet,
+			// so clients can discover them.
+			pub fn metadata() -> crate::metadata::RuntimeMetadata {
+				crate::metadata::RuntimeMetadata { pallets: vec![ #( #pallet_metadata ),* ] }
+			}
+
+			// Finish the genesis state, by committing to it in the genesis header. Its hash identifies
+			// the chain: extrinsics are signed for it, and the first block builds on it.
+			pub fn finalize_genesis(&mut self) -> types::Header {
+				let header = support::Header {
+					block_number: self.system.block_number(),
+					parent_hash: crate::support::Hash::default(),
+					extrinsics_root: crate::support::extrinsics_root::<types::Extrinsic>(&[]),
+					state_root: self.state_root(),
+					seal: None,
+				};
+				self.system.set_genesis_hash(header.hash());
+				self.note_finished_block(&header);
+				header
+			}
+
+			// Check whether a signed extrinsic is valid against the current state, without
+			// executing it. The nonce may be ahead of the caller's current nonce, in which case the
+			// extrinsic is only valid once the extrinsics before it have been executed. Returns the
+			// fee the caller will pay.
+			pub fn validate_transaction(
+				&self,
+				extrinsic: &types::Extrinsic,
+			) -> Result<<Self as crate::support::TransactionPayment>::Balance, &'static str> {
+				use crate::support::{GetWeight, IdentifyAccount, TransactionPayment, Verify};
+				let Some(signed) = &extrinsic.signed else {
+					return Err("unsigned extrinsics are not transactions")
+				};
+				if extrinsic.call.weight() > crate::support::MAX_BLOCK_WEIGHT {
+					return Err("call exceeds the block weight limit")
+				}
+				let payload = crate::support::signing_payload(
+					&extrinsic.call,
+					&signed.nonce,
+					&signed.tip,
+					&self.system.genesis_hash(),
+				);
+				if !signed.signature.verify(&payload, &signed.signer) {
+					return Err("invalid signature")
+				}
+				let caller = signed.signer.clone().into_account();
+				if signed.nonce < self.system.get_nonce(&caller) {
+					return Err("nonce has already been used")
 				}
+				let fee = Self::compute_fee(&extrinsic.call, signed.tip);
+				if !self.can_pay_fee(&caller, fee) {
+					return Err("cannot pay the fee")
+				}
+				Ok(fee)
 			}
 
-			// Execute a block of extrinsics. Increments the block number.
-			fn execute_block(&mut self, block: types::Block) -> crate::support::DispatchResult {
+			// Execute a single extrinsic. The outer `Result` is an error if the extrinsic is invalid,
+			// in which case nothing happens. Otherwise the fee is withdrawn and the nonce
+			// incremented, and the inner `Result` is the outcome of the call, which may still fail.
+			//
+			// Unsigned extrinsics are validated and dispatched by `support::DispatchUnsigned`, and pay
+			// no fee.
+			pub fn apply_extrinsic(
+				&mut self,
+				extrinsic: types::Extrinsic,
+			) -> crate::support::ApplyExtrinsicResult {
+				use crate::support::DispatchUnsigned;
+				let Some((caller, nonce)) = extrinsic.sender() else {
+					self.validate_unsigned(&extrinsic.call)?;
+					return Ok(self.dispatch_unsigned(extrinsic.call))
+				};
+				let fee = self.validate_transaction(&extrinsic)?;
+				if nonce != self.system.get_nonce(&caller) {
+					return Err("nonce is not the next nonce of the caller")
+				}
+				crate::support::TransactionPayment::withdraw_fee(self, &caller, fee)?;
+				self.system.inc_nonce(&caller);
+				Ok(self.dispatch_transactional(caller, extrinsic.call))
+			}
+
+			// Dispatch a call in a transaction of the shared storage, which is rolled back if the
+			// call fails, so a failed call has no effect even if it changed some storage before
+			// failing.
+			fn dispatch_transactional(
+				&mut self,
+				caller: <Self as crate::support::Dispatch>::Caller,
+				call: <Self as crate::support::Dispatch>::Call,
+			) -> crate::support::DispatchResult {
+				use crate::support::Dispatch;
+				let storage = self.shared_storage();
+				storage.start_transaction();
+				let result = self.dispatch(caller, call);
+				match result {
+					Ok(()) => storage.commit_transaction(),
+					Err(_) => storage.rollback_transaction(),
+				}
+				result
+			}
+
+			// The fee an extrinsic pays if it is applied: the fee for its call and its tip if it is
+			// signed, and nothing if it is unsigned.
+			pub fn extrinsic_fee(
+				extrinsic: &types::Extrinsic,
+			) -> <Self as crate::support::TransactionPayment>::Balance {
+				use crate::support::TransactionPayment;
+				match &extrinsic.signed {
+					Some(signed) => Self::compute_fee(&extrinsic.call, signed.tip),
+					None => num::Zero::zero(),
+				}
+			}
+
+			// Execute a single extrinsic against a copy of the current state, and return its result
+			// along with the events it emits and the fee it pays. The runtime itself is untouched.
+			pub fn dry_run(
+				&self,
+				extrinsic: types::Extrinsic,
+			) -> crate::support::DryRunResult<
+				<Self as crate::support::Events>::Event,
+				<Self as crate::support::TransactionPayment>::Balance,
+			> {
+				use crate::support::Events;
+				let fee = Self::extrinsic_fee(&extrinsic);
+				let mut runtime = self.clone();
+				let events_before = runtime.events().len();
+				let result = runtime.apply_extrinsic(extrinsic);
+				let events = runtime.events().split_off(events_before);
+				crate::support::DryRunResult { result, events, fee }
+			}
+
+			// Start a new block with the given header. Increments the block number, calls the
+			// `on_initialize` hook of each pallet and dispatches any scheduled calls, returning a
+			// receipt for each. The roots in the header are not checked, since they are only known
+			// once the block is finished.
+			pub fn initialize_block(
+				&mut self,
+				header: &types::Header,
+			) -> Result<
+				Vec<crate::support::ScheduledReceipt<<Self as crate::support::Events>::Event>>,
+				&'static str,
+			> {
 				self.system.inc_block_number();
-				if block.header.block_number != self.system.block_number() {
+				if header.block_number != self.system.block_number() {
 					return Err(&"block number does not match what is expected")
 				}
-				for (i, support::Extrinsic { caller, call }) in block.extrinsics.into_iter().enumerate() {
-					self.system.inc_nonce(&caller);
-					let _res = self.dispatch(caller, call).map_err(|e| {
-						eprintln!(
-							"Extrinsic Error\n\tBlock Number: {}\n\tExtrinsic Number: {}\n\tError: {}",
-							block.header.block_number, i, e
-						)
-					});
+				if header.parent_hash != self.system.parent_hash() {
+					return Err(&"parent hash does not match the last block")
 				}
+				let block_number = header.block_number;
+				#(
+					crate::support::Hooks::on_initialize(&mut self.#pallet_names, block_number);
+				)*
+				Ok(#dispatch_scheduled)
+			}
+
+			// Finish the current block, calling the `on_finalize` hook of each pallet.
+			pub fn finalize_block(&mut self, block_number: types::BlockNumber) {
+				#(
+					crate::support::Hooks::on_finalize(&mut self.#pallet_names, block_number);
+				)*
+			}
+
+			// Record that the block with `header` is finished, so it becomes the parent of the next
+			// block. This must be called after the state root of the block is computed.
+			pub fn note_finished_block(&mut self, header: &types::Header) {
+				self.system.set_parent_hash(header.hash());
+			}
+
+			// The storage of all the pallets, with the keys of each pallet prefixed by its name.
+			pub fn storage(&self) -> crate::storage::Storage {
+				self.shared_storage().storage()
+			}
+
+			// Restore the runtime to the state after the block with `header`, on the chain whose
+			// genesis header has `genesis_hash`. The `storage` must have been returned by `storage`
+			// on a runtime of the same type. The hashes of headers are not part of the state, so
+			// they are given along with it, and the state root of `header` is checked against it.
+			pub fn restore(
+				&mut self,
+				storage: &crate::storage::Storage,
+				genesis_hash: crate::support::Hash,
+				header: &types::Header,
+			) -> Result<(), &'static str> {
+				if storage.root() != header.state_root {
+					return Err("storage does not match the state root of the header")
+				}
+				self.shared_storage().set_storage(storage);
+				self.system.set_genesis_hash(genesis_hash);
+				self.note_finished_block(header);
 				Ok(())
 			}
+
+			// The root of the current state, which is the root of the trie of the storage of all
+			// the pallets.
+			pub fn state_root(&self) -> crate::support::Hash {
+				self.shared_storage().root()
+			}
+
+			// Execute a block of extrinsics, checking the header against the result. Returns a
+			// receipt for each extrinsic, whose call may have failed, but an invalid extrinsic makes
+			// the whole block invalid.
+			//
+			// If this returns an error, the state of the runtime should be discarded, since the
+			// block may have been partially executed.
+			fn execute_block(
+				&mut self,
+				block: types::Block,
+			) -> Result<
+				crate::support::BlockReport<
+					<Self as crate::support::Events>::Event,
+					<Self as crate::support::TransactionPayment>::Balance,
+				>,
+				&'static str,
+			> {
+				use crate::support::{CheckSeal, DispatchUnsigned, Events, GetWeight};
+				let support::Block { header, extrinsics } = block;
+				self.check_seal(&header)?;
+				if crate::support::extrinsics_root(&extrinsics) != header.extrinsics_root {
+					return Err(&"extrinsics root does not match the extrinsics")
+				}
+				let scheduled = self.initialize_block(&header)?;
+				// The scheduled calls count against the weight limit along with the extrinsics.
+				let block_weight = scheduled
+					.iter()
+					.map(|receipt| receipt.weight)
+					.chain(extrinsics.iter().map(|extrinsic| extrinsic.call.weight()))
+					.fold(0, crate::support::Weight::saturating_add);
+				if block_weight > crate::support::MAX_BLOCK_WEIGHT {
+					return Err(&"block weight exceeds the limit")
+				}
+
+				let inherents = extrinsics
+					.iter()
+					.take_while(|extrinsic| extrinsic.signed.is_none())
+					.map(|extrinsic| extrinsic.call.clone())
+					.collect::<Vec<_>>();
+				let mut receipts = Vec::new();
+				for extrinsic in extrinsics {
+					let weight = extrinsic.call.weight();
+					let fee = Self::extrinsic_fee(&extrinsic);
+					let events_before = self.events().len();
+					let result = self.apply_extrinsic(extrinsic)?;
+					let events = self.events().split_off(events_before);
+					receipts.push(crate::support::ExtrinsicReceipt { result, weight, fee, events });
+				}
+				self.check_inherents(&inherents)?;
+				self.finalize_block(header.block_number);
+
+				if self.state_root() != header.state_root {
+					return Err(&"state root does not match the state after execution")
+				}
+				self.note_finished_block(&header);
+				Ok(crate::support::BlockReport { block_hash: header.hash(), scheduled, receipts })
+			}
+		}
+
+		// The pallets of a clone share nothing with the original: each item of a cloned pallet has
+		// its own copy of its pairs, which are copied again into the storage the clone is
+		// attached to.
+		impl Clone for #runtime_struct {
+			fn clone(&self) -> Self {
+				let mut runtime = Self {
+					system: self.system.clone(),
+					#(
+						#pallet_names: self.#pallet_names.clone()
+					),*
+				};
+				runtime.attach_storage(&crate::storage::SharedStorage::default());
+				runtime
+			}
 		}
 	};
 
@@ -51,10 +387,67 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 		//
 		// The parsed function names will be `snake_case`, and that will show up in the enum.
 		#[allow(non_camel_case_types)]
+		#[derive(Debug, Clone)]
 		pub enum RuntimeCall {
 			#( #pallet_names(#pallet_names::Call<#runtime_struct>) ),*
 		}
 
+		impl RuntimeCall {
+			// The name of the pallet this call is routed to. This allows calls to be filtered by
+			// pallet, for example to restrict what a proxy may do on behalf of an account.
+			pub fn pallet_name(&self) -> &'static str {
+				match self {
+					#(
+						RuntimeCall::#pallet_names(_) => stringify!(#pallet_names)
+					),*
+				}
+			}
+		}
+
+		// Runtime calls are encoded as the index of the pallet, followed by the pallet call.
+		impl crate::codec::Encode for RuntimeCall {
+			fn encode_to(&self, dest: &mut Vec<u8>) {
+				match self {
+					#(
+						RuntimeCall::#pallet_names(call) => {
+							crate::codec::Encode::encode_to(&#pallet_index, dest);
+							crate::codec::Encode::encode_to(call, dest);
+						}
+					),*
+				}
+			}
+		}
+
+		// Runtime calls are described in the metadata by the calls of each pallet.
+		impl crate::metadata::TypeInfo for RuntimeCall {
+			fn type_def() -> crate::metadata::TypeDef {
+				crate::metadata::TypeDef::Call
+			}
+		}
+
+		impl crate::codec::Decode for RuntimeCall {
+			fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+				match <u8 as crate::codec::Decode>::decode_from(input)? {
+					#(
+						#pallet_index => Ok(RuntimeCall::#pallet_names(
+							crate::codec::Decode::decode_from(input)?
+						)),
+					)*
+					_ => Err("invalid pallet index"),
+				}
+			}
+		}
+
+		impl crate::support::GetWeight for RuntimeCall {
+			fn weight(&self) -> crate::support::Weight {
+				match self {
+					#(
+						RuntimeCall::#pallet_names(call) => call.weight()
+					),*
+				}
+			}
+		}
+
 		impl crate::support::Dispatch for #runtime_struct {
 			type Caller = <Runtime as system::Config>::AccountId;
 			type Call = RuntimeCall;
@@ -73,7 +466,7 @@ pub fn expand_runtime(def: RuntimeDef) -> proc_macro2::TokenStream {
 				match runtime_call {
 					#(
 						RuntimeCall::#pallet_names(call) => {
-							self.#pallet_names.dispatch(caller, call)?;
+							#dispatch_pallet
 						}
 					),*
 				}
@@ -1,3 +1,5 @@
+use quote::ToTokens;
+
 pub mod expand;
 pub mod parse;
 
@@ -6,11 +8,14 @@ pub fn runtime(
 	_attr: proc_macro::TokenStream,
 	item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
+	let item_mod = syn::parse_macro_input!(item as syn::Item);
+
 	// The final expanded code will be placed here.
 	// Since our macro only adds new code, our final product will contain all of our old code too,
-	// hence we clone `item`.
-	let mut finished = item.clone();
-	let item_mod = syn::parse_macro_input!(item as syn::Item);
+	// minus the helper attributes on the fields which only have meaning to this macro.
+	let mut original = item_mod.clone();
+	parse::strip_field_attrs(&mut original);
+	let mut finished: proc_macro::TokenStream = original.into_token_stream().into();
 
 	// First we parse the `Runtime` struct...
 	let generated: proc_macro::TokenStream = match parse::RuntimeDef::try_from(item_mod.clone()) {
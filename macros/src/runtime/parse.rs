@@ -1,13 +1,53 @@
+use crate::utils::parse_index_attr;
 use syn::spanned::Spanned;
 
+/// The attribute used to declare the index of a pallet, which identifies it in the encoding of the
+/// calls to the pallet.
+pub const PALLET_INDEX_ATTR: &str = "pallet_index";
+
+/// The attribute used to mark a pallet whose calls are dispatched through
+/// `support::RuntimeDispatch`.
+pub const RUNTIME_DISPATCH_ATTR: &str = "runtime_dispatch";
+
+/// The attribute used to mark the scheduler pallet, whose due calls are dispatched at the start of
+/// each block.
+pub const SCHEDULER_ATTR: &str = "scheduler";
+
+/// The attribute used to mark a pallet which has an `Event` enum expanded by `#[macros::event]`,
+/// whose events are included in the metadata.
+pub const EVENTS_ATTR: &str = "events";
+
+/// All the attributes we parse on the `Runtime` fields.
+const FIELD_ATTRS: &[&str] =
+	&[PALLET_INDEX_ATTR, RUNTIME_DISPATCH_ATTR, SCHEDULER_ATTR, EVENTS_ATTR];
+
 /// This object will collect all the information we need to keep while parsing the `Runtime` struct.
 #[derive(Debug)]
 pub struct RuntimeDef {
 	/// This is the name of the struct used by the user. We mostly assume it is `Runtime`.
 	pub runtime_struct: syn::Ident,
 	/// This is the list of pallets included in the `Runtime` struct. We omit `system` from this
-	/// list, but during parsing we check that system exists.
-	pub pallets: Vec<(syn::Ident, syn::Type)>,
+	/// list, but during parsing we check that system exists. See `PalletDef`.
+	pub pallets: Vec<PalletDef>,
+	/// The name of the pallet marked `#[scheduler]`, if any.
+	pub scheduler: Option<syn::Ident>,
+}
+
+/// This is the metadata we keep about each pallet included in the `Runtime` struct.
+#[derive(Debug)]
+pub struct PalletDef {
+	/// The name of the field in the `Runtime` struct. We assume it is also the name of the module
+	/// where the pallet is defined.
+	pub name: syn::Ident,
+	/// The type of the pallet, for example `balances::Pallet<Self>`.
+	pub type_: syn::Type,
+	/// The index given in the `#[pallet_index(..)]` attribute of the field.
+	pub index: u8,
+	/// Whether the field is marked `#[runtime_dispatch]`, meaning calls to this pallet are
+	/// dispatched with access to the whole runtime through `support::RuntimeDispatch`.
+	pub runtime_dispatch: bool,
+	/// Whether the field is marked `#[events]`, meaning the pallet has an `Event` enum.
+	pub events: bool,
 }
 
 impl RuntimeDef {
@@ -26,14 +66,34 @@ impl RuntimeDef {
 
 		// Here is where we will store a list of all the pallets.
 		let mut pallets = vec![];
+		let mut scheduler = None;
 		// We skip `system`, which we ensure is the first field in `check_system`.
 		for field in item_struct.fields.into_iter().skip(1) {
+			let has_attr = |name| field.attrs.iter().any(|attr| attr.path().is_ident(name));
+			let runtime_dispatch = has_attr(RUNTIME_DISPATCH_ATTR);
+			let is_scheduler = has_attr(SCHEDULER_ATTR);
+			let events = has_attr(EVENTS_ATTR);
 			if let Some(ident) = field.ident {
-				pallets.push((ident, field.ty))
+				// The index of each pallet must be unique, so reordering the fields does not change
+				// the meaning of encoded calls.
+				let (index, index_attr) = parse_index_attr(&field.attrs, PALLET_INDEX_ATTR, &ident)?;
+				if let Some(other) = pallets.iter().find(|other: &&PalletDef| other.index == index) {
+					let msg = format!("pallet index {index} is already used by `{}`", other.name);
+					return Err(syn::Error::new(index_attr.span(), msg))
+				}
+				if is_scheduler {
+					if scheduler.is_some() {
+						let msg = "runtime can only have one `#[scheduler]` pallet";
+						return Err(syn::Error::new(ident.span(), msg))
+					}
+					scheduler = Some(ident.clone());
+				}
+				let type_ = field.ty;
+				pallets.push(PalletDef { name: ident, type_, index, runtime_dispatch, events })
 			}
 		}
 
-		Ok(Self { runtime_struct, pallets })
+		Ok(Self { runtime_struct, pallets, scheduler })
 	}
 }
 
@@ -65,3 +125,13 @@ fn check_system(item_struct: &syn::ItemStruct) -> syn::Result<()> {
 
 	Ok(())
 }
+
+/// Remove the attributes we parse on the `Runtime` fields, since they are not real attributes and
+/// would otherwise fail to compile when we return the original struct.
+pub fn strip_field_attrs(item: &mut syn::Item) {
+	if let syn::Item::Struct(item_struct) = item {
+		for field in item_struct.fields.iter_mut() {
+			field.attrs.retain(|attr| !FIELD_ATTRS.iter().any(|name| attr.path().is_ident(name)));
+		}
+	}
+}
@@ -0,0 +1,27 @@
+use super::parse::StorageDef;
+use quote::quote;
+
+/// See the `fn storage` docs at the `lib.rs` of this crate for a high level definition.
+pub fn expand_storage(def: StorageDef) -> proc_macro2::TokenStream {
+	let StorageDef { pallet_struct, generics, items } = def;
+	let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+	// This is a vector of the name of each storage item, which is part of the prefix of its keys.
+	let item_name = items.iter().map(|item| item.to_string()).collect::<Vec<_>>();
+
+	quote! {
+		impl #impl_generics crate::storage::PalletStorage for #pallet_struct #type_generics
+		#where_clause
+		{
+			fn storage_items(&self) -> Vec<(&'static str, &dyn crate::storage::StorageItem)> {
+				vec![ #( (#item_name, &self.#items) ),* ]
+			}
+
+			fn storage_items_mut(
+				&mut self,
+			) -> Vec<(&'static str, &mut dyn crate::storage::StorageItem)> {
+				vec![ #( (#item_name, &mut self.#items) ),* ]
+			}
+		}
+	}
+}
@@ -0,0 +1,54 @@
+use syn::spanned::Spanned;
+
+/// The types of storage items. Fields of the pallet struct with one of these types are part of the
+/// storage of the pallet.
+pub const STORAGE_TYPES: &[&str] = &["StorageValue", "StorageMap", "StorageDoubleMap"];
+
+/// This object will collect all the information we need to keep while parsing the storage items
+/// of a pallet.
+#[derive(Debug)]
+pub struct StorageDef {
+	/// This is the name of the pallet struct. We mostly assume it is `Pallet`.
+	pub pallet_struct: syn::Ident,
+	/// The generics of the pallet struct, usually `<T: Config>`.
+	pub generics: syn::Generics,
+	/// The names of the fields which are storage items, in the order they are declared.
+	pub items: Vec<syn::Ident>,
+}
+
+impl StorageDef {
+	pub fn try_from(item: syn::Item) -> syn::Result<Self> {
+		// First we check that we are parsing a `struct` with named fields.
+		let item_struct = if let syn::Item::Struct(item) = item {
+			item
+		} else {
+			let msg = "Invalid pallet::storage, expected item struct";
+			return Err(syn::Error::new(item.span(), msg))
+		};
+
+		let mut items = vec![];
+		for field in &item_struct.fields {
+			let Some(ident) = &field.ident else {
+				let msg = "Invalid pallet::storage, expected named fields";
+				return Err(syn::Error::new(field.span(), msg))
+			};
+			if is_storage_type(&field.ty) {
+				items.push(ident.clone());
+			}
+		}
+
+		Ok(Self { pallet_struct: item_struct.ident, generics: item_struct.generics, items })
+	}
+}
+
+/// Check whether `ty` is one of the `STORAGE_TYPES`, by the last segment of its path.
+fn is_storage_type(ty: &syn::Type) -> bool {
+	match ty {
+		syn::Type::Path(path) => path
+			.path
+			.segments
+			.last()
+			.is_some_and(|segment| STORAGE_TYPES.iter().any(|name| segment.ident == name)),
+		_ => false,
+	}
+}
@@ -0,0 +1,98 @@
+use quote::ToTokens;
+use syn::{spanned::Spanned, visit::Visit};
+
+/// The lines of the doc comments in `attrs`, without the space after `///`.
+pub fn doc_lines(attrs: &[syn::Attribute]) -> Vec<String> {
+	attrs
+		.iter()
+		.filter(|attr| attr.path().is_ident("doc"))
+		.filter_map(|attr| match &attr.meta {
+			syn::Meta::NameValue(syn::MetaNameValue {
+				value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }),
+				..
+			}) => Some(lit.value()),
+			_ => None,
+		})
+		.map(|line| line.strip_prefix(' ').map(str::to_string).unwrap_or(line))
+		.collect()
+}
+
+/// Parse the index given in the attribute `#[name(n)]`, which is required and can only be given
+/// once. Returns the index along with the attribute, to point errors at it.
+pub fn parse_index_attr<'a>(
+	attrs: &'a [syn::Attribute],
+	name: &str,
+	item: &impl Spanned,
+) -> syn::Result<(u8, &'a syn::Attribute)> {
+	let mut index = None;
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident(name)) {
+		if index.is_some() {
+			let msg = format!("duplicate `#[{name}]` attribute");
+			return Err(syn::Error::new(attr.span(), msg))
+		}
+		index = Some((attr.parse_args::<syn::LitInt>()?.base10_parse::<u8>()?, attr));
+	}
+	index.ok_or_else(|| syn::Error::new(item.span(), format!("missing `#[{name}(..)]` attribute")))
+}
+
+/// The type as it is written in the source, like `T::AccountId`, without the spaces `quote` puts
+/// between tokens. Spaces are only kept between two words, like in `&'static str`.
+pub fn type_string(ty: &syn::Type) -> String {
+	let tokens = ty.to_token_stream().to_string();
+	let is_word = |c: char| c.is_alphanumeric() || c == '_';
+	let mut result = String::new();
+	let mut chars = tokens.chars().peekable();
+	while let Some(c) = chars.next() {
+		if c == ' ' {
+			let before = result.chars().last().is_some_and(is_word);
+			let after = chars.peek().is_some_and(|c| is_word(*c));
+			if !(before && after) {
+				continue;
+			}
+		}
+		result.push(c);
+	}
+	result
+}
+
+/// Collects the string literals a function returns as errors, in the order they first appear:
+/// `Err("..")`, and the literal errors given to `ok_or("..")` and `ok_or_else(|| "..")`.
+///
+/// Errors returned by other functions the function calls are not found, since the macros only see
+/// the code they are applied to.
+#[derive(Default)]
+pub struct ErrorCollector {
+	pub errors: Vec<String>,
+}
+
+impl ErrorCollector {
+	fn collect(&mut self, expr: &syn::Expr) {
+		let expr = match expr {
+			syn::Expr::Closure(closure) => &closure.body,
+			expr => expr,
+		};
+		if let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) = expr {
+			if !self.errors.contains(&lit.value()) {
+				self.errors.push(lit.value());
+			}
+		}
+	}
+}
+
+impl<'ast> Visit<'ast> for ErrorCollector {
+	fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+		if let syn::Expr::Path(path) = &*call.func {
+			if path.path.is_ident("Err") && call.args.len() == 1 {
+				self.collect(&call.args[0]);
+			}
+		}
+		syn::visit::visit_expr_call(self, call);
+	}
+
+	fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+		if (call.method == "ok_or" || call.method == "ok_or_else") && call.args.len() == 1 {
+			self.collect(&call.args[0]);
+		}
+		syn::visit::visit_expr_method_call(self, call);
+	}
+}
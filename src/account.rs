@@ -0,0 +1,142 @@
+use crate::{
+	codec::{Decode, Encode},
+	metadata::{TypeDef, TypeInfo},
+};
+use num::BigUint;
+use sha2::{Digest, Sha512};
+use std::{fmt, str::FromStr};
+
+/// The prefix of addresses on this chain, which identifies the network they belong to.
+pub const ADDRESS_PREFIX: u8 = 42;
+
+/// The alphabet of base58, which leaves out characters that are easily confused: `0`, `O`, `I`
+/// and `l`.
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// A 32 byte account identifier, which is the public key of the account.
+///
+/// Accounts are displayed and parsed as SS58-style addresses: the base58 encoding of the address
+/// prefix, the 32 bytes of the account, and a two byte checksum.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AccountId32(pub [u8; 32]);
+
+impl AccountId32 {
+	/// The checksum of an address, which is the start of the SHA-512 hash of its prefix and
+	/// account, domain separated by `SS58PRE`.
+	fn checksum(prefix: u8, account: &[u8; 32]) -> [u8; 2] {
+		let hash =
+			Sha512::new().chain_update(b"SS58PRE").chain_update([prefix]).chain_update(account).finalize();
+		[hash[0], hash[1]]
+	}
+}
+
+impl fmt::Display for AccountId32 {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let mut bytes = vec![ADDRESS_PREFIX];
+		bytes.extend_from_slice(&self.0);
+		bytes.extend_from_slice(&Self::checksum(ADDRESS_PREFIX, &self.0));
+		f.write_str(&base58_encode(&bytes))
+	}
+}
+
+// Accounts are shown as addresses in the debug output of the runtime.
+impl fmt::Debug for AccountId32 {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, f)
+	}
+}
+
+impl FromStr for AccountId32 {
+	type Err = &'static str;
+
+	fn from_str(address: &str) -> Result<Self, Self::Err> {
+		let bytes = base58_decode(address)?;
+		let [prefix, rest @ ..] = bytes.as_slice() else { return Err("invalid address length") };
+		let Ok::<[u8; 34], _>(rest) = rest.try_into() else { return Err("invalid address length") };
+		if *prefix != ADDRESS_PREFIX {
+			return Err("address is for another network");
+		}
+		let account: [u8; 32] = rest[..32].try_into().expect("slice has 32 bytes");
+		if rest[32..] != Self::checksum(*prefix, &account) {
+			return Err("invalid address checksum");
+		}
+		Ok(Self(account))
+	}
+}
+
+impl Encode for AccountId32 {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.0.encode_to(dest);
+	}
+}
+
+impl Decode for AccountId32 {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self(Decode::decode_from(input)?))
+	}
+}
+
+impl TypeInfo for AccountId32 {
+	fn type_def() -> TypeDef {
+		TypeDef::AccountId
+	}
+}
+
+/// Encode bytes in base58. Each leading zero byte is encoded as a leading `1`.
+fn base58_encode(bytes: &[u8]) -> String {
+	let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+	let digits = match BigUint::from_bytes_be(bytes) {
+		n if n == BigUint::default() => Vec::new(),
+		n => n.to_radix_be(58),
+	};
+	core::iter::repeat_n(b'1', zeros)
+		.chain(digits.into_iter().map(|digit| BASE58_ALPHABET[digit as usize]))
+		.map(char::from)
+		.collect()
+}
+
+/// Decode base58 encoded bytes.
+fn base58_decode(encoded: &str) -> Result<Vec<u8>, &'static str> {
+	let digits = encoded
+		.bytes()
+		.map(|c| BASE58_ALPHABET.iter().position(|a| *a == c).map(|digit| digit as u8))
+		.collect::<Option<Vec<_>>>()
+		.ok_or("invalid base58 character")?;
+	let zeros = digits.iter().take_while(|digit| **digit == 0).count();
+	let mut bytes = vec![0; zeros];
+	if zeros < digits.len() {
+		let n = BigUint::from_radix_be(&digits, 58).expect("digits are below the radix");
+		bytes.extend(n.to_bytes_be());
+	}
+	Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+	use super::{base58_decode, base58_encode, AccountId32};
+
+	#[test]
+	fn base58_round_trip() {
+		assert_eq!(base58_encode(b"hello world"), "StV1DL6CwTryKyV");
+		assert_eq!(base58_encode(&[0, 0, 1]), "112");
+		assert_eq!(base58_decode("112"), Ok(vec![0, 0, 1]));
+		assert_eq!(base58_decode("StV1DL6CwTryKyV"), Ok(b"hello world".to_vec()));
+		assert_eq!(base58_decode("0OIl"), Err("invalid base58 character"));
+	}
+
+	#[test]
+	fn address_round_trip() {
+		let account = AccountId32([7; 32]);
+		let address = account.to_string();
+		assert!(address.starts_with('5'));
+		assert_eq!(address.parse(), Ok(account));
+
+		// Changing any character breaks the checksum.
+		let mut tampered = address.into_bytes();
+		tampered[10] = if tampered[10] == b'a' { b'b' } else { b'a' };
+		let tampered = String::from_utf8(tampered).unwrap();
+		assert_eq!(tampered.parse::<AccountId32>(), Err("invalid address checksum"));
+
+		assert_eq!("5".parse::<AccountId32>(), Err("invalid address length"));
+	}
+}
@@ -0,0 +1,309 @@
+use crate::codec::{Decode, Encode};
+use crate::storage::{StorageDoubleMap, StorageMap};
+use crate::support::DispatchResult;
+use core::fmt::Debug;
+use num::traits::{CheckedAdd, CheckedSub, Zero};
+
+pub trait Config: crate::system::Config {
+	/// The type used to identify an asset.
+	type AssetId: Ord + Copy + Debug + Encode + Decode;
+	/// The balance type of the assets. This is separate from the native `balances` currency.
+	type Balance: Zero + CheckedAdd + CheckedSub + Copy + Ord + Debug + Encode + Decode;
+}
+
+/// The details of an asset.
+#[derive(Debug, Clone)]
+pub struct AssetDetails<T: Config> {
+	/// The account which created the asset, and paid the deposit for it.
+	pub owner: T::AccountId,
+	/// The account which can mint, burn and freeze the asset.
+	pub admin: T::AccountId,
+	/// The number of decimals used to display balances of the asset.
+	pub decimals: u8,
+	/// The smallest non-zero balance an account can hold of the asset.
+	pub min_balance: T::Balance,
+	/// The total amount of the asset held by all accounts.
+	pub supply: T::Balance,
+}
+
+impl<T: Config> Encode for AssetDetails<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.owner.encode_to(dest);
+		self.admin.encode_to(dest);
+		self.decimals.encode_to(dest);
+		self.min_balance.encode_to(dest);
+		self.supply.encode_to(dest);
+	}
+}
+
+impl<T: Config> Decode for AssetDetails<T> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self {
+			owner: Decode::decode_from(input)?,
+			admin: Decode::decode_from(input)?,
+			decimals: Decode::decode_from(input)?,
+			min_balance: Decode::decode_from(input)?,
+			supply: Decode::decode_from(input)?,
+		})
+	}
+}
+
+/// This is the Assets Module.
+/// It allows any account to create assets alongside the native currency of the `balances` pallet.
+#[derive(Debug, Clone)]
+#[macros::storage]
+pub struct Pallet<T: Config> {
+	/// The details of each asset.
+	assets: StorageMap<T::AssetId, AssetDetails<T>>,
+	/// The balance of each account, for each asset.
+	accounts: StorageDoubleMap<T::AssetId, T::AccountId, T::Balance>,
+	/// The accounts which are frozen for each asset, and cannot transfer it. Frozen accounts map
+	/// to `true`.
+	frozen: StorageDoubleMap<T::AssetId, T::AccountId, bool>,
+}
+
+impl<T: Config> Pallet<T> {
+	/// Create a new instance of the Assets Module.
+	pub fn new() -> Self {
+		Self {
+			assets: StorageMap::new(),
+			accounts: StorageDoubleMap::new(),
+			frozen: StorageDoubleMap::new(),
+		}
+	}
+
+	/// Get the details of an asset, if it exists.
+	#[allow(dead_code, reason = "a query for users of the pallet, which `main` does not need")]
+	pub fn asset(&self, id: &T::AssetId) -> Option<AssetDetails<T>> {
+		self.assets.get(id)
+	}
+
+	/// Get the balance of `who` for the asset `id`.
+	pub fn balance(&self, id: T::AssetId, who: &T::AccountId) -> T::Balance {
+		self.accounts.get(&id, who).unwrap_or(T::Balance::zero())
+	}
+
+	/// Check whether `who` is frozen for the asset `id`.
+	pub fn is_frozen(&self, id: T::AssetId, who: &T::AccountId) -> bool {
+		self.frozen.contains_key(&id, who)
+	}
+
+	/// Get the details of an asset, checking that the `caller` is its admin.
+	fn admin_asset(
+		&self,
+		id: T::AssetId,
+		caller: &T::AccountId,
+	) -> Result<AssetDetails<T>, &'static str> {
+		let asset = self.assets.get(&id).ok_or("asset does not exist")?;
+		if asset.admin != *caller {
+			return Err("caller is not the asset admin");
+		}
+		Ok(asset)
+	}
+
+	/// Set the balance of `who` for the asset `id`, checking it is either zero or at least the
+	/// minimum balance of the asset.
+	fn set_balance(&mut self, id: T::AssetId, who: T::AccountId, amount: T::Balance) -> DispatchResult {
+		let min_balance = self.assets.get(&id).ok_or("asset does not exist")?.min_balance;
+		if amount.is_zero() {
+			self.accounts.remove(&id, &who);
+		} else if amount < min_balance {
+			return Err("balance would be below the minimum balance");
+		} else {
+			self.accounts.insert(&id, &who, &amount);
+		}
+		Ok(())
+	}
+}
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+	/// Create a new asset `id` owned by the `caller`, administered by `admin`. The runtime reserves
+	/// a deposit from the `caller` for it.
+	#[call_index(0)]
+	pub fn create(
+		&mut self,
+		caller: T::AccountId,
+		id: T::AssetId,
+		admin: T::AccountId,
+		decimals: u8,
+		min_balance: T::Balance,
+	) -> DispatchResult {
+		if self.assets.contains_key(&id) {
+			return Err("asset already exists");
+		}
+		if min_balance.is_zero() {
+			return Err("minimum balance must be greater than zero");
+		}
+		let asset = AssetDetails { owner: caller, admin, decimals, min_balance, supply: T::Balance::zero() };
+		self.assets.insert(&id, &asset);
+		Ok(())
+	}
+
+	/// Destroy the asset `id`, which must be owned by the `caller` and have no supply left. The
+	/// runtime returns the deposit to the `caller`.
+	#[call_index(1)]
+	pub fn destroy(&mut self, caller: T::AccountId, id: T::AssetId) -> DispatchResult {
+		let asset = self.assets.get(&id).ok_or("asset does not exist")?;
+		if asset.owner != caller {
+			return Err("caller is not the asset owner");
+		}
+		if !asset.supply.is_zero() {
+			return Err("asset still has supply");
+		}
+		self.assets.remove(&id);
+		self.frozen.remove_prefix(&id);
+		Ok(())
+	}
+
+	/// Mint `amount` of the asset `id` to `beneficiary`. Only the admin of the asset can mint.
+	#[call_index(2)]
+	pub fn mint(
+		&mut self,
+		caller: T::AccountId,
+		id: T::AssetId,
+		beneficiary: T::AccountId,
+		amount: T::Balance,
+	) -> DispatchResult {
+		let mut asset = self.admin_asset(id, &caller)?;
+		asset.supply = asset.supply.checked_add(&amount).ok_or("Overflow")?;
+		let new_balance = self.balance(id, &beneficiary).checked_add(&amount).ok_or("Overflow")?;
+		self.set_balance(id, beneficiary, new_balance)?;
+		self.assets.insert(&id, &asset);
+		Ok(())
+	}
+
+	/// Burn `amount` of the asset `id` from `who`. Only the admin of the asset can burn.
+	#[call_index(3)]
+	pub fn burn(
+		&mut self,
+		caller: T::AccountId,
+		id: T::AssetId,
+		who: T::AccountId,
+		amount: T::Balance,
+	) -> DispatchResult {
+		let mut asset = self.admin_asset(id, &caller)?;
+		asset.supply = asset.supply.checked_sub(&amount).ok_or("Overflow")?;
+		let new_balance = self.balance(id, &who).checked_sub(&amount).ok_or("Not enough funds.")?;
+		self.set_balance(id, who, new_balance)?;
+		self.assets.insert(&id, &asset);
+		Ok(())
+	}
+
+	/// Transfer `amount` of the asset `id` from the `caller` to `to`.
+	#[call_index(4)]
+	pub fn transfer(
+		&mut self,
+		caller: T::AccountId,
+		id: T::AssetId,
+		to: T::AccountId,
+		amount: T::Balance,
+	) -> DispatchResult {
+		if self.is_frozen(id, &caller) {
+			return Err("account is frozen");
+		}
+		// A transfer to the caller moves nothing, and would otherwise credit the old balance.
+		if to == caller {
+			return Ok(());
+		}
+		let new_caller_balance =
+			self.balance(id, &caller).checked_sub(&amount).ok_or("Not enough funds.")?;
+		let new_to_balance = self.balance(id, &to).checked_add(&amount).ok_or("Overflow")?;
+		let min_balance = self.assets.get(&id).ok_or("asset does not exist")?.min_balance;
+		if !new_caller_balance.is_zero() && new_caller_balance < min_balance ||
+			new_to_balance < min_balance
+		{
+			return Err("balance would be below the minimum balance");
+		}
+		self.set_balance(id, caller, new_caller_balance)?;
+		self.set_balance(id, to, new_to_balance)?;
+		Ok(())
+	}
+
+	/// Freeze `who` for the asset `id`, so it cannot transfer it. Only the admin of the asset can
+	/// freeze accounts.
+	#[call_index(5)]
+	pub fn freeze(&mut self, caller: T::AccountId, id: T::AssetId, who: T::AccountId) -> DispatchResult {
+		self.admin_asset(id, &caller)?;
+		self.frozen.insert(&id, &who, &true);
+		Ok(())
+	}
+
+	/// Thaw `who` for the asset `id`, so it can transfer it again. Only the admin of the asset can
+	/// thaw accounts.
+	#[call_index(6)]
+	pub fn thaw(&mut self, caller: T::AccountId, id: T::AssetId, who: T::AccountId) -> DispatchResult {
+		self.admin_asset(id, &caller)?;
+		if self.frozen.remove(&id, &who).is_none() {
+			return Err("account is not frozen");
+		}
+		Ok(())
+	}
+}
+
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {}
+
+#[cfg(test)]
+mod test {
+	struct TestConfig;
+
+	impl crate::system::Config for TestConfig {
+		type AccountId = String;
+		type BlockNumber = u32;
+		type Nonce = u32;
+		type Signature = crate::ed25519::Signature;
+	}
+
+	impl super::Config for TestConfig {
+		type AssetId = u32;
+		type Balance = u128;
+	}
+
+	#[test]
+	fn create_mint_and_transfer() {
+		let (alice, bob, admin) = ("alice".to_string(), "bob".to_string(), "admin".to_string());
+		let mut assets = super::Pallet::<TestConfig>::new();
+		assert_eq!(
+			assets.create(alice.clone(), 1, admin.clone(), 6, 0),
+			Err("minimum balance must be greater than zero")
+		);
+		assert_eq!(assets.create(alice.clone(), 1, admin.clone(), 6, 10), Ok(()));
+		assert_eq!(assets.create(bob.clone(), 1, bob.clone(), 6, 10), Err("asset already exists"));
+
+		assert_eq!(
+			assets.mint(alice.clone(), 1, alice.clone(), 100),
+			Err("caller is not the asset admin")
+		);
+		assert_eq!(
+			assets.mint(admin.clone(), 1, alice.clone(), 5),
+			Err("balance would be below the minimum balance")
+		);
+		assert_eq!(assets.mint(admin.clone(), 1, alice.clone(), 100), Ok(()));
+		assert_eq!(assets.asset(&1).unwrap().supply, 100);
+
+		assert_eq!(
+			assets.transfer(alice.clone(), 1, bob.clone(), 95),
+			Err("balance would be below the minimum balance")
+		);
+		assert_eq!(assets.transfer(alice.clone(), 1, bob.clone(), 40), Ok(()));
+		assert_eq!(assets.balance(1, &alice), 60);
+		assert_eq!(assets.balance(1, &bob), 40);
+
+		// A transfer to self moves nothing, and mints nothing.
+		assert_eq!(assets.transfer(alice.clone(), 1, alice.clone(), 60), Ok(()));
+		assert_eq!(assets.balance(1, &alice), 60);
+		assert_eq!(assets.asset(&1).unwrap().supply, 100);
+
+		assert_eq!(assets.freeze(admin.clone(), 1, bob.clone()), Ok(()));
+		assert_eq!(assets.transfer(bob.clone(), 1, alice.clone(), 10), Err("account is frozen"));
+		assert_eq!(assets.thaw(admin.clone(), 1, bob.clone()), Ok(()));
+		assert_eq!(assets.transfer(bob.clone(), 1, alice.clone(), 40), Ok(()));
+		assert_eq!(assets.balance(1, &bob), 0);
+
+		assert_eq!(assets.destroy(alice.clone(), 1), Err("asset still has supply"));
+		assert_eq!(assets.burn(admin.clone(), 1, alice.clone(), 100), Ok(()));
+		assert_eq!(assets.destroy(admin, 1), Err("caller is not the asset owner"));
+		assert_eq!(assets.destroy(alice, 1), Ok(()));
+		assert!(assets.asset(&1).is_none());
+	}
+}
@@ -0,0 +1,188 @@
+use crate::codec::{Decode, Encode};
+use crate::storage::StorageValue;
+use crate::support::DispatchResult;
+use core::fmt::Debug;
+
+pub trait Config: crate::system::Config<BlockNumber: Into<u64>> {
+	/// The type which identifies an authority, which is the public key it seals blocks with.
+	type AuthorityId: Clone + PartialEq + Debug + Encode + Decode;
+	/// The number of blocks in a session. Changes to the authority set take effect at the start of
+	/// the next session.
+	const SESSION_LENGTH: u64;
+}
+
+/// This is the Authorities Module.
+/// It holds the set of authorities which may author blocks. Each block has a slot author, chosen
+/// from the authorities round-robin by block number, who must seal it.
+///
+/// The governor can add and remove authorities. Changes are queued, and take effect at the start of
+/// the next session, so the authors of the blocks in a session are known when it starts.
+#[derive(Debug, Clone)]
+#[macros::storage]
+pub struct Pallet<T: Config> {
+	/// The authorities of the current session, in the order of their slots.
+	authorities: StorageValue<Vec<T::AuthorityId>>,
+	/// The authorities of the next session, if they have been changed.
+	queued: StorageValue<Vec<T::AuthorityId>>,
+	/// The account allowed to change the authorities.
+	governor: StorageValue<T::AccountId>,
+}
+
+impl<T: Config> Pallet<T> {
+	/// Create a new instance of the Authorities Module.
+	pub fn new() -> Self {
+		Self {
+			authorities: StorageValue::new(),
+			queued: StorageValue::new(),
+			governor: StorageValue::new(),
+		}
+	}
+
+	/// Get the authorities of the current session.
+	pub fn authorities(&self) -> Vec<T::AuthorityId> {
+		self.authorities.get().unwrap_or_default()
+	}
+
+	/// Get the authorities of the next session, if they have been changed.
+	pub fn queued(&self) -> Option<Vec<T::AuthorityId>> {
+		self.queued.get()
+	}
+
+	/// Set the authorities immediately. This is meant for the genesis state.
+	pub fn set_authorities(&mut self, authorities: Vec<T::AuthorityId>) {
+		self.authorities.put(&authorities);
+	}
+
+	/// Set the account allowed to change the authorities. This is meant for the genesis state.
+	pub fn set_governor(&mut self, governor: T::AccountId) {
+		self.governor.put(&governor);
+	}
+
+	/// Get the authority which must seal block `block_number`, if there are any authorities.
+	pub fn slot_author(&self, block_number: T::BlockNumber) -> Option<T::AuthorityId> {
+		let mut authorities = self.authorities();
+		let len = authorities.len() as u64;
+		if len == 0 {
+			return None;
+		}
+		Some(authorities.swap_remove((block_number.into() % len) as usize))
+	}
+
+	/// Get the authorities of the next session for the governor to change, starting from the
+	/// current authorities if none are queued.
+	fn queued_for(&self, caller: &T::AccountId) -> Result<Vec<T::AuthorityId>, &'static str> {
+		if self.governor.get().as_ref() != Some(caller) {
+			return Err("caller is not the governor");
+		}
+		Ok(self.queued().unwrap_or_else(|| self.authorities()))
+	}
+}
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+	/// Add `authority` to the authorities from the next session. Only the governor can do this.
+	#[call_index(0)]
+	pub fn add_authority(
+		&mut self,
+		caller: T::AccountId,
+		authority: T::AuthorityId,
+	) -> DispatchResult {
+		let mut queued = self.queued_for(&caller)?;
+		if queued.contains(&authority) {
+			return Err("already an authority");
+		}
+		queued.push(authority);
+		self.queued.put(&queued);
+		Ok(())
+	}
+
+	/// Remove `authority` from the authorities from the next session. Only the governor can do
+	/// this, and there must be at least one authority left.
+	#[call_index(1)]
+	pub fn remove_authority(
+		&mut self,
+		caller: T::AccountId,
+		authority: T::AuthorityId,
+	) -> DispatchResult {
+		let mut queued = self.queued_for(&caller)?;
+		let index = queued.iter().position(|a| *a == authority).ok_or("not an authority")?;
+		if queued.len() == 1 {
+			return Err("cannot remove the last authority");
+		}
+		queued.remove(index);
+		self.queued.put(&queued);
+		Ok(())
+	}
+}
+
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {
+	/// At the end of the last block of a session, the queued authorities take over.
+	fn on_finalize(&mut self, block_number: T::BlockNumber) {
+		if (block_number.into() + 1) % T::SESSION_LENGTH == 0 {
+			if let Some(queued) = self.queued.take() {
+				self.authorities.put(&queued);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::support::Hooks;
+
+	struct TestConfig;
+
+	impl crate::system::Config for TestConfig {
+		type AccountId = String;
+		type BlockNumber = u32;
+		type Nonce = u32;
+		type Signature = crate::ed25519::Signature;
+	}
+
+	impl super::Config for TestConfig {
+		type AuthorityId = String;
+		const SESSION_LENGTH: u64 = 4;
+	}
+
+	#[test]
+	fn authority_changes_take_effect_next_session() {
+		let (alice, bob, charlie) = ("alice".to_string(), "bob".to_string(), "charlie".to_string());
+		let root = "root".to_string();
+		let mut authorities = super::Pallet::<TestConfig>::new();
+		assert_eq!(authorities.slot_author(1), None);
+		authorities.set_authorities(vec![alice.clone(), bob.clone()]);
+		authorities.set_governor(root.clone());
+		assert_eq!(authorities.slot_author(1), Some(bob.clone()));
+		assert_eq!(authorities.slot_author(2), Some(alice.clone()));
+
+		assert_eq!(
+			authorities.add_authority(alice.clone(), charlie.clone()),
+			Err("caller is not the governor")
+		);
+		assert_eq!(authorities.add_authority(root.clone(), charlie.clone()), Ok(()));
+		assert_eq!(
+			authorities.add_authority(root.clone(), charlie.clone()),
+			Err("already an authority")
+		);
+		assert_eq!(
+			authorities.remove_authority(root.clone(), "dave".to_string()),
+			Err("not an authority")
+		);
+		assert_eq!(authorities.remove_authority(root.clone(), alice.clone()), Ok(()));
+		assert_eq!(authorities.queued(), Some(vec![bob.clone(), charlie.clone()]));
+
+		// Nothing changes until the last block of the session is finalized.
+		authorities.on_finalize(2);
+		assert_eq!(authorities.authorities(), [alice, bob.clone()]);
+		authorities.on_finalize(3);
+		assert_eq!(authorities.authorities(), [bob.clone(), charlie.clone()]);
+		assert_eq!(authorities.queued(), None);
+		assert_eq!(authorities.slot_author(4), Some(bob.clone()));
+
+		assert_eq!(authorities.remove_authority(root.clone(), bob), Ok(()));
+		assert_eq!(
+			authorities.remove_authority(root, charlie),
+			Err("cannot remove the last authority")
+		);
+	}
+}
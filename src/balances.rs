@@ -1,29 +1,239 @@
-use std::collections::BTreeMap;
-use num::traits::{CheckedAdd, CheckedSub, Zero};
+use crate::codec::{Decode, Encode};
+use crate::storage::{StorageDoubleMap, StorageMap, StorageValue};
+use num::traits::{CheckedAdd, CheckedMul, CheckedSub, Zero};
 
 pub trait Config: crate::system::Config {
 	//type AccountId: Ord + Clone;
-	type Balance: Zero + CheckedAdd + CheckedSub + Copy;
+	/// The balance type. Vesting schedules unlock an amount per block, so we need to be able to
+	/// convert a number of blocks into a balance.
+	type Balance: Zero
+		+ CheckedAdd
+		+ CheckedSub
+		+ CheckedMul
+		+ Copy
+		+ Ord
+		+ From<Self::BlockNumber>
+		+ Encode
+		+ Decode;
 }
 
-#[derive(Debug)]
+/// An identifier for a lock. Each lock on an account is kept separately, and the largest one
+/// decides how much of the account's balance cannot be moved.
+pub type LockIdentifier = [u8; 8];
+
+/// The lock used for vesting schedules.
+pub const VESTING_ID: LockIdentifier = *b"vesting ";
+
+/// A vesting schedule: `locked` is unlocked linearly by `per_block` every block after
+/// `starting_block`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VestingInfo<Balance, BlockNumber> {
+	/// The amount which is locked at `starting_block`.
+	pub locked: Balance,
+	/// The amount unlocked every block after `starting_block`.
+	pub per_block: Balance,
+	/// The block number unlocking starts at. Nothing is unlocked before this block, which acts as
+	/// a cliff.
+	pub starting_block: BlockNumber,
+}
+
+impl<Balance, BlockNumber> VestingInfo<Balance, BlockNumber>
+where
+	Balance: Zero + CheckedSub + CheckedMul + Copy + From<BlockNumber>,
+	BlockNumber: CheckedSub + Copy,
+{
+	/// The amount still locked at block `now`.
+	pub fn locked_at(&self, now: BlockNumber) -> Balance {
+		let elapsed = match now.checked_sub(&self.starting_block) {
+			Some(elapsed) => Balance::from(elapsed),
+			None => return self.locked,
+		};
+		self.per_block
+			.checked_mul(&elapsed)
+			.and_then(|unlocked| self.locked.checked_sub(&unlocked))
+			.unwrap_or(Balance::zero())
+	}
+}
+
+impl<Balance: Encode, BlockNumber: Encode> Encode for VestingInfo<Balance, BlockNumber> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.locked.encode_to(dest);
+		self.per_block.encode_to(dest);
+		self.starting_block.encode_to(dest);
+	}
+}
+
+impl<Balance: Decode, BlockNumber: Decode> Decode for VestingInfo<Balance, BlockNumber> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self {
+			locked: Decode::decode_from(input)?,
+			per_block: Decode::decode_from(input)?,
+			starting_block: Decode::decode_from(input)?,
+		})
+	}
+}
+
+/// The events emitted by this pallet.
+#[derive(Debug, Clone)]
+#[macros::event]
+pub enum Event<T: Config> {
+	/// `amount` was transferred from `from` to `to`.
+	Transfer { from: T::AccountId, to: T::AccountId, amount: T::Balance },
+	/// `owner` allowed `spender` to transfer up to `amount` on their behalf.
+	Approval { owner: T::AccountId, spender: T::AccountId, amount: T::Balance },
+}
+
+// Events are kept in storage, so that the events of a failed call are rolled back with it.
+impl<T: Config> Encode for Event<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		match self {
+			Event::Transfer { from, to, amount } => {
+				0u8.encode_to(dest);
+				from.encode_to(dest);
+				to.encode_to(dest);
+				amount.encode_to(dest);
+			},
+			Event::Approval { owner, spender, amount } => {
+				1u8.encode_to(dest);
+				owner.encode_to(dest);
+				spender.encode_to(dest);
+				amount.encode_to(dest);
+			},
+		}
+	}
+}
+
+impl<T: Config> Decode for Event<T> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		match u8::decode_from(input)? {
+			0 => Ok(Event::Transfer {
+				from: Decode::decode_from(input)?,
+				to: Decode::decode_from(input)?,
+				amount: Decode::decode_from(input)?,
+			}),
+			1 => Ok(Event::Approval {
+				owner: Decode::decode_from(input)?,
+				spender: Decode::decode_from(input)?,
+				amount: Decode::decode_from(input)?,
+			}),
+			_ => Err("invalid balances event"),
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+#[macros::storage]
 pub struct Pallet<T: Config> {
-	balances: BTreeMap<T:: AccountId, T::Balance>,
+	balances: StorageMap<T:: AccountId, T::Balance>,
+	/// The amount each spender is allowed to transfer on behalf of each owner, keyed by
+	/// `(owner, spender)`.
+	allowances: StorageDoubleMap<T::AccountId, T::AccountId, T::Balance>,
+	/// The balance reserved by each account, for example as a deposit. Reserved funds are not
+	/// part of the free balance returned by `balance`.
+	reserved: StorageMap<T::AccountId, T::Balance>,
+	/// The locks on each account's balance, keyed by `(account, lock)`.
+	locks: StorageDoubleMap<T::AccountId, LockIdentifier, T::Balance>,
+	/// The vesting schedule of each account.
+	vesting: StorageMap<T::AccountId, VestingInfo<T::Balance, T::BlockNumber>>,
+	/// The current block number, which vesting schedules are checked against.
+	block_number: StorageValue<T::BlockNumber>,
+	/// The events emitted in the current block.
+	events: StorageValue<Vec<Event<T>>>,
 }
 
 impl <T: Config> Pallet<T> {
 	pub fn new() -> Self {
 		Self {
-			balances: BTreeMap::new()
+			balances: StorageMap::new(),
+			allowances: StorageDoubleMap::new(),
+			reserved: StorageMap::new(),
+			locks: StorageDoubleMap::new(),
+			vesting: StorageMap::new(),
+			block_number: StorageValue::new(),
+			events: StorageValue::new(),
 		}
 	}
 
 	pub fn set_balance(&mut self, who: &T::AccountId, amount: T::Balance) {
-		self.balances.insert(who.clone(), amount);
+		self.balances.insert(who, &amount);
 	}
 
 	pub fn balance(&self, who: &T::AccountId) -> T::Balance {
-		*self.balances.get(who).unwrap_or(&T::Balance::zero())
+		self.balances.get(who).unwrap_or(T::Balance::zero())
+	}
+
+	/// The amount `spender` is allowed to transfer on behalf of `owner`.
+	pub fn allowance(&self, owner: &T::AccountId, spender: &T::AccountId) -> T::Balance {
+		self.allowances.get(owner, spender).unwrap_or(T::Balance::zero())
+	}
+
+	/// The events emitted by this pallet in the current block.
+	pub fn events(&self) -> Vec<Event<T>> {
+		self.events.get().unwrap_or_default()
+	}
+
+	/// Add `event` to the events of the current block.
+	fn deposit_event(&mut self, event: Event<T>) {
+		let mut events = self.events();
+		events.push(event);
+		self.events.put(&events);
+	}
+
+	/// The current block number, which vesting schedules are checked against.
+	fn block_number(&self) -> T::BlockNumber {
+		self.block_number.get().unwrap_or(T::BlockNumber::zero())
+	}
+
+	/// The amount of `who`'s balance which is reserved.
+	pub fn reserved_balance(&self, who: &T::AccountId) -> T::Balance {
+		self.reserved.get(who).unwrap_or(T::Balance::zero())
+	}
+
+	/// Move `amount` from the free balance of `who` to its reserved balance. Fails if `who` does
+	/// not have enough funds which are not locked.
+	pub fn reserve(&mut self, who: &T::AccountId, amount: T::Balance) -> crate::support::DispatchResult {
+		let new_balance = self.balance(who).checked_sub(&amount).ok_or("Not enough funds.")?;
+		if new_balance < self.locked(who) {
+			return Err("Funds are locked.");
+		}
+		let new_reserved = self.reserved_balance(who).checked_add(&amount).ok_or("Overflow")?;
+		self.balances.insert(who, &new_balance);
+		self.reserved.insert(who, &new_reserved);
+		Ok(())
+	}
+
+	/// Move `amount` from the reserved balance of `who` back to its free balance.
+	pub fn unreserve(&mut self, who: &T::AccountId, amount: T::Balance) -> crate::support::DispatchResult {
+		let new_reserved = self.reserved_balance(who).checked_sub(&amount).ok_or("Not enough reserved.")?;
+		let new_balance = self.balance(who).checked_add(&amount).ok_or("Overflow")?;
+		if new_reserved.is_zero() {
+			self.reserved.remove(who);
+		} else {
+			self.reserved.insert(who, &new_reserved);
+		}
+		self.balances.insert(who, &new_balance);
+		Ok(())
+	}
+
+	/// Lock `amount` of the balance of `who` under `id`, replacing any existing lock with that id.
+	/// Locked funds cannot be moved out of the account.
+	pub fn set_lock(&mut self, id: LockIdentifier, who: &T::AccountId, amount: T::Balance) {
+		self.locks.insert(who, &id, &amount);
+	}
+
+	/// Remove the lock `id` from the balance of `who`.
+	pub fn remove_lock(&mut self, id: LockIdentifier, who: &T::AccountId) {
+		self.locks.remove(who, &id);
+	}
+
+	/// The amount of the balance of `who` which cannot be moved: the largest of its locks.
+	pub fn locked(&self, who: &T::AccountId) -> T::Balance {
+		self.locks.iter_prefix(who).map(|(_, amount)| amount).max().unwrap_or(T::Balance::zero())
+	}
+
+	/// The vesting schedule of `who`, if any.
+	pub fn vesting(&self, who: &T::AccountId) -> Option<VestingInfo<T::Balance, T::BlockNumber>> {
+		self.vesting.get(who)
 	}
 }
 
@@ -32,6 +242,7 @@ impl<T: Config> Pallet<T> {
     /// Transfer `amount` from one account to another.
     /// This function verifies that `from` has at least `amount` balance to transfer,
     /// and that no mathematical overflows occur.
+    #[call_index(0)]
     pub fn transfer(
         &mut self,
         caller: T::AccountId,
@@ -42,11 +253,103 @@ impl<T: Config> Pallet<T> {
         let to_balance = self.balance(&to);
 
         let new_caller_balance = caller_balance.checked_sub(&amount).ok_or("Not enough funds.")?;
+        if new_caller_balance < self.locked(&caller) {
+            return Err("Funds are locked.");
+        }
+        // A transfer to the caller moves nothing, and would otherwise credit the old balance.
+        if to == caller {
+            return Ok(());
+        }
         let new_to_balance = to_balance.checked_add(&amount).ok_or("Overflow")?;
 
-        self.balances.insert(caller, new_caller_balance);
-        self.balances.insert(to, new_to_balance);
+        self.balances.insert(&caller, &new_caller_balance);
+        self.balances.insert(&to, &new_to_balance);
+        self.deposit_event(Event::Transfer { from: caller, to, amount });
+
+        Ok(())
+    }
+
+    /// Allow `spender` to transfer up to `amount` on behalf of the `caller`, replacing any
+    /// existing allowance.
+    #[call_index(1)]
+    pub fn approve(
+        &mut self,
+        caller: T::AccountId,
+        spender: T::AccountId,
+        amount: T::Balance,
+    ) -> crate::support::DispatchResult {
+        if amount.is_zero() {
+            self.allowances.remove(&caller, &spender);
+        } else {
+            self.allowances.insert(&caller, &spender, &amount);
+        }
+        self.deposit_event(Event::Approval { owner: caller, spender, amount });
+        Ok(())
+    }
+
+    /// Transfer `amount` from `owner` to `to`, spending from the allowance `owner` gave the
+    /// `caller`. Transferring back to the `owner` is rejected, since it would spend the allowance
+    /// without moving anything.
+    #[call_index(2)]
+    pub fn transfer_from(
+        &mut self,
+        caller: T::AccountId,
+        owner: T::AccountId,
+        to: T::AccountId,
+        amount: T::Balance,
+    ) -> crate::support::DispatchResult {
+        if owner == to {
+            return Err("Cannot transfer to the owner.");
+        }
+        let allowance = self.allowance(&owner, &caller);
+        let new_allowance = allowance.checked_sub(&amount).ok_or("Allowance exceeded.")?;
+        self.transfer(owner.clone(), to, amount)?;
+
+        if new_allowance.is_zero() {
+            self.allowances.remove(&owner, &caller);
+        } else {
+            self.allowances.insert(&owner, &caller, &new_allowance);
+        }
+        Ok(())
+    }
+
+    /// Unlock whatever the vesting schedule of the `caller` has unlocked so far. Once the whole
+    /// amount is unlocked, the schedule is removed.
+    #[call_index(3)]
+    pub fn vest(&mut self, caller: T::AccountId) -> crate::support::DispatchResult {
+        let schedule = self.vesting(&caller).ok_or("No vesting schedule.")?;
+        let locked = schedule.locked_at(self.block_number());
+        if locked.is_zero() {
+            self.vesting.remove(&caller);
+            self.remove_lock(VESTING_ID, &caller);
+        } else {
+            self.set_lock(VESTING_ID, &caller, locked);
+        }
+        Ok(())
+    }
+
+    /// Transfer `locked` to `to`, locked under a vesting schedule which unlocks `per_block` every
+    /// block after `starting_block`.
+    #[call_index(4)]
+    pub fn vested_transfer(
+        &mut self,
+        caller: T::AccountId,
+        to: T::AccountId,
+        locked: T::Balance,
+        per_block: T::Balance,
+        starting_block: T::BlockNumber,
+    ) -> crate::support::DispatchResult {
+        if per_block.is_zero() {
+            return Err("Vesting must unlock something each block.");
+        }
+        if self.vesting.contains_key(&to) {
+            return Err("Account already has a vesting schedule.");
+        }
+        self.transfer(caller, to.clone(), locked)?;
 
+        let schedule = VestingInfo { locked, per_block, starting_block };
+        self.set_lock(VESTING_ID, &to, schedule.locked_at(self.block_number()));
+        self.vesting.insert(&to, &schedule);
         Ok(())
     }
 }
@@ -74,8 +377,18 @@ impl<T: Config> Pallet<T> {
 //	}
 //}
 
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {
+	/// Keep track of the current block number, which vesting schedules are checked against.
+	/// Also clears the events of the previous block.
+	fn on_initialize(&mut self, block_number: T::BlockNumber) {
+		self.block_number.put(&block_number);
+		self.events.kill();
+	}
+}
+
 #[cfg(test)]
 mod test {
+	use crate::support::Hooks;
 
 	struct TestConfig;
 
@@ -83,6 +396,7 @@ mod test {
 		type AccountId = String;
         type BlockNumber = u32;
         type Nonce = u32;
+        type Signature = crate::ed25519::Signature;
 	}
 
 	impl super::Config for TestConfig {
@@ -118,4 +432,127 @@ mod test {
 		assert_eq!(balances.balance(&foo), 50);
 		assert_eq!(balances.balance(&bar), 50);
 	}
+
+	#[test]
+	fn vested_transfer_unlocks_linearly() {
+		let foo = "foo".to_string();
+		let bar = "bar".to_string();
+
+		let mut balances = super::Pallet::<TestConfig>::new();
+		balances.set_balance(&foo, 100);
+
+		// 50 locked, unlocking 10 per block after block 2.
+		assert_eq!(balances.vested_transfer(foo.clone(), bar.clone(), 50, 10, 2), Ok(()));
+		assert_eq!(balances.balance(&bar), 50);
+		assert_eq!(balances.locked(&bar), 50);
+		assert_eq!(balances.transfer(bar.clone(), foo.clone(), 1), Err("Funds are locked."));
+		assert_eq!(
+			balances.vested_transfer(foo.clone(), bar.clone(), 10, 1, 0),
+			Err("Account already has a vesting schedule.")
+		);
+
+		balances.on_initialize(2);
+		assert_eq!(balances.vest(bar.clone()), Ok(()));
+		assert_eq!(balances.locked(&bar), 50);
+
+		balances.on_initialize(4);
+		assert_eq!(balances.vest(bar.clone()), Ok(()));
+		assert_eq!(balances.locked(&bar), 30);
+		assert_eq!(balances.transfer(bar.clone(), foo.clone(), 20), Ok(()));
+		assert_eq!(balances.transfer(bar.clone(), foo.clone(), 1), Err("Funds are locked."));
+
+		balances.on_initialize(7);
+		assert_eq!(balances.vest(bar.clone()), Ok(()));
+		assert_eq!(balances.locked(&bar), 0);
+		assert!(balances.vesting(&bar).is_none());
+		assert_eq!(balances.vest(bar.clone()), Err("No vesting schedule."));
+	}
+
+	#[test]
+	fn approve_and_transfer_from() {
+		let foo = "foo".to_string();
+		let bar = "bar".to_string();
+		let baz = "baz".to_string();
+
+		let mut balances = super::Pallet::<TestConfig>::new();
+		balances.set_balance(&foo, 100);
+
+		assert_eq!(balances.transfer_from(bar.clone(), foo.clone(), baz.clone(), 10), Err("Allowance exceeded."));
+		assert_eq!(balances.approve(foo.clone(), bar.clone(), 30), Ok(()));
+		assert_eq!(balances.allowance(&foo, &bar), 30);
+		assert!(matches!(
+			balances.events().last(),
+			Some(super::Event::Approval { amount: 30, .. })
+		));
+
+		assert_eq!(balances.transfer_from(bar.clone(), foo.clone(), baz.clone(), 20), Ok(()));
+		assert_eq!(balances.allowance(&foo, &bar), 10);
+		assert_eq!(balances.balance(&foo), 80);
+		assert_eq!(balances.balance(&baz), 20);
+		assert!(matches!(
+			balances.events().last(),
+			Some(super::Event::Transfer { amount: 20, .. })
+		));
+
+		assert_eq!(balances.transfer_from(bar.clone(), foo.clone(), baz.clone(), 11), Err("Allowance exceeded."));
+		assert_eq!(balances.transfer_from(bar.clone(), foo.clone(), baz.clone(), 10), Ok(()));
+		assert_eq!(balances.allowance(&foo, &bar), 0);
+
+		// Transferring back to the owner would spend the allowance without moving anything.
+		assert_eq!(balances.approve(foo.clone(), bar.clone(), 30), Ok(()));
+		assert_eq!(
+			balances.transfer_from(bar.clone(), foo.clone(), foo.clone(), 20),
+			Err("Cannot transfer to the owner.")
+		);
+		assert_eq!(balances.balance(&foo), 70);
+		assert_eq!(balances.allowance(&foo, &bar), 30);
+	}
+
+	#[test]
+	fn rolled_back_calls_leave_no_events() {
+		use crate::storage::{PalletStorage, SharedStorage};
+		let foo = "foo".to_string();
+		let bar = "bar".to_string();
+
+		let mut balances = super::Pallet::<TestConfig>::new();
+		let storage = SharedStorage::default();
+		balances.attach_storage("balances", &storage);
+		balances.set_balance(&foo, 100);
+
+		// The runtime rolls back the changes of a failed call, including the events it emitted.
+		storage.start_transaction();
+		assert_eq!(balances.approve(foo.clone(), bar.clone(), 30), Ok(()));
+		assert_eq!(balances.transfer(foo.clone(), bar.clone(), 101), Err("Not enough funds."));
+		storage.rollback_transaction();
+		assert!(balances.events().is_empty());
+		assert_eq!(balances.allowance(&foo, &bar), 0);
+	}
+
+	#[test]
+	fn transfer_to_self_moves_nothing() {
+		let foo = "foo".to_string();
+		let mut balances = super::Pallet::<TestConfig>::new();
+		balances.set_balance(&foo, 100);
+
+		assert_eq!(balances.transfer(foo.clone(), foo.clone(), 40), Ok(()));
+		assert_eq!(balances.balance(&foo), 100);
+		assert_eq!(balances.transfer(foo.clone(), foo.clone(), 101), Err("Not enough funds."));
+	}
+
+	#[test]
+	fn reserve_and_unreserve() {
+		let foo = "foo".to_string();
+		let mut balances = super::Pallet::<TestConfig>::new();
+		balances.set_balance(&foo, 100);
+
+		assert_eq!(balances.reserve(&foo, 101), Err("Not enough funds."));
+		assert_eq!(balances.reserve(&foo, 40), Ok(()));
+		assert_eq!(balances.balance(&foo), 60);
+		assert_eq!(balances.reserved_balance(&foo), 40);
+
+		assert_eq!(balances.unreserve(&foo, 41), Err("Not enough reserved."));
+		assert_eq!(balances.unreserve(&foo, 40), Ok(()));
+		assert_eq!(balances.balance(&foo), 100);
+		assert_eq!(balances.reserved_balance(&foo), 0);
+	}
 }
@@ -0,0 +1,201 @@
+use crate::{
+	support::{self, ApplyExtrinsicResult, GetWeight, Weight},
+	timestamp, types, Runtime, RuntimeCall,
+};
+
+/// Builds a new block on top of the current state of a runtime.
+///
+/// Extrinsics are applied to the runtime as they are pushed, and only those which are valid and fit
+/// in the block are included. Building the block fills in the roots of its header, so it can be
+/// imported by other runtimes with `execute_block`, once it is sealed if the runtime requires it.
+pub struct BlockBuilder<'a> {
+	runtime: &'a mut Runtime,
+	header: types::Header,
+	extrinsics: Vec<types::Extrinsic>,
+	/// The total weight of the scheduled calls and the extrinsics included so far.
+	weight: Weight,
+}
+
+impl<'a> BlockBuilder<'a> {
+	/// Start building the next block on top of `runtime`, initializing the block and including the
+	/// inherents, which set the timestamp of the block to `now`.
+	///
+	/// If this returns an error, the state of the runtime should be discarded, since the block may
+	/// have been partially initialized.
+	pub fn new(runtime: &'a mut Runtime, now: types::Moment) -> Result<Self, &'static str> {
+		let header = support::Header {
+			block_number: runtime.system.block_number() + 1,
+			parent_hash: runtime.system.parent_hash(),
+			extrinsics_root: support::Hash::default(),
+			state_root: support::Hash::default(),
+			seal: None,
+		};
+		let scheduled = runtime.initialize_block(&header)?;
+		// The scheduled calls leave less room for extrinsics.
+		let weight = scheduled.iter().map(|receipt| receipt.weight).fold(0, Weight::saturating_add);
+		let mut builder = Self { runtime, header, extrinsics: Vec::new(), weight };
+		let inherent = RuntimeCall::timestamp(timestamp::Call::set { now });
+		builder.push(support::Extrinsic::new_unsigned(inherent))??;
+		Ok(builder)
+	}
+
+	/// Apply `extrinsic` and include it in the block. The outer `Result` is an error if the
+	/// extrinsic is invalid or would exceed the block weight limit, in which case it is not
+	/// included. Extrinsics whose call fails are still included, since they pay their fee.
+	pub fn push(&mut self, extrinsic: types::Extrinsic) -> ApplyExtrinsicResult {
+		let weight = self.weight.saturating_add(extrinsic.call.weight());
+		if weight > support::MAX_BLOCK_WEIGHT {
+			return Err("extrinsic would exceed the block weight limit");
+		}
+		let result = self.runtime.apply_extrinsic(extrinsic.clone())?;
+		self.weight = weight;
+		self.extrinsics.push(extrinsic);
+		Ok(result)
+	}
+
+	/// Push each of `extrinsics` in order, such as the ready extrinsics of a transaction pool,
+	/// skipping those which cannot be included.
+	pub fn push_all(&mut self, extrinsics: impl IntoIterator<Item = types::Extrinsic>) {
+		for extrinsic in extrinsics {
+			let _res = self.push(extrinsic);
+		}
+	}
+
+	/// Finalize the block and fill in the roots of its header.
+	pub fn build(self) -> types::Block {
+		let Self { runtime, mut header, extrinsics, .. } = self;
+		runtime.finalize_block(header.block_number);
+		header.extrinsics_root = support::extrinsics_root(&extrinsics);
+		header.state_root = runtime.state_root();
+		runtime.note_finished_block(&header);
+		support::Block { header, extrinsics }
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::BlockBuilder;
+	use crate::{balances, keystore::DevKey, support, types, Runtime, RuntimeCall};
+
+	fn transfer(caller: DevKey, nonce: types::Nonce, amount: types::Balance) -> types::Extrinsic {
+		let call =
+			RuntimeCall::balances(balances::Call::transfer { to: DevKey::Bob.account(), amount });
+		support::Extrinsic::new_signed(&caller.pair(), call, nonce, 0, support::Hash::default())
+	}
+
+	fn runtime() -> Runtime {
+		let mut runtime = Runtime::new();
+		runtime.balances.set_balance(&DevKey::Alice.account(), 100);
+		runtime
+	}
+
+	#[test]
+	fn built_blocks_are_imported() {
+		let mut author = runtime();
+		let mut replica = runtime();
+
+		for now in [3_000, 6_000] {
+			let nonce = author.system.get_nonce(&DevKey::Alice.account());
+			let mut builder = BlockBuilder::new(&mut author, now).unwrap();
+			assert_eq!(builder.push(transfer(DevKey::Alice, nonce, 10)), Ok(Ok(())));
+			// The call fails, but the extrinsic is included since it pays its fee.
+			assert_eq!(
+				builder.push(transfer(DevKey::Alice, nonce + 1, 1_000)),
+				Ok(Err("Not enough funds."))
+			);
+			assert_eq!(
+				builder.push(transfer(DevKey::Alice, nonce, 10)),
+				Err("nonce has already been used")
+			);
+			assert_eq!(builder.push(transfer(DevKey::Charlie, 0, 10)), Err("cannot pay the fee"));
+
+			let block = builder.build();
+			// The timestamp inherent comes first.
+			assert_eq!(block.extrinsics.len(), 3);
+			let report = replica.execute_block(block).unwrap();
+			assert_eq!(report.receipts[2].result, Err("Not enough funds."));
+		}
+
+		assert_eq!(replica.balances.balance(&DevKey::Bob.account()), 20);
+		assert_eq!(replica.state_root(), author.state_root());
+		assert_eq!(replica.system.parent_hash(), author.system.parent_hash());
+	}
+
+	#[test]
+	fn scheduled_calls_count_against_the_weight_limit() {
+		let schedule = |runtime: &mut Runtime| {
+			let call = balances::Call::transfer { to: DevKey::Bob.account(), amount: 1 };
+			let call = Box::new(RuntimeCall::balances(call));
+			runtime.scheduler.schedule(DevKey::Alice.account(), 1, None, call).unwrap();
+		};
+		let fill = |runtime: &mut Runtime| {
+			let mut builder = BlockBuilder::new(runtime, 0).unwrap();
+			builder.push_all((0..101).map(|nonce| transfer(DevKey::Alice, nonce, 0)));
+			builder.build()
+		};
+		let funded = || {
+			let mut runtime = runtime();
+			runtime.balances.set_balance(&DevKey::Alice.account(), 10_000);
+			runtime
+		};
+
+		// The scheduled call leaves room for one less transfer.
+		let mut author = funded();
+		schedule(&mut author);
+		let block = fill(&mut author);
+		assert_eq!(block.extrinsics.len(), 100);
+		let mut replica = funded();
+		schedule(&mut replica);
+		assert_eq!(replica.execute_block(block).unwrap().scheduled.len(), 1);
+
+		// A full block does not leave room for the scheduled call.
+		let block = fill(&mut funded());
+		assert_eq!(block.extrinsics.len(), 101);
+		let mut replica = funded();
+		schedule(&mut replica);
+		assert_eq!(replica.execute_block(block).unwrap_err(), "block weight exceeds the limit");
+	}
+
+	#[test]
+	fn tampered_blocks_are_rejected() {
+		let mut author = runtime();
+		author.balances.set_balance(&DevKey::Alice.account(), 10_000);
+		let mut builder = BlockBuilder::new(&mut author, 0).unwrap();
+		builder.push_all((0..101).map(|nonce| transfer(DevKey::Alice, nonce, 0)));
+		assert_eq!(
+			builder.push(transfer(DevKey::Alice, 100, 0)),
+			Err("extrinsic would exceed the block weight limit")
+		);
+		let block = builder.build();
+		assert_eq!(block.extrinsics.len(), 101);
+
+		let replica = || {
+			let mut runtime = runtime();
+			runtime.balances.set_balance(&DevKey::Alice.account(), 10_000);
+			runtime
+		};
+		assert_eq!(replica().execute_block(block.clone()).unwrap().receipts.len(), 101);
+
+		let mut tampered = block.clone();
+		tampered.extrinsics.pop();
+		assert_eq!(
+			replica().execute_block(tampered).unwrap_err(),
+			"extrinsics root does not match the extrinsics"
+		);
+
+		let mut tampered = block.clone();
+		tampered.header.state_root = support::Hash::default();
+		assert_eq!(
+			replica().execute_block(tampered).unwrap_err(),
+			"state root does not match the state after execution"
+		);
+
+		let mut tampered = block;
+		tampered.header.parent_hash = support::Hash::default();
+		tampered.header.parent_hash[0] = 1;
+		assert_eq!(
+			replica().execute_block(tampered).unwrap_err(),
+			"parent hash does not match the last block"
+		);
+	}
+}
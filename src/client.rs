@@ -0,0 +1,472 @@
+use crate::{
+	block_builder::BlockBuilder,
+	support::{self, Hash},
+	types, Runtime,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A rule for choosing the best chain among the forks of the block tree.
+pub trait ForkChoice {
+	/// Whether the chain ending at `candidate` should become the best chain, instead of the one
+	/// ending at `best`.
+	fn is_better(&self, candidate: &types::Header, best: &types::Header) -> bool;
+}
+
+/// The longest chain is the best chain. Between chains of equal length, the one seen first wins.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LongestChain;
+
+impl ForkChoice for LongestChain {
+	fn is_better(&self, candidate: &types::Header, best: &types::Header) -> bool {
+		candidate.block_number > best.block_number
+	}
+}
+
+/// The route through the block tree between two blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeRoute {
+	/// The last block both blocks build on, which may be one of them.
+	pub common_ancestor: Hash,
+	/// The blocks from the first block back to the common ancestor, excluding the ancestor.
+	pub retracted: Vec<Hash>,
+	/// The blocks from the common ancestor to the second block, excluding the ancestor.
+	pub enacted: Vec<Hash>,
+}
+
+/// The outcome of importing a block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportOutcome {
+	/// The hash of the imported block.
+	pub hash: Hash,
+	/// Whether the imported block is the new best block.
+	pub is_new_best: bool,
+	/// If the best chain switched to another fork, the route from the old best block to the new
+	/// one.
+	pub reorg: Option<TreeRoute>,
+}
+
+/// Imports blocks into a tree of forks, keyed by hash, and follows the best chain according to a
+/// fork choice rule.
+///
+/// The state after each head of the tree is kept, so blocks can be imported on top of any head.
+/// Blocks which build on a block in the middle of a chain start a new fork, and the state of their
+/// parent is recomputed by re-executing the blocks from the base of the tree, which is the last
+/// finalized block.
+///
+/// Finalized blocks can never be reverted: blocks which do not build on the last finalized block
+/// are rejected, and the forks which do not include it are pruned when it is finalized.
+pub struct Client<F = LongestChain> {
+	/// All imported blocks, by hash, except those of pruned forks.
+	blocks: BTreeMap<Hash, types::Block>,
+	/// The state after each block which has no children yet.
+	heads: BTreeMap<Hash, Runtime>,
+	/// The hash of the last finalized block, which all other blocks build on, and the state after
+	/// it.
+	base: (Hash, Runtime),
+	/// The hash of the head of the best chain.
+	best: Hash,
+	fork_choice: F,
+}
+
+impl<F: ForkChoice> Client<F> {
+	/// Create a client for the chain starting with the `genesis` state, which is finalized into the
+	/// genesis block.
+	pub fn new(mut genesis: Runtime, fork_choice: F) -> Self {
+		let header = genesis.finalize_genesis();
+		let hash = header.hash();
+		Self {
+			blocks: BTreeMap::from([(hash, support::Block { header, extrinsics: Vec::new() })]),
+			heads: BTreeMap::from([(hash, genesis.clone())]),
+			base: (hash, genesis),
+			best: hash,
+			fork_choice,
+		}
+	}
+
+	/// The hash of the genesis block.
+	pub fn genesis_hash(&self) -> Hash {
+		self.base.1.system.genesis_hash()
+	}
+
+	/// The hash of the head of the best chain.
+	pub fn best_hash(&self) -> Hash {
+		self.best
+	}
+
+	/// The header of the head of the best chain.
+	pub fn best_header(&self) -> &types::Header {
+		&self.blocks[&self.best].header
+	}
+
+	/// The state after the head of the best chain.
+	pub fn best_state(&self) -> &Runtime {
+		&self.heads[&self.best]
+	}
+
+	/// The hash of the last finalized block.
+	pub fn finalized_hash(&self) -> Hash {
+		self.base.0
+	}
+
+	/// The header of the last finalized block.
+	pub fn finalized_header(&self) -> &types::Header {
+		&self.blocks[&self.base.0].header
+	}
+
+	/// Whether the block with `hash` is the last finalized block or one of its ancestors.
+	#[allow(dead_code, reason = "the node only needs the last finalized block")]
+	pub fn is_finalized(&self, hash: &Hash) -> bool {
+		self.blocks.get(hash).is_some_and(|block| {
+			let number = block.header.block_number;
+			number <= self.finalized_header().block_number && self.hash_at(number) == Some(*hash)
+		})
+	}
+
+	/// Get an imported block by hash.
+	pub fn block(&self, hash: &Hash) -> Option<&types::Block> {
+		self.blocks.get(hash)
+	}
+
+	/// The hashes of the heads of all forks.
+	pub fn heads(&self) -> Vec<Hash> {
+		self.heads.keys().copied().collect()
+	}
+
+	/// The hash of the block at `block_number` on the best chain, if the chain is that long.
+	pub fn hash_at(&self, block_number: types::BlockNumber) -> Option<Hash> {
+		let mut hash = self.best;
+		loop {
+			let header = &self.blocks.get(&hash)?.header;
+			if header.block_number == block_number {
+				return Some(hash);
+			}
+			if header.block_number < block_number {
+				return None;
+			}
+			hash = header.parent_hash;
+		}
+	}
+
+	/// Get the state after the block with `hash`. The state of a head is cloned, and the state of
+	/// any other block is recomputed by re-executing the blocks from the base of the tree. The
+	/// state of the ancestors of the last finalized block is pruned.
+	pub fn state_at(&self, hash: &Hash) -> Result<Runtime, &'static str> {
+		if let Some(state) = self.heads.get(hash) {
+			return Ok(state.clone());
+		}
+		let finalized_number = self.finalized_header().block_number;
+		let mut route = Vec::new();
+		let mut current = *hash;
+		while current != self.base.0 {
+			let block = self.blocks.get(&current).ok_or("block is unknown")?;
+			if block.header.block_number <= finalized_number {
+				return Err("state of finalized blocks is pruned");
+			}
+			current = block.header.parent_hash;
+			route.push(block);
+		}
+		let mut state = self.base.1.clone();
+		for block in route.into_iter().rev() {
+			state.execute_block(block.clone())?;
+		}
+		Ok(state)
+	}
+
+	/// Build a block with `extrinsics` on top of the block with `parent`, at time `now`. Extrinsics
+	/// which cannot be included are skipped. The block is not imported.
+	pub fn build_block(
+		&self,
+		parent: &Hash,
+		now: types::Moment,
+		extrinsics: impl IntoIterator<Item = types::Extrinsic>,
+	) -> Result<types::Block, &'static str> {
+		let mut state = self.state_at(parent)?;
+		let mut builder = BlockBuilder::new(&mut state, now)?;
+		builder.push_all(extrinsics);
+		Ok(builder.build())
+	}
+
+	/// Execute and import a block on top of its parent, which must already be imported, and switch
+	/// the best chain to it if the fork choice rule prefers it. If the block is invalid, or does
+	/// not build on the last finalized block, nothing is imported.
+	pub fn import_block(&mut self, block: types::Block) -> Result<ImportOutcome, &'static str> {
+		let hash = block.header.hash();
+		let parent = block.header.parent_hash;
+		if self.blocks.contains_key(&hash) {
+			return Err("block is already imported");
+		}
+		let parent_header = &self.blocks.get(&parent).ok_or("parent block is unknown")?.header;
+		// Blocks below the last finalized block are all finalized, so this would revert finality.
+		if parent_header.block_number < self.finalized_header().block_number {
+			return Err("block does not build on the finalized block");
+		}
+
+		let mut state = self.state_at(&parent)?;
+		state.execute_block(block.clone())?;
+
+		self.heads.remove(&parent);
+		self.heads.insert(hash, state);
+		self.blocks.insert(hash, block);
+
+		let header = &self.blocks[&hash].header;
+		let is_new_best = self.fork_choice.is_better(header, self.best_header());
+		let mut reorg = None;
+		if is_new_best {
+			let route = self.tree_route(&self.best, &hash)?;
+			if !route.retracted.is_empty() {
+				reorg = Some(route);
+			}
+			self.best = hash;
+		}
+		Ok(ImportOutcome { hash, is_new_best, reorg })
+	}
+
+	/// Finalize the block with `hash` and its ancestors, and prune the blocks and state of all
+	/// forks which do not include it. If the best chain is pruned, the best of the remaining chains
+	/// is chosen. Returns the hashes of the pruned blocks.
+	pub fn finalize(&mut self, hash: &Hash) -> Result<Vec<Hash>, &'static str> {
+		// The newly finalized chain, from the block back to the last finalized block.
+		let mut finalized = BTreeSet::new();
+		let mut current = *hash;
+		while current != self.base.0 {
+			let block = self.blocks.get(&current).ok_or("block is unknown")?;
+			if block.header.block_number <= self.finalized_header().block_number {
+				return Err("block is not a descendant of the finalized block");
+			}
+			finalized.insert(current);
+			current = block.header.parent_hash;
+		}
+		finalized.insert(current);
+		let state = self.state_at(hash)?;
+
+		// Every head builds on the last finalized block, so walking back from a head reaches the
+		// newly finalized chain. Unless it reaches the newly finalized block itself, the head is on
+		// an abandoned fork, and every block on the way is pruned.
+		let mut pruned = BTreeSet::new();
+		for head in self.heads() {
+			let mut route = Vec::new();
+			let mut current = head;
+			while !finalized.contains(&current) {
+				route.push(current);
+				current = self.blocks[&current].header.parent_hash;
+			}
+			if current != *hash {
+				self.heads.remove(&head);
+				pruned.extend(route);
+			}
+		}
+		for pruned in &pruned {
+			self.blocks.remove(pruned);
+		}
+
+		self.base = (*hash, state);
+		if pruned.contains(&self.best) {
+			self.best = *hash;
+			for head in self.heads() {
+				if self.fork_choice.is_better(&self.blocks[&head].header, self.best_header()) {
+					self.best = head;
+				}
+			}
+		}
+		Ok(pruned.into_iter().collect())
+	}
+
+	/// Find the route through the block tree from the block with hash `from` to the block with
+	/// hash `to`.
+	pub fn tree_route(&self, from: &Hash, to: &Hash) -> Result<TreeRoute, &'static str> {
+		let header = |hash: &Hash| {
+			self.blocks.get(hash).map(|block| &block.header).ok_or("block is unknown")
+		};
+		let (mut from, mut to) = (*from, *to);
+		let (mut retracted, mut enacted) = (Vec::new(), Vec::new());
+		while from != to {
+			let (from_header, to_header) = (header(&from)?, header(&to)?);
+			// Step back along the higher chain, or both chains if they are equally high.
+			if from_header.block_number >= to_header.block_number {
+				retracted.push(from);
+				from = from_header.parent_hash;
+			}
+			if to_header.block_number >= from_header.block_number {
+				enacted.push(to);
+				to = to_header.parent_hash;
+			}
+		}
+		enacted.reverse();
+		Ok(TreeRoute { common_ancestor: from, retracted, enacted })
+	}
+}
+
+/// A simple finality gadget, which finalizes the blocks of the best chain once `depth` blocks have
+/// been built on top of them.
+#[derive(Debug, Clone, Copy)]
+pub struct Confirmations {
+	pub depth: types::BlockNumber,
+}
+
+impl Confirmations {
+	/// Finalize the deepest block of the best chain of `client` with enough confirmations, if it is
+	/// not finalized yet. Returns the hashes of the pruned blocks.
+	pub fn finalize<F: ForkChoice>(
+		&self,
+		client: &mut Client<F>,
+	) -> Result<Vec<Hash>, &'static str> {
+		let Some(target) = client.best_header().block_number.checked_sub(self.depth) else {
+			return Ok(Vec::new());
+		};
+		if target <= client.finalized_header().block_number {
+			return Ok(Vec::new());
+		}
+		let hash = client.hash_at(target).ok_or("best chain is shorter than its head")?;
+		client.finalize(&hash)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{Client, Confirmations, LongestChain, TreeRoute};
+	use crate::{balances, keystore::DevKey, support, types, Runtime, RuntimeCall};
+
+	fn transfer(nonce: types::Nonce, amount: types::Balance) -> types::Extrinsic {
+		let call =
+			RuntimeCall::balances(balances::Call::transfer { to: DevKey::Bob.account(), amount });
+		support::Extrinsic::new_signed(&DevKey::Alice.pair(), call, nonce, 0, genesis_hash())
+	}
+
+	fn genesis() -> Runtime {
+		let mut runtime = Runtime::new();
+		runtime.balances.set_balance(&DevKey::Alice.account(), 1_000);
+		runtime
+	}
+
+	fn genesis_hash() -> support::Hash {
+		Client::new(genesis(), LongestChain).genesis_hash()
+	}
+
+	fn bob(client: &Client) -> types::Balance {
+		client.best_state().balances.balance(&DevKey::Bob.account())
+	}
+
+	#[test]
+	fn longest_chain_reorg() {
+		let mut client = Client::new(genesis(), LongestChain);
+		let genesis = client.best_hash();
+
+		// The first chain transfers 10 in each block.
+		let a1 = client.build_block(&genesis, 3_000, [transfer(0, 10)]).unwrap();
+		let a1 = client.import_block(a1).unwrap();
+		assert!(a1.is_new_best && a1.reorg.is_none());
+		let a2 = client.build_block(&a1.hash, 6_000, [transfer(1, 10)]).unwrap();
+		let a2 = client.import_block(a2).unwrap().hash;
+		assert_eq!(bob(&client), 20);
+
+		// A second chain forks off genesis. It is not the best chain until it is longer.
+		let b1 = client.build_block(&genesis, 4_000, [transfer(0, 100)]).unwrap();
+		let b1 = client.import_block(b1).unwrap().hash;
+		let b2 = client.build_block(&b1, 7_000, []).unwrap();
+		let b2 = client.import_block(b2).unwrap();
+		assert!(!b2.is_new_best);
+		assert_eq!(client.best_hash(), a2);
+		assert_eq!(client.heads().len(), 2);
+
+		let b3 = client.build_block(&b2.hash, 10_000, [transfer(1, 100)]).unwrap();
+		let b3 = client.import_block(b3).unwrap();
+		assert!(b3.is_new_best);
+		let route = TreeRoute {
+			common_ancestor: genesis,
+			retracted: vec![a2, a1.hash],
+			enacted: vec![b1, b2.hash, b3.hash],
+		};
+		assert_eq!(b3.reorg, Some(route));
+		assert_eq!(bob(&client), 200);
+		assert_eq!(client.hash_at(1), Some(b1));
+		assert_eq!(client.hash_at(4), None);
+
+		// A third chain forks off the middle of the first, whose state is recomputed.
+		let c2 = client.build_block(&a1.hash, 6_500, [transfer(1, 50)]).unwrap();
+		let c2 = client.import_block(c2).unwrap();
+		assert!(!c2.is_new_best);
+		let state = client.state_at(&c2.hash).unwrap();
+		assert_eq!(state.balances.balance(&DevKey::Bob.account()), 60);
+		assert_eq!(client.heads().len(), 3);
+	}
+
+	#[test]
+	fn invalid_blocks_are_not_imported() {
+		let mut client = Client::new(genesis(), LongestChain);
+		let genesis = client.best_hash();
+		let block = client.build_block(&genesis, 3_000, [transfer(0, 10)]).unwrap();
+
+		let mut orphan = block.clone();
+		orphan.header.parent_hash = [1; 32];
+		assert_eq!(client.import_block(orphan), Err("parent block is unknown"));
+
+		let mut tampered = block.clone();
+		tampered.header.state_root = support::Hash::default();
+		assert_eq!(
+			client.import_block(tampered),
+			Err("state root does not match the state after execution")
+		);
+		assert_eq!(client.heads(), vec![genesis]);
+
+		assert!(client.import_block(block.clone()).is_ok());
+		assert_eq!(client.import_block(block), Err("block is already imported"));
+		assert_eq!(bob(&client), 10);
+	}
+
+	#[test]
+	fn finality_prunes_abandoned_forks() {
+		let mut client = Client::new(genesis(), LongestChain);
+		let genesis = client.best_hash();
+		let mut import = |parent, now, extrinsics: Vec<types::Extrinsic>| {
+			let block = client.build_block(&parent, now, extrinsics).unwrap();
+			client.import_block(block).unwrap().hash
+		};
+		let a1 = import(genesis, 3_000, vec![transfer(0, 10)]);
+		let a2 = import(a1, 6_000, vec![]);
+		let a3 = import(a2, 9_000, vec![]);
+		let b1 = import(genesis, 4_000, vec![]);
+		let b2 = import(a1, 7_000, vec![transfer(1, 20)]);
+		let b3 = import(b2, 10_000, vec![]);
+		let b4 = import(b3, 13_000, vec![]);
+		assert_eq!(client.best_hash(), b4);
+		assert_eq!(client.heads().len(), 3);
+
+		let late = client.build_block(&a1, 7_500, []).unwrap();
+
+		// Finalizing the first chain abandons the others, even though one of them is the best.
+		let mut pruned = client.finalize(&a2).unwrap();
+		pruned.sort();
+		let mut expected = vec![b1, b2, b3, b4];
+		expected.sort();
+		assert_eq!(pruned, expected);
+		assert_eq!(client.heads(), vec![a3]);
+		assert_eq!(client.best_hash(), a3);
+		assert_eq!(bob(&client), 10);
+		assert!(client.is_finalized(&a1) && client.is_finalized(&a2) && !client.is_finalized(&a3));
+		assert!(client.block(&b2).is_none() && client.block(&a1).is_some());
+
+		// Finality cannot be reverted.
+		assert_eq!(client.finalize(&a1), Err("block is not a descendant of the finalized block"));
+		let fork = client.build_block(&a2, 12_000, []).unwrap();
+		assert_eq!(client.import_block(late), Err("block does not build on the finalized block"));
+		assert_eq!(client.state_at(&a1).err(), Some("state of finalized blocks is pruned"));
+		assert_eq!(client.finalize(&a2), Ok(Vec::new()));
+
+		// Forks of the finalized block itself are still allowed.
+		assert!(client.import_block(fork).is_ok());
+		assert_eq!(client.heads().len(), 2);
+	}
+
+	#[test]
+	fn confirmations_gadget() {
+		let mut client = Client::new(genesis(), LongestChain);
+		let gadget = Confirmations { depth: 2 };
+		for i in 1..=5 {
+			let block = client.build_block(&client.best_hash(), i * 3_000, []).unwrap();
+			client.import_block(block).unwrap();
+			assert_eq!(gadget.finalize(&mut client), Ok(Vec::new()));
+			assert_eq!(client.finalized_header().block_number, (i as u32).saturating_sub(2));
+		}
+		assert_eq!(client.hash_at(3), Some(client.finalized_hash()));
+	}
+}
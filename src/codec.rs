@@ -0,0 +1,281 @@
+use std::cell::Cell;
+
+/// A trait for types which can be encoded into bytes, using a simple binary codec.
+///
+/// Integers are encoded as fixed width little endian, collections and strings are prefixed with
+/// their length as a `u32`, `Option` and enums are prefixed with a one byte tag.
+pub trait Encode {
+	/// Append the encoding of `self` to `dest`.
+	fn encode_to(&self, dest: &mut Vec<u8>);
+
+	/// Encode `self` into a new vector of bytes.
+	fn encode(&self) -> Vec<u8> {
+		let mut dest = Vec::new();
+		self.encode_to(&mut dest);
+		dest
+	}
+}
+
+/// A trait for types which can be decoded from the bytes produced by their `Encode` implementation.
+pub trait Decode: Sized {
+	/// Decode a value from the start of `input`, advancing it past the decoded bytes.
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str>;
+
+	/// Decode a value from `bytes`, which must contain exactly its encoding.
+	fn decode(mut bytes: &[u8]) -> Result<Self, &'static str> {
+		let value = Self::decode_from(&mut bytes)?;
+		if !bytes.is_empty() {
+			return Err("trailing bytes after the encoded value");
+		}
+		Ok(value)
+	}
+}
+
+/// The maximum nesting of values when decoding, so malicious input cannot overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+thread_local! {
+	/// The number of nested values being decoded on this thread.
+	static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Decode a value nested inside the value being decoded, failing if values are nested more than
+/// `MAX_DEPTH` deep. Recursive types, like calls which contain other calls, nest through `Box`,
+/// whose `Decode` uses this.
+pub fn decode_nested<T>(
+	decode: impl FnOnce() -> Result<T, &'static str>,
+) -> Result<T, &'static str> {
+	let depth = DEPTH.get();
+	if depth >= MAX_DEPTH {
+		return Err("value is nested too deeply");
+	}
+	DEPTH.set(depth + 1);
+	let result = decode();
+	DEPTH.set(depth);
+	result
+}
+
+/// Take the next `len` bytes from `input`.
+pub fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], &'static str> {
+	if input.len() < len {
+		return Err("unexpected end of input");
+	}
+	let (bytes, rest) = input.split_at(len);
+	*input = rest;
+	Ok(bytes)
+}
+
+macro_rules! impl_encode_for_int {
+	( $( $t:ty ),* ) => {
+		$(
+			impl Encode for $t {
+				fn encode_to(&self, dest: &mut Vec<u8>) {
+					dest.extend_from_slice(&self.to_le_bytes());
+				}
+			}
+
+			impl Decode for $t {
+				fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+					let bytes = take(input, core::mem::size_of::<$t>())?;
+					Ok(<$t>::from_le_bytes(bytes.try_into().expect("length was checked")))
+				}
+			}
+		)*
+	};
+}
+
+impl_encode_for_int!(u8, u16, u32, u64, u128);
+
+impl Encode for bool {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(*self as u8).encode_to(dest);
+	}
+}
+
+impl Decode for bool {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		match u8::decode_from(input)? {
+			0 => Ok(false),
+			1 => Ok(true),
+			_ => Err("invalid bool"),
+		}
+	}
+}
+
+/// Encode the length of a collection.
+fn encode_len(len: usize, dest: &mut Vec<u8>) {
+	u32::try_from(len).expect("collections are smaller than u32::MAX").encode_to(dest);
+}
+
+/// Decode the length of a collection. Each item takes at least one byte, except for zero sized
+/// items which we do not decode, so a length longer than the input is invalid. This keeps a bogus
+/// length from allocating a huge collection.
+fn decode_len(input: &mut &[u8]) -> Result<usize, &'static str> {
+	let len = u32::decode_from(input)? as usize;
+	if len > input.len() {
+		return Err("unexpected end of input");
+	}
+	Ok(len)
+}
+
+impl Encode for str {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		encode_len(self.len(), dest);
+		dest.extend_from_slice(self.as_bytes());
+	}
+}
+
+impl Encode for String {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.as_str().encode_to(dest);
+	}
+}
+
+impl Decode for String {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		let len = decode_len(input)?;
+		let bytes = take(input, len)?;
+		String::from_utf8(bytes.to_vec()).map_err(|_| "invalid utf-8")
+	}
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(**self).encode_to(dest);
+	}
+}
+
+impl<T: Encode + ?Sized> Encode for Box<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(**self).encode_to(dest);
+	}
+}
+
+impl<T: Decode> Decode for Box<T> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		decode_nested(|| T::decode_from(input)).map(Box::new)
+	}
+}
+
+impl<T: Encode, const N: usize> Encode for [T; N] {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		// The length of an array is known from its type, so we do not encode it.
+		for item in self {
+			item.encode_to(dest);
+		}
+	}
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		let items = (0..N).map(|_| T::decode_from(input)).collect::<Result<Vec<_>, _>>()?;
+		Ok(items.try_into().unwrap_or_else(|_| unreachable!("exactly N items were decoded")))
+	}
+}
+
+impl<T: Encode> Encode for [T] {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		encode_len(self.len(), dest);
+		for item in self {
+			item.encode_to(dest);
+		}
+	}
+}
+
+impl<T: Encode> Encode for Vec<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.as_slice().encode_to(dest);
+	}
+}
+
+impl<T: Decode> Decode for Vec<T> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		let len = decode_len(input)?;
+		(0..len).map(|_| T::decode_from(input)).collect()
+	}
+}
+
+impl<T: Encode> Encode for Option<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		match self {
+			None => 0u8.encode_to(dest),
+			Some(value) => {
+				1u8.encode_to(dest);
+				value.encode_to(dest);
+			},
+		}
+	}
+}
+
+impl<T: Decode> Decode for Option<T> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		match u8::decode_from(input)? {
+			0 => Ok(None),
+			1 => Ok(Some(T::decode_from(input)?)),
+			_ => Err("invalid option tag"),
+		}
+	}
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.0.encode_to(dest);
+		self.1.encode_to(dest);
+	}
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok((A::decode_from(input)?, B::decode_from(input)?))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{Decode, Encode, MAX_DEPTH};
+
+	#[test]
+	fn encode_primitives() {
+		assert_eq!(7u32.encode(), vec![7, 0, 0, 0]);
+		assert_eq!("ab".encode(), vec![2, 0, 0, 0, b'a', b'b']);
+		assert_eq!(Some(1u8).encode(), vec![1, 1]);
+		assert_eq!(None::<u8>.encode(), vec![0]);
+		assert_eq!(vec![(1u8, true)].encode(), vec![1, 0, 0, 0, 1, 1]);
+		assert_eq!([1u8, 2].encode(), vec![1, 2]);
+	}
+
+	#[test]
+	fn decode_primitives() {
+		assert_eq!(u32::decode(&[7, 0, 0, 0]), Ok(7));
+		assert_eq!(String::decode(&"ab".encode()), Ok("ab".to_string()));
+		assert_eq!(Option::<u8>::decode(&[1, 1]), Ok(Some(1)));
+		assert_eq!(Vec::<(u8, bool)>::decode(&[1, 0, 0, 0, 1, 1]), Ok(vec![(1, true)]));
+		assert_eq!(<[u8; 2]>::decode(&[1, 2]), Ok([1, 2]));
+
+		assert_eq!(u32::decode(&[7, 0, 0]), Err("unexpected end of input"));
+		assert_eq!(u8::decode(&[7, 0]), Err("trailing bytes after the encoded value"));
+		assert_eq!(bool::decode(&[2]), Err("invalid bool"));
+		assert_eq!(Vec::<u8>::decode(&[255, 255, 255, 255]), Err("unexpected end of input"));
+	}
+
+	#[test]
+	fn decode_deeply_nested() {
+		// A list nested in boxes, encoded as the number of boxes around each item.
+		#[derive(Debug, PartialEq)]
+		struct Nested(Option<Box<Nested>>);
+
+		impl Decode for Nested {
+			fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+				Ok(Nested(Decode::decode_from(input)?))
+			}
+		}
+
+		let nested = |depth| [vec![1; depth], vec![0]].concat();
+		let too_deep = Err("value is nested too deeply");
+		assert!(Nested::decode(&nested(MAX_DEPTH)).is_ok());
+		assert_eq!(Nested::decode(&nested(MAX_DEPTH + 1)), too_deep);
+		assert_eq!(Nested::decode(&nested(1_000_000)), too_deep);
+		// The depth is reset after an error.
+		assert!(Nested::decode(&nested(MAX_DEPTH)).is_ok());
+	}
+}
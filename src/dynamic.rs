@@ -0,0 +1,287 @@
+//! Calls built from JSON, and shown as JSON, using only the metadata of the runtime. No code is
+//! written for each call: the arguments are converted by the [`TypeDef`] of their type.
+//!
+//! A call is written as `{"pallet":"balances","call":"transfer","args":{"to":"5G..","amount":11}}`.
+//! Integers and booleans are JSON numbers and booleans, strings are strings, accounts are
+//! addresses, bytes are hex strings, options are `null` or their value, tuples and vectors are
+//! arrays, enums are the name of their variant, and calls are written like the call itself.
+
+use crate::{
+	account::AccountId32,
+	codec::{self, Decode, Encode},
+	json::{from_hex, to_hex, Value},
+	metadata::{RuntimeMetadata, TypeDef},
+};
+
+/// Build a call of the runtime from its JSON.
+pub fn call_from_json<C: Decode>(
+	metadata: &RuntimeMetadata,
+	call: &Value,
+) -> Result<C, &'static str> {
+	C::decode(&encode_call(metadata, call)?)
+}
+
+/// Show a call of the runtime as JSON.
+pub fn call_to_json<C: Encode>(
+	metadata: &RuntimeMetadata,
+	call: &C,
+) -> Result<Value, &'static str> {
+	let bytes = call.encode();
+	let mut input = &bytes[..];
+	let json = decode_call(metadata, &mut input)?;
+	if !input.is_empty() {
+		return Err("trailing bytes after the encoded value");
+	}
+	Ok(json)
+}
+
+/// Encode a call from its JSON.
+pub fn encode_call(metadata: &RuntimeMetadata, call: &Value) -> Result<Vec<u8>, &'static str> {
+	let mut dest = Vec::new();
+	encode_call_to(metadata, call, &mut dest)?;
+	Ok(dest)
+}
+
+/// Decode a call from the start of `input` into its JSON, advancing `input` past the call.
+pub fn decode_call(metadata: &RuntimeMetadata, input: &mut &[u8]) -> Result<Value, &'static str> {
+	let index = u8::decode_from(input)?;
+	let pallet = metadata.pallets.iter().find(|pallet| pallet.index == index);
+	let pallet = pallet.ok_or("invalid pallet index")?;
+	let index = u8::decode_from(input)?;
+	let call = pallet.calls.iter().find(|call| call.index == index).ok_or("invalid call index")?;
+	let mut args = Vec::new();
+	for arg in &call.args {
+		args.push((arg.name.clone(), decode_value(metadata, &arg.type_def, input)?));
+	}
+	Ok(Value::object([
+		("pallet", pallet.name.clone().into()),
+		("call", call.name.clone().into()),
+		("args", Value::Object(args)),
+	]))
+}
+
+fn encode_call_to(
+	metadata: &RuntimeMetadata,
+	call: &Value,
+	dest: &mut Vec<u8>,
+) -> Result<(), &'static str> {
+	let pallet = call.get("pallet").and_then(Value::as_str).ok_or("call must name its pallet")?;
+	let pallet = metadata.pallet(pallet).ok_or("unknown pallet")?;
+	let name = call.get("call").and_then(Value::as_str).ok_or("call must name its function")?;
+	let call_metadata = pallet.call(name).ok_or("unknown call")?;
+	// Calls without arguments may leave out `args`.
+	let args = match call.get("args") {
+		Some(args) => args.as_object().ok_or("args must be an object")?,
+		None => &[],
+	};
+	if args.iter().any(|(name, _)| !call_metadata.args.iter().any(|arg| &arg.name == name)) {
+		return Err("unknown argument");
+	}
+
+	pallet.index.encode_to(dest);
+	call_metadata.index.encode_to(dest);
+	for arg in &call_metadata.args {
+		let value = call.get("args").and_then(|args| args.get(&arg.name));
+		encode_value(metadata, &arg.type_def, value.ok_or("missing argument")?, dest)?;
+	}
+	Ok(())
+}
+
+fn encode_value(
+	metadata: &RuntimeMetadata,
+	type_def: &TypeDef,
+	value: &Value,
+	dest: &mut Vec<u8>,
+) -> Result<(), &'static str> {
+	match type_def {
+		TypeDef::Bool => value.as_bool().ok_or("expected a boolean")?.encode_to(dest),
+		TypeDef::Uint(bytes) => {
+			let n = value.as_i128().ok_or("expected an integer")?;
+			let n = u128::try_from(n).map_err(|_| "integer out of range")?;
+			if *bytes < 16 && n >> (bytes * 8) != 0 {
+				return Err("integer out of range");
+			}
+			dest.extend_from_slice(&n.to_le_bytes()[..*bytes]);
+		},
+		TypeDef::String => value.as_str().ok_or("expected a string")?.encode_to(dest),
+		TypeDef::AccountId => {
+			let address = value.as_str().ok_or("expected an address")?;
+			address.parse::<AccountId32>()?.encode_to(dest);
+		},
+		TypeDef::Bytes(len) => {
+			let bytes = from_hex(value.as_str().ok_or("expected a hex string")?)?;
+			if bytes.len() != *len {
+				return Err("wrong number of bytes");
+			}
+			dest.extend_from_slice(&bytes);
+		},
+		TypeDef::Option(inner) =>
+			if value.is_null() {
+				0u8.encode_to(dest);
+			} else {
+				1u8.encode_to(dest);
+				encode_value(metadata, inner, value, dest)?;
+			},
+		TypeDef::Tuple(items) => {
+			let values = value.as_array().ok_or("expected an array")?;
+			if values.len() != items.len() {
+				return Err("wrong number of items in tuple");
+			}
+			for (item, value) in items.iter().zip(values) {
+				encode_value(metadata, item, value, dest)?;
+			}
+		},
+		TypeDef::Vec(inner) => {
+			let values = value.as_array().ok_or("expected an array")?;
+			u32::try_from(values.len()).map_err(|_| "too many items")?.encode_to(dest);
+			for value in values {
+				encode_value(metadata, inner, value, dest)?;
+			}
+		},
+		TypeDef::Call => encode_call_to(metadata, value, dest)?,
+		TypeDef::Enum(variants) => {
+			let name = value.as_str().ok_or("expected the name of a variant")?;
+			let index = variants.iter().position(|variant| variant == name);
+			(index.ok_or("unknown variant")? as u8).encode_to(dest);
+		},
+	}
+	Ok(())
+}
+
+fn decode_value(
+	metadata: &RuntimeMetadata,
+	type_def: &TypeDef,
+	input: &mut &[u8],
+) -> Result<Value, &'static str> {
+	Ok(match type_def {
+		TypeDef::Bool => bool::decode_from(input)?.into(),
+		TypeDef::Uint(bytes) => {
+			let mut le_bytes = [0; 16];
+			le_bytes[..*bytes].copy_from_slice(codec::take(input, *bytes)?);
+			let n = i128::try_from(u128::from_le_bytes(le_bytes));
+			Value::Number(n.map_err(|_| "integer is too large for JSON")?)
+		},
+		TypeDef::String => String::decode_from(input)?.into(),
+		TypeDef::AccountId => AccountId32::decode_from(input)?.to_string().into(),
+		TypeDef::Bytes(len) => to_hex(codec::take(input, *len)?).into(),
+		TypeDef::Option(inner) => match u8::decode_from(input)? {
+			0 => Value::Null,
+			1 => decode_value(metadata, inner, input)?,
+			_ => return Err("invalid option tag"),
+		},
+		TypeDef::Tuple(items) => {
+			let values = items.iter().map(|item| decode_value(metadata, item, input));
+			Value::Array(values.collect::<Result<_, _>>()?)
+		},
+		TypeDef::Vec(inner) => {
+			let len = u32::decode_from(input)? as usize;
+			// Each item takes at least one byte, so a longer length is invalid.
+			if len > input.len() {
+				return Err("unexpected end of input");
+			}
+			let values = (0..len).map(|_| decode_value(metadata, inner, input));
+			Value::Array(values.collect::<Result<_, _>>()?)
+		},
+		TypeDef::Call => codec::decode_nested(|| decode_call(metadata, input))?,
+		TypeDef::Enum(variants) => {
+			let variant = variants.get(u8::decode_from(input)? as usize);
+			variant.ok_or("invalid enum variant")?.clone().into()
+		},
+	})
+}
+
+#[cfg(test)]
+mod test {
+	use super::{call_from_json, call_to_json, encode_call};
+	use crate::{
+		balances, codec::Encode, json::Value, keystore::DevKey, proxy, scheduler, ProxyType,
+		Runtime, RuntimeCall,
+	};
+
+	/// The JSON of a call, with its arguments given as the text of a JSON object.
+	fn call_json(pallet: &str, call: &str, args: &str) -> Value {
+		Value::parse(&format!(r#"{{"pallet":"{pallet}","call":"{call}","args":{args}}}"#)).unwrap()
+	}
+
+	#[test]
+	fn calls_round_trip_through_json() {
+		let metadata = Runtime::metadata();
+		let alice = DevKey::Alice.account();
+		let bob = DevKey::Bob.account();
+		let args = format!(r#"{{"to":"{bob}","amount":11}}"#);
+		let transfer = call_json("balances", "transfer", &args);
+		let call: RuntimeCall = call_from_json(&metadata, &transfer).unwrap();
+		let transfer_call =
+			RuntimeCall::balances(balances::Call::transfer { to: bob.clone(), amount: 11 });
+		assert_eq!(call.encode(), transfer_call.encode());
+		assert_eq!(call_to_json(&metadata, &call), Ok(transfer.clone()));
+
+		// Nested calls, options, tuples and enums.
+		let call = RuntimeCall::proxy(proxy::Call::proxy {
+			real: alice.clone(),
+			call: Box::new(RuntimeCall::scheduler(scheduler::Call::schedule {
+				when: 5,
+				periodic: Some((10, 3)),
+				call: Box::new(RuntimeCall::proxy(proxy::Call::add_proxy {
+					delegate: bob.clone(),
+					proxy_type: ProxyType::BalancesOnly,
+					delay: 0,
+				})),
+			})),
+		});
+		let add_proxy = call_json(
+			"proxy",
+			"add_proxy",
+			&format!(r#"{{"delegate":"{bob}","proxy_type":"BalancesOnly","delay":0}}"#),
+		);
+		let schedule = call_json(
+			"scheduler",
+			"schedule",
+			&format!(r#"{{"when":5,"periodic":[10,3],"call":{add_proxy}}}"#),
+		);
+		let args = format!(r#"{{"real":"{alice}","call":{schedule}}}"#);
+		let expected = call_json("proxy", "proxy", &args);
+		let shown = call_to_json(&metadata, &call).unwrap();
+		assert_eq!(shown, expected);
+		let parsed: RuntimeCall = call_from_json(&metadata, &shown).unwrap();
+		assert_eq!(parsed.encode(), call.encode());
+
+		// Arguments may be given in any order, and `null` is `None`.
+		let args = format!(r#"{{"call":{transfer},"periodic":null,"when":1}}"#);
+		let call: RuntimeCall =
+			call_from_json(&metadata, &call_json("scheduler", "schedule", &args)).unwrap();
+		let expected = RuntimeCall::scheduler(scheduler::Call::schedule {
+			when: 1,
+			periodic: None,
+			call: Box::new(transfer_call),
+		});
+		assert_eq!(call.encode(), expected.encode());
+	}
+
+	#[test]
+	fn invalid_calls() {
+		let metadata = Runtime::metadata();
+		let encode = |pallet: &str, call: &str, args: &str| {
+			encode_call(&metadata, &call_json(pallet, call, args))
+		};
+		let bob = DevKey::Bob.account();
+		let no_pallet = Value::parse(r#"{"call":"transfer"}"#).unwrap();
+		assert_eq!(encode_call(&metadata, &no_pallet), Err("call must name its pallet"));
+		assert_eq!(encode("nope", "transfer", "{}"), Err("unknown pallet"));
+		assert_eq!(encode("balances", "nope", "{}"), Err("unknown call"));
+		assert_eq!(encode("balances", "transfer", r#"{"amount":1}"#), Err("missing argument"));
+		let args = format!(r#"{{"to":"{bob}","amount":1,"extra":1}}"#);
+		assert_eq!(encode("balances", "transfer", &args), Err("unknown argument"));
+		let args = r#"{"to":"foo","amount":1}"#;
+		assert_eq!(encode("balances", "transfer", args), Err("invalid address length"));
+		let args = format!(r#"{{"to":"{bob}","amount":-1}}"#);
+		assert_eq!(encode("balances", "transfer", &args), Err("integer out of range"));
+		assert_eq!(encode("timestamp", "set", r#"{"now":"1"}"#), Err("expected an integer"));
+		let args = r#"{"when":1,"periodic":[1,4294967296],"call":null}"#;
+		assert_eq!(encode("scheduler", "schedule", args), Err("integer out of range"));
+		let args = format!(r#"{{"delegate":"{bob}","proxy_type":"All","delay":0}}"#);
+		assert_eq!(encode("proxy", "add_proxy", &args), Err("unknown variant"));
+		let args = r#"{"authority":"0x01"}"#;
+		assert_eq!(encode("authorities", "add_authority", args), Err("wrong number of bytes"));
+	}
+}
@@ -0,0 +1,338 @@
+//! Ed25519 signatures, as specified in RFC 8032.
+//!
+//! The field and scalar arithmetic uses big integers, which keeps the implementation close to the
+//! specification at the cost of speed. It is not constant time, so it must not be used to protect
+//! real funds.
+
+use crate::{
+	account::AccountId32,
+	codec::{Decode, Encode},
+	metadata::{TypeDef, TypeInfo},
+	support::{self, IdentifyAccount, Verify},
+};
+use num::{BigUint, One, Zero};
+use sha2::{Digest, Sha512};
+use std::sync::OnceLock;
+
+/// An Ed25519 public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Public(pub [u8; 32]);
+
+/// An Ed25519 signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature(pub [u8; 64]);
+
+/// An Ed25519 key pair, which can sign messages.
+#[derive(Clone)]
+pub struct Pair {
+	/// The secret scalar, derived from the seed.
+	scalar: BigUint,
+	/// The second half of the hash of the seed, used to derive the nonce of each signature.
+	prefix: [u8; 32],
+	public: Public,
+}
+
+impl Pair {
+	/// Create the key pair for a 32 byte secret `seed`.
+	pub fn from_seed(seed: &[u8; 32]) -> Self {
+		let hash = Sha512::digest(seed);
+		let mut scalar = [0u8; 32];
+		scalar.copy_from_slice(&hash[..32]);
+		// Clamp the scalar, so it is a multiple of the cofactor with its highest bit set.
+		scalar[0] &= 248;
+		scalar[31] &= 127;
+		scalar[31] |= 64;
+		let scalar = BigUint::from_bytes_le(&scalar);
+		let mut prefix = [0u8; 32];
+		prefix.copy_from_slice(&hash[32..]);
+		let public = Public(curve().base.mul(&scalar).encode());
+		Self { scalar, prefix, public }
+	}
+}
+
+impl support::Pair for Pair {
+	type Public = Public;
+	type Signature = Signature;
+
+	fn public(&self) -> Public {
+		self.public
+	}
+
+	fn sign(&self, message: &[u8]) -> Signature {
+		let curve = curve();
+		let r = hash_to_scalar(&[&self.prefix, message]);
+		let big_r = curve.base.mul(&r).encode();
+		let k = hash_to_scalar(&[&big_r, &self.public.0, message]);
+		let s = (r + k * &self.scalar) % &curve.l;
+
+		let mut signature = [0u8; 64];
+		signature[..32].copy_from_slice(&big_r);
+		signature[32..].copy_from_slice(&to_bytes(&s));
+		Signature(signature)
+	}
+}
+
+impl Verify for Signature {
+	type Signer = Public;
+
+	fn verify(&self, message: &[u8], signer: &Public) -> bool {
+		let curve = curve();
+		let s = BigUint::from_bytes_le(&self.0[32..]);
+		if s >= curve.l {
+			return false;
+		}
+		let (Some(public), Some(big_r)) = (Point::decode(&signer.0), Point::decode(&self.0[..32])) else {
+			return false;
+		};
+		let k = hash_to_scalar(&[&self.0[..32], &signer.0, message]);
+		curve.base.mul(&s).encode() == big_r.add(&public.mul(&k)).encode()
+	}
+}
+
+/// The account of a public key is the public key itself.
+impl IdentifyAccount for Public {
+	type AccountId = AccountId32;
+
+	fn into_account(self) -> AccountId32 {
+		AccountId32(self.0)
+	}
+}
+
+impl Encode for Public {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.0.encode_to(dest);
+	}
+}
+
+impl Decode for Public {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self(Decode::decode_from(input)?))
+	}
+}
+
+impl TypeInfo for Public {
+	fn type_def() -> TypeDef {
+		TypeDef::Bytes(32)
+	}
+}
+
+impl Encode for Signature {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.0.encode_to(dest);
+	}
+}
+
+impl Decode for Signature {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self(Decode::decode_from(input)?))
+	}
+}
+
+impl TypeInfo for Signature {
+	fn type_def() -> TypeDef {
+		TypeDef::Bytes(64)
+	}
+}
+
+/// The constants of the curve.
+struct Curve {
+	/// The prime of the field, `2^255 - 19`.
+	p: BigUint,
+	/// The order of the base point, `2^252 + 27742317777372353535851937790883648493`.
+	l: BigUint,
+	/// Twice the `d` parameter of the curve equation `-x^2 + y^2 = 1 + d x^2 y^2`.
+	d2: BigUint,
+	/// The `d` parameter of the curve equation.
+	d: BigUint,
+	/// A square root of -1 in the field.
+	sqrt_m1: BigUint,
+	/// The base point of the curve.
+	base: Point,
+}
+
+/// The constants of the curve, computed the first time they are needed.
+fn curve() -> &'static Curve {
+	static CURVE: OnceLock<Curve> = OnceLock::new();
+	CURVE.get_or_init(|| {
+		let p = (BigUint::one() << 255u32) - 19u32;
+		let l = (BigUint::one() << 252u32) +
+			BigUint::parse_bytes(b"27742317777372353535851937790883648493", 10).expect("valid number");
+		let inv = |x: &BigUint| x.modpow(&(&p - 2u32), &p);
+		let d = (&p - 121665u32) * inv(&BigUint::from(121666u32)) % &p;
+		let d2 = &d * 2u32 % &p;
+		let sqrt_m1 = BigUint::from(2u32).modpow(&((&p - 1u32) >> 2u32), &p);
+
+		// The base point is the point with `y = 4/5` and a positive `x`. We decode it from its
+		// encoding, which needs the other constants, so we set a placeholder first.
+		let base_y = BigUint::from(4u32) * inv(&BigUint::from(5u32)) % &p;
+		let mut curve = Curve { p, l, d2, d, sqrt_m1, base: Point::identity() };
+		curve.base = Point::decode_with(&curve, &to_bytes(&base_y)).expect("base point is valid");
+		curve
+	})
+}
+
+/// A point on the curve, in extended coordinates `(X, Y, Z, T)` where `x = X/Z`, `y = Y/Z` and
+/// `x * y = T/Z`.
+#[derive(Clone)]
+struct Point {
+	x: BigUint,
+	y: BigUint,
+	z: BigUint,
+	t: BigUint,
+}
+
+impl Point {
+	/// The neutral element of the curve.
+	fn identity() -> Self {
+		Self { x: BigUint::zero(), y: BigUint::one(), z: BigUint::one(), t: BigUint::zero() }
+	}
+
+	/// Add two points, using the complete addition formula from RFC 8032.
+	fn add(&self, other: &Point) -> Point {
+		let p = &curve().p;
+		let sub = |a: &BigUint, b: &BigUint| (a + p - b) % p;
+		let a = sub(&self.y, &self.x) * sub(&other.y, &other.x) % p;
+		let b = (&self.y + &self.x) * (&other.y + &other.x) % p;
+		let c = &self.t * &curve().d2 % p * &other.t % p;
+		let d = &self.z * 2u32 * &other.z % p;
+		let (e, f, g, h) = (sub(&b, &a), sub(&d, &c), (&d + &c) % p, (&b + &a) % p);
+		Point { x: &e * &f % p, y: &g * &h % p, z: f * g % p, t: e * h % p }
+	}
+
+	/// Multiply the point by `scalar`, by doubling and adding.
+	fn mul(&self, scalar: &BigUint) -> Point {
+		let mut result = Point::identity();
+		for i in (0..scalar.bits()).rev() {
+			result = result.add(&result);
+			if scalar.bit(i) {
+				result = result.add(self);
+			}
+		}
+		result
+	}
+
+	/// Encode the point as the little endian `y` coordinate, with the lowest bit of `x` in the
+	/// highest bit.
+	fn encode(&self) -> [u8; 32] {
+		let p = &curve().p;
+		let z_inv = self.z.modpow(&(p - 2u32), p);
+		let x = &self.x * &z_inv % p;
+		let y = &self.y * &z_inv % p;
+		let mut bytes = to_bytes(&y);
+		if x.bit(0) {
+			bytes[31] |= 0x80;
+		}
+		bytes
+	}
+
+	/// Decode a point from its encoding, if it is a valid point on the curve.
+	fn decode(bytes: &[u8]) -> Option<Point> {
+		Self::decode_with(curve(), bytes)
+	}
+
+	fn decode_with(curve: &Curve, bytes: &[u8]) -> Option<Point> {
+		let p = &curve.p;
+		let mut bytes: [u8; 32] = bytes.try_into().ok()?;
+		let sign = bytes[31] >> 7 == 1;
+		bytes[31] &= 0x7f;
+		let y = BigUint::from_bytes_le(&bytes);
+		if y >= *p {
+			return None;
+		}
+
+		// Recover `x` from `x^2 = (y^2 - 1) / (d y^2 + 1)`, computing the square root and the
+		// division at once as in RFC 8032.
+		let y2 = &y * &y % p;
+		let u = (&y2 + p - 1u32) % p;
+		let v = (&curve.d * &y2 + 1u32) % p;
+		let v3 = v.modpow(&BigUint::from(3u32), p);
+		let v7 = &v3 * &v3 % p * &v % p;
+		let mut x = &u * &v3 % p * (&u * &v7 % p).modpow(&((p - 5u32) >> 3u32), p) % p;
+		let vx2 = &v * &x % p * &x % p;
+		if vx2 != u {
+			if !((&vx2 + &u) % p).is_zero() {
+				return None;
+			}
+			x = x * &curve.sqrt_m1 % p;
+		}
+
+		if x.is_zero() && sign {
+			return None;
+		}
+		if x.bit(0) != sign {
+			x = p - x;
+		}
+		let t = &x * &y % p;
+		Some(Point { x, y, z: BigUint::one(), t })
+	}
+}
+
+/// Encode a field element or scalar as 32 little endian bytes.
+fn to_bytes(n: &BigUint) -> [u8; 32] {
+	let mut bytes = [0u8; 32];
+	let le = n.to_bytes_le();
+	bytes[..le.len()].copy_from_slice(&le);
+	bytes
+}
+
+/// Hash the concatenation of `parts` with SHA-512, and reduce it to a scalar.
+fn hash_to_scalar(parts: &[&[u8]]) -> BigUint {
+	let mut hasher = Sha512::new();
+	for part in parts {
+		hasher.update(part);
+	}
+	BigUint::from_bytes_le(&hasher.finalize()) % &curve().l
+}
+
+#[cfg(test)]
+mod test {
+	use super::{Pair, Public, Signature};
+	use crate::support::{Pair as _, Verify};
+
+	fn from_hex<const N: usize>(hex: &str) -> [u8; N] {
+		let bytes = (0..hex.len())
+			.step_by(2)
+			.map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+			.collect::<Vec<_>>();
+		bytes.try_into().unwrap()
+	}
+
+	#[test]
+	fn rfc8032_test_vectors() {
+		let vectors = [
+			(
+				"9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+				"d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+				&b""[..],
+				"e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+			),
+			(
+				"4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+				"3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+				&[0x72][..],
+				"92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+			),
+		];
+		for (seed, public, message, signature) in vectors {
+			let pair = Pair::from_seed(&from_hex(seed));
+			assert_eq!(pair.public(), Public(from_hex(public)));
+			let signature = Signature(from_hex(signature));
+			assert_eq!(pair.sign(message), signature);
+			assert!(signature.verify(message, &pair.public()));
+		}
+	}
+
+	#[test]
+	fn rejects_invalid_signatures() {
+		let pair = Pair::from_seed(&[1; 32]);
+		let other = Pair::from_seed(&[2; 32]);
+		let signature = pair.sign(b"message");
+		assert!(signature.verify(b"message", &pair.public()));
+		assert!(!signature.verify(b"other message", &pair.public()));
+		assert!(!signature.verify(b"message", &other.public()));
+
+		let mut tampered = signature;
+		tampered.0[40] ^= 1;
+		assert!(!tampered.verify(b"message", &pair.public()));
+	}
+}
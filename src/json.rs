@@ -0,0 +1,401 @@
+//! A minimal JSON value, with a parser and a serializer, for talking to external tools.
+//!
+//! Only integer numbers are supported, since nothing in the runtime uses fractions, and they cover
+//! the full range of balances. Bytes, such as hashes and encoded extrinsics, are represented as hex
+//! strings prefixed with `0x`.
+
+use std::fmt::{self, Write};
+
+/// The maximum nesting of arrays and objects when parsing, so malicious input cannot overflow the
+/// stack.
+const MAX_DEPTH: usize = 64;
+
+/// A JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	Null,
+	Bool(bool),
+	Number(i128),
+	String(String),
+	Array(Vec<Value>),
+	/// The fields of an object, in the order they were parsed or inserted.
+	Object(Vec<(String, Value)>),
+}
+
+impl Value {
+	/// Create an object from its fields.
+	pub fn object<K: Into<String>>(fields: impl IntoIterator<Item = (K, Value)>) -> Self {
+		Value::Object(fields.into_iter().map(|(key, value)| (key.into(), value)).collect())
+	}
+
+	/// Parse a JSON document.
+	pub fn parse(text: &str) -> Result<Self, &'static str> {
+		let mut parser = Parser { input: text.as_bytes(), pos: 0 };
+		let value = parser.value(0)?;
+		parser.whitespace();
+		if parser.pos != parser.input.len() {
+			return Err("trailing characters after the JSON value");
+		}
+		Ok(value)
+	}
+
+	/// Get the field `key` of an object, or `None` if this is not an object or has no such field.
+	pub fn get(&self, key: &str) -> Option<&Value> {
+		match self {
+			Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, value)| value),
+			_ => None,
+		}
+	}
+
+	pub fn is_null(&self) -> bool {
+		matches!(self, Value::Null)
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match self {
+			Value::Bool(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	pub fn as_i128(&self) -> Option<i128> {
+		match self {
+			Value::Number(value) => Some(*value),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			Value::String(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> Option<&[Value]> {
+		match self {
+			Value::Array(values) => Some(values),
+			_ => None,
+		}
+	}
+
+	pub fn as_object(&self) -> Option<&[(String, Value)]> {
+		match self {
+			Value::Object(fields) => Some(fields),
+			_ => None,
+		}
+	}
+}
+
+macro_rules! impl_from_int {
+	( $( $t:ty ),* ) => {
+		$(
+			impl From<$t> for Value {
+				fn from(value: $t) -> Self {
+					Value::Number(value as i128)
+				}
+			}
+		)*
+	};
+}
+
+impl_from_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, usize);
+
+impl From<bool> for Value {
+	fn from(value: bool) -> Self {
+		Value::Bool(value)
+	}
+}
+
+impl From<&str> for Value {
+	fn from(value: &str) -> Self {
+		Value::String(value.to_string())
+	}
+}
+
+impl From<String> for Value {
+	fn from(value: String) -> Self {
+		Value::String(value)
+	}
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+	fn from(value: Option<T>) -> Self {
+		value.map_or(Value::Null, Into::into)
+	}
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+	fn from(values: Vec<T>) -> Self {
+		Value::Array(values.into_iter().map(Into::into).collect())
+	}
+}
+
+/// Values are displayed as compact JSON.
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Value::Null => f.write_str("null"),
+			Value::Bool(value) => write!(f, "{}", value),
+			Value::Number(value) => write!(f, "{}", value),
+			Value::String(value) => write_string(f, value),
+			Value::Array(values) => {
+				f.write_char('[')?;
+				for (i, value) in values.iter().enumerate() {
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					write!(f, "{}", value)?;
+				}
+				f.write_char(']')
+			},
+			Value::Object(fields) => {
+				f.write_char('{')?;
+				for (i, (key, value)) in fields.iter().enumerate() {
+					if i > 0 {
+						f.write_char(',')?;
+					}
+					write_string(f, key)?;
+					write!(f, ":{}", value)?;
+				}
+				f.write_char('}')
+			},
+		}
+	}
+}
+
+/// Write `value` as a quoted JSON string, escaping quotes, backslashes and control characters.
+fn write_string(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+	f.write_char('"')?;
+	for c in value.chars() {
+		match c {
+			'"' => f.write_str("\\\"")?,
+			'\\' => f.write_str("\\\\")?,
+			'\n' => f.write_str("\\n")?,
+			'\r' => f.write_str("\\r")?,
+			'\t' => f.write_str("\\t")?,
+			c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+			c => f.write_char(c)?,
+		}
+	}
+	f.write_char('"')
+}
+
+/// A recursive descent parser over the bytes of a JSON document.
+struct Parser<'a> {
+	input: &'a [u8],
+	pos: usize,
+}
+
+impl Parser<'_> {
+	fn peek(&self) -> Option<u8> {
+		self.input.get(self.pos).copied()
+	}
+
+	fn whitespace(&mut self) {
+		while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+			self.pos += 1;
+		}
+	}
+
+	/// Consume `literal` if the input continues with it.
+	fn eat(&mut self, literal: &str) -> bool {
+		let matches = self.input[self.pos..].starts_with(literal.as_bytes());
+		if matches {
+			self.pos += literal.len();
+		}
+		matches
+	}
+
+	/// Parse a value nested in `depth` arrays and objects.
+	fn value(&mut self, depth: usize) -> Result<Value, &'static str> {
+		if depth > MAX_DEPTH {
+			return Err("JSON is nested too deeply");
+		}
+		self.whitespace();
+		match self.peek().ok_or("unexpected end of JSON")? {
+			b'n' if self.eat("null") => Ok(Value::Null),
+			b't' if self.eat("true") => Ok(Value::Bool(true)),
+			b'f' if self.eat("false") => Ok(Value::Bool(false)),
+			b'"' => Ok(Value::String(self.string()?)),
+			b'[' => {
+				self.pos += 1;
+				let mut values = Vec::new();
+				self.whitespace();
+				if !self.eat("]") {
+					loop {
+						values.push(self.value(depth + 1)?);
+						self.whitespace();
+						if self.eat("]") {
+							break;
+						}
+						if !self.eat(",") {
+							return Err("expected ',' or ']' in array");
+						}
+					}
+				}
+				Ok(Value::Array(values))
+			},
+			b'{' => {
+				self.pos += 1;
+				let mut fields = Vec::new();
+				self.whitespace();
+				if !self.eat("}") {
+					loop {
+						self.whitespace();
+						if self.peek() != Some(b'"') {
+							return Err("expected a string key in object");
+						}
+						let key = self.string()?;
+						self.whitespace();
+						if !self.eat(":") {
+							return Err("expected ':' in object");
+						}
+						fields.push((key, self.value(depth + 1)?));
+						self.whitespace();
+						if self.eat("}") {
+							break;
+						}
+						if !self.eat(",") {
+							return Err("expected ',' or '}' in object");
+						}
+					}
+				}
+				Ok(Value::Object(fields))
+			},
+			b'-' | b'0'..=b'9' => self.number(),
+			_ => Err("unexpected character in JSON"),
+		}
+	}
+
+	fn number(&mut self) -> Result<Value, &'static str> {
+		let start = self.pos;
+		self.eat("-");
+		while matches!(self.peek(), Some(b'0'..=b'9')) {
+			self.pos += 1;
+		}
+		if matches!(self.peek(), Some(b'.' | b'e' | b'E')) {
+			return Err("only integer numbers are supported");
+		}
+		let digits = std::str::from_utf8(&self.input[start..self.pos]).expect("digits are ascii");
+		digits.parse().map(Value::Number).map_err(|_| "invalid number")
+	}
+
+	/// Parse a quoted string, starting at the opening quote.
+	fn string(&mut self) -> Result<String, &'static str> {
+		self.pos += 1;
+		let mut bytes = Vec::new();
+		loop {
+			let byte = self.peek().ok_or("unterminated string")?;
+			self.pos += 1;
+			match byte {
+				b'"' => break,
+				b'\\' => {
+					let escape = self.peek().ok_or("unterminated string")?;
+					self.pos += 1;
+					let c = match escape {
+						b'"' => '"',
+						b'\\' => '\\',
+						b'/' => '/',
+						b'b' => '\u{8}',
+						b'f' => '\u{c}',
+						b'n' => '\n',
+						b'r' => '\r',
+						b't' => '\t',
+						b'u' => self.unicode_escape()?,
+						_ => return Err("invalid escape in string"),
+					};
+					bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+				},
+				byte if byte < 0x20 => return Err("control character in string"),
+				byte => bytes.push(byte),
+			}
+		}
+		String::from_utf8(bytes).map_err(|_| "invalid utf-8 in string")
+	}
+
+	/// Parse the code point of a `\u` escape, which is a pair of escapes for characters outside
+	/// the basic multilingual plane.
+	fn unicode_escape(&mut self) -> Result<char, &'static str> {
+		let high = self.hex4()?;
+		let code = if (0xd800..0xdc00).contains(&high) {
+			if !self.eat("\\u") {
+				return Err("unpaired surrogate in string");
+			}
+			let low = self.hex4()?;
+			if !(0xdc00..0xe000).contains(&low) {
+				return Err("unpaired surrogate in string");
+			}
+			0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+		} else {
+			high
+		};
+		char::from_u32(code).ok_or("invalid unicode escape in string")
+	}
+
+	fn hex4(&mut self) -> Result<u32, &'static str> {
+		let digits = self.input.get(self.pos..self.pos + 4).ok_or("unterminated string")?;
+		let digits = std::str::from_utf8(digits).map_err(|_| "invalid unicode escape in string")?;
+		let code = u32::from_str_radix(digits, 16).map_err(|_| "invalid unicode escape in string")?;
+		self.pos += 4;
+		Ok(code)
+	}
+}
+
+/// Encode bytes as a hex string prefixed with `0x`.
+pub fn to_hex(bytes: &[u8]) -> String {
+	let mut hex = String::with_capacity(2 + 2 * bytes.len());
+	hex.push_str("0x");
+	for byte in bytes {
+		write!(hex, "{:02x}", byte).expect("writing to a string cannot fail");
+	}
+	hex
+}
+
+/// Decode a hex string prefixed with `0x`.
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, &'static str> {
+	let hex = hex.strip_prefix("0x").ok_or("hex string must start with 0x")?;
+	if hex.len() % 2 != 0 || !hex.is_ascii() {
+		return Err("invalid hex string");
+	}
+	(0..hex.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| "invalid hex string"))
+		.collect()
+}
+
+#[cfg(test)]
+mod test {
+	use super::{from_hex, to_hex, Value};
+
+	#[test]
+	fn parse_and_display() {
+		let text = r#" { "a" : [1, -2, true, false, null], "b": {"c": "d\"\\\né😀"} } "#;
+		let value = Value::parse(text).unwrap();
+		assert_eq!(value.get("a").unwrap().as_array().unwrap()[1], Value::Number(-2));
+		assert_eq!(value.get("b").unwrap().get("c").unwrap().as_str(), Some("d\"\\\né😀"));
+		assert_eq!(value.to_string(), r#"{"a":[1,-2,true,false,null],"b":{"c":"d\"\\\né😀"}}"#);
+		assert_eq!(Value::parse(&value.to_string()), Ok(value));
+
+		let balance = Value::from(u128::MAX / 2);
+		assert_eq!(Value::parse(&balance.to_string()), Ok(balance));
+		assert_eq!(Value::from("\u{1}").to_string(), r#""\u0001""#);
+
+		assert_eq!(Value::parse("[1,]"), Err("unexpected character in JSON"));
+		assert_eq!(Value::parse("{\"a\" 1}"), Err("expected ':' in object"));
+		assert_eq!(Value::parse("1.5"), Err("only integer numbers are supported"));
+		assert_eq!(Value::parse("\"abc"), Err("unterminated string"));
+		assert_eq!(Value::parse("[] []"), Err("trailing characters after the JSON value"));
+		assert_eq!(Value::parse(&"[".repeat(100)), Err("JSON is nested too deeply"));
+	}
+
+	#[test]
+	fn hex_round_trip() {
+		assert_eq!(to_hex(&[0, 1, 0xab]), "0x0001ab");
+		assert_eq!(from_hex("0x0001ab"), Ok(vec![0, 1, 0xab]));
+		assert_eq!(from_hex("0x"), Ok(vec![]));
+		assert_eq!(from_hex("0001"), Err("hex string must start with 0x"));
+		assert_eq!(from_hex("0x123"), Err("invalid hex string"));
+		assert_eq!(from_hex("0xzz"), Err("invalid hex string"));
+	}
+}
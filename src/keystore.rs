@@ -0,0 +1,440 @@
+//! Key generation, derivation and storage.
+//!
+//! Keys are described by secret URIs of the form `<phrase or seed>//<hard junction>///<password>`,
+//! for example `//Alice` for the development key of Alice:
+//! - The phrase is a BIP39 mnemonic, or a 32 byte seed in hex prefixed with `0x`. When it is left
+//!   out, the public `DEV_PHRASE` is used.
+//! - Each `//junction` derives a child key. Ed25519 only supports hard derivation, so soft
+//!   junctions like `/junction` are rejected.
+//! - The password after `///` is mixed into the seed derived from the mnemonic.
+
+use crate::{
+	codec::Encode,
+	ed25519,
+	support::{self, IdentifyAccount, Pair},
+	types,
+};
+use sha2::{Digest, Sha256, Sha512};
+use std::{collections::BTreeMap, fs, io::Read, path::PathBuf, sync::OnceLock};
+
+/// The mnemonic of the development keys. It is public, so keys derived from it must only be used
+/// for tests and development chains.
+pub const DEV_PHRASE: &str =
+	"bottom drive obey lake curtain smoke basket hold race lonely fit walk";
+
+/// The BIP39 English word list.
+const WORDS: &str = include_str!("keystore/english.txt");
+
+/// The number of PBKDF2 rounds used to derive the encryption key of a keystore file from its
+/// password.
+const KEYSTORE_ROUNDS: u32 = 10_000;
+
+/// A 32 byte secret seed, from which an Ed25519 key pair is created.
+pub type Seed = [u8; 32];
+
+/// Development keys, derived from `DEV_PHRASE` with their name as a hard junction, like `//Alice`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DevKey {
+	Alice,
+	Bob,
+	Charlie,
+	Dave,
+	Eve,
+	Ferdie,
+}
+
+impl DevKey {
+	/// The key pair of this development key.
+	pub fn pair(self) -> ed25519::Pair {
+		// Deriving a key from a mnemonic is slow, so we only do it once for each key.
+		static PAIRS: [OnceLock<ed25519::Pair>; 6] = [const { OnceLock::new() }; 6];
+		PAIRS[self as usize]
+			.get_or_init(|| {
+				pair_from_uri(&format!("//{:?}", self)).expect("development key URIs are valid")
+			})
+			.clone()
+	}
+
+	/// The public key of this development key.
+	pub fn public(self) -> ed25519::Public {
+		self.pair().public()
+	}
+
+	/// The account of this development key.
+	pub fn account(self) -> types::AccountId {
+		self.public().into_account()
+	}
+}
+
+/// Generate a new random mnemonic of 12 words.
+pub fn generate_mnemonic() -> Result<String, &'static str> {
+	Ok(entropy_to_mnemonic(&random_bytes::<16>()?))
+}
+
+/// Create the key pair described by the secret URI `suri`.
+pub fn pair_from_uri(suri: &str) -> Result<ed25519::Pair, &'static str> {
+	Ok(ed25519::Pair::from_seed(&seed_from_uri(suri)?))
+}
+
+/// Derive the seed of the key described by the secret URI `suri`.
+pub fn seed_from_uri(suri: &str) -> Result<Seed, &'static str> {
+	let (suri, password) = match suri.split_once("///") {
+		Some((suri, password)) => (suri, password),
+		None => (suri, ""),
+	};
+	let (phrase, mut path) = suri.find('/').map_or((suri, ""), |index| suri.split_at(index));
+
+	let mut seed = if phrase.is_empty() {
+		seed_from_mnemonic(DEV_PHRASE, password)?
+	} else if let Some(hex) = phrase.strip_prefix("0x") {
+		decode_hex(hex).ok_or("invalid hex seed")?
+	} else {
+		seed_from_mnemonic(phrase, password)?
+	};
+
+	while !path.is_empty() {
+		let Some(rest) = path.strip_prefix("//") else {
+			return Err("ed25519 keys only support hard derivation");
+		};
+		let (junction, rest) = rest.find('/').map_or((rest, ""), |index| rest.split_at(index));
+		if junction.is_empty() {
+			return Err("invalid derivation path");
+		}
+		seed = derive_hard(&seed, junction);
+		path = rest;
+	}
+	Ok(seed)
+}
+
+/// Derive the seed of a mnemonic: the first 32 bytes of PBKDF2 over its entropy, salted with
+/// `mnemonic` and the password.
+pub fn seed_from_mnemonic(phrase: &str, password: &str) -> Result<Seed, &'static str> {
+	let entropy = mnemonic_to_entropy(phrase)?;
+	let salt = format!("mnemonic{}", password);
+	let key = pbkdf2_sha512(&entropy, salt.as_bytes(), 2048);
+	Ok(key[..32].try_into().expect("key has 64 bytes"))
+}
+
+/// Derive the seed of the hard child `junction` of `seed`. A numeric junction is encoded as a
+/// number, anything else as a string.
+fn derive_hard(seed: &Seed, junction: &str) -> Seed {
+	let encoded = match junction.parse::<u64>() {
+		Ok(index) => index.encode(),
+		Err(_) => junction.encode(),
+	};
+	let mut chain_code = [0u8; 32];
+	if encoded.len() > 32 {
+		chain_code = support::hash(&encoded);
+	} else {
+		chain_code[..encoded.len()].copy_from_slice(&encoded);
+	}
+	let mut data = "Ed25519HDKD".encode();
+	seed.encode_to(&mut data);
+	chain_code.encode_to(&mut data);
+	support::hash(&data)
+}
+
+/// Encode `entropy` as a BIP39 mnemonic: the bits of the entropy followed by a checksum, split in
+/// groups of 11 bits which each select a word.
+fn entropy_to_mnemonic(entropy: &[u8]) -> String {
+	let words = WORDS.lines().collect::<Vec<_>>();
+	let checksum = Sha256::digest(entropy);
+	let mut bits = entropy
+		.iter()
+		.flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+		.collect::<Vec<_>>();
+	bits.extend((0..entropy.len() / 4).map(|i| (checksum[i / 8] >> (7 - i % 8)) & 1 == 1));
+	bits.chunks(11)
+		.map(|chunk| words[chunk.iter().fold(0, |index, bit| index << 1 | *bit as usize)])
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Decode a BIP39 mnemonic to its entropy, checking its checksum.
+fn mnemonic_to_entropy(phrase: &str) -> Result<Vec<u8>, &'static str> {
+	let words = WORDS.lines().collect::<Vec<_>>();
+	let indices = phrase
+		.split_whitespace()
+		.map(|word| words.iter().position(|w| *w == word))
+		.collect::<Option<Vec<_>>>()
+		.ok_or("invalid word in mnemonic")?;
+	if indices.len() < 12 || indices.len() > 24 || indices.len() % 3 != 0 {
+		return Err("invalid number of words in mnemonic");
+	}
+
+	let bits = indices
+		.iter()
+		.flat_map(|index| (0..11).rev().map(move |i| (index >> i) & 1 == 1))
+		.collect::<Vec<_>>();
+	let entropy_bits = bits.len() * 32 / 33;
+	let entropy = bits[..entropy_bits]
+		.chunks(8)
+		.map(|chunk| chunk.iter().fold(0u8, |byte, bit| byte << 1 | *bit as u8))
+		.collect::<Vec<_>>();
+	if entropy_to_mnemonic(&entropy)
+		!= indices.iter().map(|index| words[*index]).collect::<Vec<_>>().join(" ")
+	{
+		return Err("invalid mnemonic checksum");
+	}
+	Ok(entropy)
+}
+
+/// A store of key pairs, kept in a file encrypted with a password.
+///
+/// The file holds a random salt, the seeds of the keys encrypted with a key derived from the
+/// password and the salt, and a MAC of both, which detects a wrong password or a corrupted file.
+pub struct Keystore {
+	path: PathBuf,
+	password: String,
+	/// The seed of each key in the store, by public key.
+	seeds: BTreeMap<ed25519::Public, Seed>,
+}
+
+impl Keystore {
+	/// Open the keystore at `path`, decrypting it with `password`. If the file does not exist,
+	/// the keystore is empty and the file is created when the first key is added.
+	pub fn open(path: impl Into<PathBuf>, password: &str) -> Result<Self, &'static str> {
+		let path = path.into();
+		let mut seeds = BTreeMap::new();
+		if path.exists() {
+			let file = fs::read(&path).map_err(|_| "cannot read keystore file")?;
+			for seed in decrypt(&file, password)?.chunks(32) {
+				let seed: Seed = seed.try_into().map_err(|_| "corrupted keystore file")?;
+				seeds.insert(ed25519::Pair::from_seed(&seed).public(), seed);
+			}
+		}
+		Ok(Self { path, password: password.to_string(), seeds })
+	}
+
+	/// Generate a new key from a random mnemonic and add it to the store. Returns the mnemonic,
+	/// which is needed to recover the key, and the public key.
+	pub fn generate(&mut self) -> Result<(String, ed25519::Public), &'static str> {
+		let phrase = generate_mnemonic()?;
+		let public = self.insert(&phrase)?;
+		Ok((phrase, public))
+	}
+
+	/// Add the key described by the secret URI `suri` to the store.
+	pub fn insert(&mut self, suri: &str) -> Result<ed25519::Public, &'static str> {
+		let seed = seed_from_uri(suri)?;
+		let public = ed25519::Pair::from_seed(&seed).public();
+		self.seeds.insert(public, seed);
+		self.save()?;
+		Ok(public)
+	}
+
+	/// The public keys in the store.
+	pub fn public_keys(&self) -> Vec<ed25519::Public> {
+		self.seeds.keys().copied().collect()
+	}
+
+	/// The key pair of `public`, if it is in the store.
+	pub fn pair(&self, public: &ed25519::Public) -> Option<ed25519::Pair> {
+		self.seeds.get(public).map(ed25519::Pair::from_seed)
+	}
+
+	/// Sign `message` with the key of `public`.
+	#[allow(dead_code, reason = "the node seals blocks with the key pairs of the keystore")]
+	pub fn sign(
+		&self,
+		public: &ed25519::Public,
+		message: &[u8],
+	) -> Result<ed25519::Signature, &'static str> {
+		Ok(self.pair(public).ok_or("key is not in the keystore")?.sign(message))
+	}
+
+	/// Write the store to its file, encrypted with a fresh salt.
+	fn save(&self) -> Result<(), &'static str> {
+		let seeds = self.seeds.values().flatten().copied().collect::<Vec<_>>();
+		let file = encrypt(&seeds, &self.password)?;
+		fs::write(&self.path, file).map_err(|_| "cannot write keystore file")
+	}
+}
+
+/// Encrypt `plaintext` with `password`, as `salt | ciphertext | mac`.
+fn encrypt(plaintext: &[u8], password: &str) -> Result<Vec<u8>, &'static str> {
+	let salt = random_bytes::<16>()?;
+	let (encryption_key, mac_key) = keystore_keys(password, &salt);
+	let mut file = salt.to_vec();
+	file.extend(apply_keystream(&encryption_key, plaintext));
+	let mac = hmac_sha512(&mac_key, &file);
+	file.extend(mac);
+	Ok(file)
+}
+
+/// Decrypt a file made by `encrypt`, checking its MAC.
+fn decrypt(file: &[u8], password: &str) -> Result<Vec<u8>, &'static str> {
+	if file.len() < 16 + 64 {
+		return Err("corrupted keystore file");
+	}
+	let (data, mac) = file.split_at(file.len() - 64);
+	let (salt, ciphertext) = data.split_at(16);
+	let (encryption_key, mac_key) = keystore_keys(password, salt);
+	if hmac_sha512(&mac_key, data)[..] != *mac {
+		return Err("wrong password or corrupted keystore file");
+	}
+	Ok(apply_keystream(&encryption_key, ciphertext))
+}
+
+/// Derive the encryption key and the MAC key of a keystore file from its password and salt.
+fn keystore_keys(password: &str, salt: &[u8]) -> ([u8; 32], [u8; 32]) {
+	let key = pbkdf2_sha512(password.as_bytes(), salt, KEYSTORE_ROUNDS);
+	(
+		key[..32].try_into().expect("key has 64 bytes"),
+		key[32..].try_into().expect("key has 64 bytes"),
+	)
+}
+
+/// XOR `data` with a keystream of SHA-512 hashes of the key and a counter. Since each save uses a
+/// fresh salt, and so a fresh key, the keystream is never reused.
+fn apply_keystream(key: &[u8; 32], data: &[u8]) -> Vec<u8> {
+	data.chunks(64)
+		.enumerate()
+		.flat_map(|(counter, chunk)| {
+			let block = Sha512::new()
+				.chain_update(key)
+				.chain_update((counter as u64).to_le_bytes())
+				.finalize();
+			chunk.iter().zip(block).map(|(byte, key)| byte ^ key).collect::<Vec<_>>()
+		})
+		.collect()
+}
+
+/// HMAC with SHA-512, as specified in RFC 2104, keyed once and used for many messages.
+#[derive(Clone)]
+struct HmacSha512 {
+	/// The hash state after the inner padded key.
+	inner: Sha512,
+	/// The hash state after the outer padded key.
+	outer: Sha512,
+}
+
+impl HmacSha512 {
+	fn new(key: &[u8]) -> Self {
+		let mut block = [0u8; 128];
+		if key.len() > block.len() {
+			block[..64].copy_from_slice(&Sha512::digest(key));
+		} else {
+			block[..key.len()].copy_from_slice(key);
+		}
+		Self {
+			inner: Sha512::new().chain_update(block.map(|byte| byte ^ 0x36)),
+			outer: Sha512::new().chain_update(block.map(|byte| byte ^ 0x5c)),
+		}
+	}
+
+	fn mac(&self, message: &[u8]) -> [u8; 64] {
+		let inner = self.inner.clone().chain_update(message).finalize();
+		self.outer.clone().chain_update(inner).finalize().into()
+	}
+}
+
+fn hmac_sha512(key: &[u8], message: &[u8]) -> [u8; 64] {
+	HmacSha512::new(key).mac(message)
+}
+
+/// PBKDF2 with HMAC-SHA-512, as specified in RFC 8018, deriving a single 64 byte block.
+fn pbkdf2_sha512(password: &[u8], salt: &[u8], rounds: u32) -> [u8; 64] {
+	let hmac = HmacSha512::new(password);
+	let mut u = hmac.mac(&[salt, &1u32.to_be_bytes()].concat());
+	let mut key = u;
+	for _ in 1..rounds {
+		u = hmac.mac(&u);
+		key.iter_mut().zip(u).for_each(|(byte, u)| *byte ^= u);
+	}
+	key
+}
+
+/// Decode a 32 byte hex string.
+fn decode_hex(hex: &str) -> Option<[u8; 32]> {
+	if hex.len() != 64 || !hex.is_ascii() {
+		return None;
+	}
+	let mut bytes = [0u8; 32];
+	for (i, byte) in bytes.iter_mut().enumerate() {
+		*byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+	}
+	Some(bytes)
+}
+
+/// Random bytes from the operating system.
+fn random_bytes<const N: usize>() -> Result<[u8; N], &'static str> {
+	let mut bytes = [0u8; N];
+	fs::File::open("/dev/urandom")
+		.and_then(|mut file| file.read_exact(&mut bytes))
+		.map_err(|_| "cannot read random bytes")?;
+	Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn bip39_mnemonics() {
+		let phrase = entropy_to_mnemonic(&[0; 16]);
+		assert_eq!(phrase, "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about");
+		assert_eq!(mnemonic_to_entropy(&phrase), Ok(vec![0; 16]));
+		assert!(mnemonic_to_entropy(DEV_PHRASE).is_ok());
+		assert_eq!(
+			mnemonic_to_entropy(&DEV_PHRASE.replace("walk", "wall")),
+			Err("invalid mnemonic checksum")
+		);
+		assert_eq!(
+			mnemonic_to_entropy("abandon about"),
+			Err("invalid number of words in mnemonic")
+		);
+
+		// The BIP39 seed of the mnemonic with the password `TREZOR`, from the reference test vectors.
+		let seed = pbkdf2_sha512(phrase.as_bytes(), b"mnemonicTREZOR", 2048);
+		assert_eq!(seed[..8], [0xc5, 0x52, 0x57, 0xc3, 0x60, 0xc0, 0x7c, 0x72],);
+
+		let generated = generate_mnemonic().unwrap();
+		assert_eq!(generated.split(' ').count(), 12);
+		assert!(mnemonic_to_entropy(&generated).is_ok());
+	}
+
+	#[test]
+	fn derivation_paths() {
+		let alice = seed_from_uri("//Alice").unwrap();
+		assert_eq!(seed_from_uri(&format!("{}//Alice", DEV_PHRASE)), Ok(alice));
+		assert_ne!(seed_from_uri("//Bob"), Ok(alice));
+		assert_ne!(seed_from_uri("//Alice///password"), Ok(alice));
+		assert_ne!(seed_from_uri("//Alice//stash"), Ok(alice));
+		assert_eq!(seed_from_uri("//Alice/soft"), Err("ed25519 keys only support hard derivation"));
+
+		let hex = "0x0101010101010101010101010101010101010101010101010101010101010101";
+		assert_eq!(seed_from_uri(hex), Ok([1; 32]));
+		assert_eq!(DevKey::Alice.public(), ed25519::Pair::from_seed(&alice).public());
+	}
+
+	#[test]
+	fn encrypted_keystore_file() {
+		let path = std::env::temp_dir().join(format!("keystore-test-{}", std::process::id()));
+		let _ = fs::remove_file(&path);
+
+		let mut keystore = Keystore::open(&path, "password").unwrap();
+		let alice = keystore.insert("//Alice").unwrap();
+		let (phrase, generated) = keystore.generate().unwrap();
+		assert_eq!(pair_from_uri(&phrase).unwrap().public(), generated);
+
+		// The seeds are not stored in the clear.
+		let file = fs::read(&path).unwrap();
+		let seed = seed_from_uri("//Alice").unwrap();
+		assert!(!file.windows(32).any(|window| window == seed));
+
+		assert!(Keystore::open(&path, "wrong").is_err());
+		let keystore = Keystore::open(&path, "password").unwrap();
+		let mut expected = vec![alice, generated];
+		expected.sort();
+		assert_eq!(keystore.public_keys(), expected);
+
+		let signature = keystore.sign(&alice, b"message").unwrap();
+		assert!(support::Verify::verify(&signature, b"message", &alice));
+		assert_eq!(
+			keystore.sign(&DevKey::Bob.public(), b"message"),
+			Err("key is not in the keystore")
+		);
+		fs::remove_file(&path).unwrap();
+	}
+}
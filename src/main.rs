@@ -1,23 +1,57 @@
+mod account;
+mod assets;
+mod authorities;
 mod balances;
+mod block_builder;
+mod client;
+mod codec;
+#[allow(dead_code, reason = "only tests build calls from JSON, until a command line tool does")]
+mod dynamic;
+mod ed25519;
+mod json;
+mod keystore;
+mod metadata;
+mod node;
+mod p2p;
 mod system;
 mod support;
 mod proof_of_existence;
+mod proxy;
+mod rpc;
+mod scheduler;
+#[cfg(test)]
+mod simulator;
+mod storage;
+mod timestamp;
+mod transaction_pool;
+mod trie;
 
 use std::vec;
 
-use crate::support::Dispatch;
+use crate::{
+	block_builder::BlockBuilder,
+	codec::Decode,
+	support::{Dispatch, IdentifyAccount, Pair},
+};
 
 mod types {
 	use crate::support;
 
-	pub type AccountId = String;
+	pub type AccountId = crate::account::AccountId32;
 	pub type Balance = u128;
 	pub type BlockNumber = u32;
 	pub type Nonce = u32;
-	pub type Extrinsic = support::Extrinsic<AccountId, crate::RuntimeCall>;
-	pub type Header = support::Header<BlockNumber>;
+	pub type Signature = crate::ed25519::Signature;
+	pub type Signer = <Signature as support::Verify>::Signer;
+	pub type Extrinsic = support::Extrinsic<Signer, Signature, crate::RuntimeCall, Nonce, Balance>;
+	pub type Header = support::Header<BlockNumber, Signature>;
 	pub type Block = support::Block<Header, Extrinsic>;
-	pub type Content = &'static str;
+	pub type Content = String;
+	pub type TaskName = String;
+	pub type AssetId = u32;
+	/// Milliseconds since the unix epoch.
+	pub type Moment = u64;
+	pub type AuthorityId = Signer;
 }
 
 //pub enum RuntimeCall {
@@ -29,14 +63,31 @@ mod types {
 #[macros::runtime]
 pub struct Runtime {
 	system: system::Pallet<Self>,
+	#[pallet_index(0)]
+	#[events]
 	balances: balances::Pallet<Self>,
+	#[pallet_index(1)]
 	proof_of_existence: proof_of_existence::Pallet<Self>,
+	#[pallet_index(2)]
+	#[runtime_dispatch]
+	proxy: proxy::Pallet<Self>,
+	#[pallet_index(3)]
+	#[scheduler]
+	scheduler: scheduler::Pallet<Self>,
+	#[pallet_index(4)]
+	#[runtime_dispatch]
+	assets: assets::Pallet<Self>,
+	#[pallet_index(5)]
+	timestamp: timestamp::Pallet<Self>,
+	#[pallet_index(6)]
+	authorities: authorities::Pallet<Self>,
 }
 
 impl system::Config for Runtime {
 	type AccountId = types::AccountId;
 	type BlockNumber = types::BlockNumber;
 	type Nonce = types::Nonce;
+	type Signature = types::Signature;
 }
 
 impl balances::Config for Runtime {
@@ -46,6 +97,95 @@ impl balances::Config for Runtime {
 
 impl proof_of_existence::Config for Runtime {
 	type Content = types::Content;
+	type Moment = types::Moment;
+}
+
+/// The kinds of proxy an account can register, restricting which pallets the proxy can call.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProxyType {
+	Any,
+	BalancesOnly,
+	ProofOfExistenceOnly,
+}
+
+impl support::InstanceFilter<RuntimeCall> for ProxyType {
+	fn filter(&self, call: &RuntimeCall) -> bool {
+		match self {
+			ProxyType::Any => true,
+			ProxyType::BalancesOnly => call.pallet_name() == "balances",
+			ProxyType::ProofOfExistenceOnly => call.pallet_name() == "proof_of_existence",
+		}
+	}
+}
+
+impl codec::Encode for ProxyType {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(*self as u8).encode_to(dest);
+	}
+}
+
+impl codec::Decode for ProxyType {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		match u8::decode_from(input)? {
+			0 => Ok(ProxyType::Any),
+			1 => Ok(ProxyType::BalancesOnly),
+			2 => Ok(ProxyType::ProofOfExistenceOnly),
+			_ => Err("invalid proxy type"),
+		}
+	}
+}
+
+impl metadata::TypeInfo for ProxyType {
+	fn type_def() -> metadata::TypeDef {
+		let variants = ["Any", "BalancesOnly", "ProofOfExistenceOnly"];
+		metadata::TypeDef::Enum(variants.map(String::from).to_vec())
+	}
+}
+
+impl proxy::Config for Runtime {
+	type RuntimeCall = RuntimeCall;
+	type ProxyType = ProxyType;
+}
+
+impl scheduler::Config for Runtime {
+	type RuntimeCall = RuntimeCall;
+	type TaskName = types::TaskName;
+	const MAX_WEIGHT_PER_BLOCK: support::Weight = 100;
+	const MAX_PERIODIC_COUNT: u32 = 10;
+}
+
+impl assets::Config for Runtime {
+	type AssetId = types::AssetId;
+	type Balance = types::Balance;
+}
+
+impl timestamp::Config for Runtime {
+	type Moment = types::Moment;
+	const MINIMUM_PERIOD: types::Moment = 3_000;
+}
+
+impl authorities::Config for Runtime {
+	type AuthorityId = types::AuthorityId;
+	const SESSION_LENGTH: u64 = 10;
+}
+
+/// The deposit reserved from the native balance of an account when it creates an asset.
+pub const ASSET_DEPOSIT: types::Balance = 10;
+
+// The proxy pallet authorizes calls on behalf of other accounts, but only the runtime can dispatch
+// them, so we hand any authorized call back to `dispatch` with the proxied account as the caller.
+impl support::RuntimeDispatch<proxy::Call<Runtime>> for Runtime {
+	fn runtime_dispatch(
+		&mut self,
+		caller: Self::Caller,
+		call: proxy::Call<Runtime>,
+	) -> support::DispatchResult {
+		self.proxy.dispatch(caller, call)?;
+		if let Some((real, call)) = self.proxy.take_pending() {
+			self.dispatch(real, call)?;
+		}
+		Ok(())
+	}
 }
 
 //impl Runtime {
@@ -101,49 +241,580 @@ impl proof_of_existence::Config for Runtime {
 //	}
 //}
 
-fn main() {
-    println!("Hello, rust!");
-	let mut runtime = Runtime::new();
-	let jae = String::from("jae");
-	let foo = String::from("foo");
-	let bar = String::from("bar");
-
-	runtime.balances.set_balance(&jae, 100);
-
-	let block_1 = types::Block {
-		header: support::Header { block_number: 1 },
-		extrinsics: vec![
-			support::Extrinsic {
-				caller: jae.clone(),
-				call: RuntimeCall::balances(balances::Call::transfer { to: (foo.clone()), amount: (11) })
-			},
-			support::Extrinsic {
-				caller: jae.clone(),
-				call: RuntimeCall::balances(balances::Call::transfer { to: (bar.clone()), amount: (11) })
-			},
-		],
-	};
+/// The fee of an extrinsic is one unit of the native currency per unit of weight of its call, plus
+/// the tip. Fees are burned.
+impl support::TransactionPayment for Runtime {
+	type AccountId = types::AccountId;
+	type Call = RuntimeCall;
+	type Balance = types::Balance;
+
+	fn compute_fee(call: &Self::Call, tip: Self::Balance) -> Self::Balance {
+		types::Balance::from(support::GetWeight::weight(call)).saturating_add(tip)
+	}
+
+	fn can_pay_fee(&self, who: &Self::AccountId, fee: Self::Balance) -> bool {
+		self.balances.balance(who).checked_sub(self.balances.locked(who)).is_some_and(|free| free >= fee)
+	}
 
-	let block_2 = types::Block {
-		header: support::Header { block_number: 2 },
-		extrinsics: vec![
-			support::Extrinsic {
-				caller: jae.clone(),
-				call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "jaeClaim" })
+	fn withdraw_fee(&mut self, who: &Self::AccountId, fee: Self::Balance) -> support::DispatchResult {
+		if !self.can_pay_fee(who, fee) {
+			return Err("cannot pay the fee");
+		}
+		self.balances.set_balance(who, self.balances.balance(who) - fee);
+		Ok(())
+	}
+}
+
+// The only unsigned extrinsic is the timestamp inherent, which every block must include. Claims
+// record the time of the block they are made in.
+impl support::DispatchUnsigned for Runtime {
+	type Call = RuntimeCall;
+
+	fn validate_unsigned(&self, call: &Self::Call) -> support::DispatchResult {
+		match call {
+			RuntimeCall::timestamp(timestamp::Call::set { now }) => self.timestamp.check(*now),
+			_ => Err("call cannot be dispatched unsigned"),
+		}
+	}
+
+	fn dispatch_unsigned(&mut self, call: Self::Call) -> support::DispatchResult {
+		match call {
+			RuntimeCall::timestamp(timestamp::Call::set { now }) => {
+				self.timestamp.set(now)?;
+				self.proof_of_existence.set_now(now);
+				Ok(())
 			},
-			support::Extrinsic {
-				caller: foo.clone(),
-				call: RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "fooClaim" })
+			_ => Err("call cannot be dispatched unsigned"),
+		}
+	}
+
+	fn check_inherents(&self, inherents: &[Self::Call]) -> support::DispatchResult {
+		if !self.timestamp.did_update() {
+			return Err("timestamp must be set in every block");
+		}
+		match inherents {
+			[RuntimeCall::timestamp(timestamp::Call::set { .. })] => Ok(()),
+			_ => Err("timestamp must be the first extrinsic"),
+		}
+	}
+}
+
+// Blocks must be sealed by the authority whose slot it is. A runtime without authorities, like most
+// of the runtimes in tests, lets anyone author blocks without a seal.
+impl support::CheckSeal for Runtime {
+	type Header = types::Header;
+
+	fn check_seal(&self, header: &Self::Header) -> support::DispatchResult {
+		use support::Verify;
+		let Some(author) = self.authorities.slot_author(header.block_number) else {
+			return Ok(());
+		};
+		let seal = header.seal.as_ref().ok_or("block is not sealed")?;
+		if !seal.verify(&header.hash(), &author) {
+			return Err("block is not sealed by the slot author");
+		}
+		Ok(())
+	}
+}
+
+/// The events of all pallets in the runtime.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone)]
+pub enum RuntimeEvent {
+	balances(balances::Event<Runtime>),
+}
+
+impl support::Events for Runtime {
+	type Event = RuntimeEvent;
+
+	fn events(&self) -> Vec<Self::Event> {
+		self.balances.events().into_iter().map(RuntimeEvent::balances).collect()
+	}
+}
+
+// Creating an asset reserves a deposit in the native currency of the balances pallet, which is
+// returned when the asset is destroyed.
+impl support::RuntimeDispatch<assets::Call<Runtime>> for Runtime {
+	fn runtime_dispatch(
+		&mut self,
+		caller: Self::Caller,
+		call: assets::Call<Runtime>,
+	) -> support::DispatchResult {
+		match call {
+			assets::Call::create { .. } => {
+				self.balances.reserve(&caller, ASSET_DEPOSIT)?;
+				self.assets.dispatch(caller.clone(), call).inspect_err(|_| {
+					// Nothing else can have touched the reserve in between, so this cannot fail.
+					let _ = self.balances.unreserve(&caller, ASSET_DEPOSIT);
+				})
 			},
-			support::Extrinsic {
-				caller: jae.clone(),
-				call: RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim { claim: "randomClaim" })
+			assets::Call::destroy { .. } => {
+				self.assets.dispatch(caller.clone(), call)?;
+				self.balances.unreserve(&caller, ASSET_DEPOSIT)
 			},
-		]
+			call => self.assets.dispatch(caller, call),
+		}
+	}
+}
+
+fn main() {
+	// `node` runs a node of the development chain and `key` adds keys to its keystore, see the
+	// `node` module. Without arguments, we run a short demo chain in this process.
+	let mut args = std::env::args().skip(1);
+	let result = match args.next().as_deref() {
+		Some("node") => node::NodeConfig::parse(args).and_then(node::run),
+		Some("key") => node::key_command(args),
+		Some(_) => Err("unknown command, expected `node` or `key`"),
+		None => {
+			demo();
+			Ok(())
+		},
 	};
+	if let Err(e) = result {
+		eprintln!("Error: {}", e);
+		std::process::exit(1);
+	}
+}
+
+/// Author a few blocks with a variety of calls, and import them into a replica.
+fn demo() {
+    println!("Hello, rust!");
+	let key = |name: &str| ed25519::Pair::from_seed(&support::hash(name.as_bytes()));
+	let (jae_key, foo_key, bar_key) = (key("jae"), key("foo"), key("bar"));
+	let jae = jae_key.public().into_account();
+	let foo = foo_key.public().into_account();
+	let bar = bar_key.public().into_account();
+	println!("Accounts: jae = {}, foo = {}, bar = {}", jae, foo, bar);
+
+	let genesis = || {
+		let mut runtime = Runtime::new();
+		runtime.balances.set_balance(&jae, 1000);
+		runtime.balances.set_balance(&foo, 100);
+		runtime.balances.set_balance(&bar, 100);
+		runtime.authorities.set_authorities(vec![jae_key.public()]);
+		runtime.authorities.set_governor(jae.clone());
+		runtime.finalize_genesis();
+		runtime
+	};
+	let mut runtime = genesis();
+	let genesis_hash = runtime.system.genesis_hash();
+	let sign = |pair: &ed25519::Pair, nonce, call| {
+		support::Extrinsic::new_signed(pair, call, nonce, 0, genesis_hash)
+	};
+
+	let block_1 = vec![
+		sign(&jae_key, 0, RuntimeCall::balances(balances::Call::transfer { to: (foo.clone()), amount: (11) })),
+		sign(&jae_key, 1, RuntimeCall::balances(balances::Call::transfer { to: (bar.clone()), amount: (11) })),
+	];
 
-	runtime.execute_block(block_1).expect("invalid block");
-	runtime.execute_block(block_2).expect("invalid block");
+	let block_2 = vec![
+		sign(&jae_key, 2, RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "jaeClaim".to_string() })),
+		sign(&foo_key, 0, RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim { claim: "fooClaim".to_string() })),
+		sign(&jae_key, 3, RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim { claim: "randomClaim".to_string() })),
+	];
+
+	let block_3 = vec![
+		sign(&jae_key, 4, RuntimeCall::proxy(proxy::Call::add_proxy {
+			delegate: bar.clone(),
+			proxy_type: ProxyType::BalancesOnly,
+			delay: 0,
+		})),
+		sign(&bar_key, 0, RuntimeCall::proxy(proxy::Call::proxy {
+			real: jae.clone(),
+			call: Box::new(RuntimeCall::balances(balances::Call::transfer { to: foo.clone(), amount: 20 })),
+		})),
+		sign(&bar_key, 1, RuntimeCall::proxy(proxy::Call::proxy {
+			real: jae.clone(),
+			call: Box::new(RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim { claim: "jaeClaim".to_string() })),
+		})),
+		sign(&jae_key, 5, RuntimeCall::scheduler(scheduler::Call::schedule_named {
+			name: "allowance".to_string(),
+			when: 4,
+			periodic: Some((1, 2)),
+			call: Box::new(RuntimeCall::balances(balances::Call::transfer { to: bar.clone(), amount: 5 })),
+		})),
+		sign(&foo_key, 1, RuntimeCall::assets(assets::Call::create {
+			id: 1,
+			admin: foo.clone(),
+			decimals: 2,
+			min_balance: 1,
+		})),
+		sign(&foo_key, 2, RuntimeCall::assets(assets::Call::mint { id: 1, beneficiary: bar.clone(), amount: 500 })),
+	];
+
+	let block_4 = vec![];
+	let block_5 = vec![];
+
+	// Blocks are authored on top of `runtime`, and then imported by `replica`, the way another node
+	// would import them. We pretend a block is authored every six seconds from now.
+	let mut replica = genesis();
+	let start = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.expect("the clock is after the unix epoch")
+		.as_millis() as types::Moment;
+	for (i, extrinsics) in [block_1, block_2, block_3, block_4, block_5].into_iter().enumerate() {
+		let now = start + i as types::Moment * 6_000;
+		let mut builder =
+			BlockBuilder::new(&mut runtime, now).expect("can build on the current state");
+		builder.push_all(extrinsics);
+		let mut block = builder.build();
+		block.header.seal_with(&jae_key);
+		let header = block.header.clone();
+		let block_number = header.block_number;
+		let report = replica.execute_block(block).expect("invalid block");
+		for (i, receipt) in report.receipts.iter().enumerate() {
+			if let Err(e) = receipt.result {
+				eprintln!(
+					"Extrinsic Error\n\tBlock Number: {}\n\tExtrinsic Number: {}\n\tError: {}",
+					block_number, i, e
+				);
+			}
+		}
+
+		// A light client with only the header can check jae's balance, with a proof from the
+		// replica.
+		let key = storage::storage_key("balances", "balances", &jae);
+		let proof = trie::Trie::new(&replica.storage()).prove(&key);
+		let balance = trie::verify_proof(&header.state_root, &key, &proof).expect("valid proof");
+		let balance = balance.map_or(Ok(0), |balance| types::Balance::decode(&balance));
+		println!(
+			"Block {}: jae's balance is {}, proven with {} trie nodes",
+			block_number,
+			balance.expect("balances decode"),
+			proof.nodes.len()
+		);
+	}
+	assert_eq!(runtime.state_root(), replica.state_root());
 
 	print!("{:#?}", runtime);
 }
+
+#[cfg(test)]
+mod test {
+	use crate::{
+		authorities, balances, block_builder::BlockBuilder, codec, keystore::DevKey,
+		proof_of_existence, proxy, scheduler, support, timestamp, types, ProxyType, Runtime,
+		RuntimeCall, RuntimeEvent,
+	};
+	use codec::{Decode, Encode};
+	use support::Pair;
+
+	fn signed(key: DevKey, nonce: types::Nonce, tip: types::Balance, call: RuntimeCall) -> types::Extrinsic {
+		support::Extrinsic::new_signed(&key.pair(), call, nonce, tip, support::Hash::default())
+	}
+
+	fn transfer(caller: DevKey, nonce: types::Nonce, amount: types::Balance) -> types::Extrinsic {
+		let call = RuntimeCall::balances(balances::Call::transfer { to: DevKey::Bob.account(), amount });
+		signed(caller, nonce, 2, call)
+	}
+
+	fn runtime() -> Runtime {
+		let mut runtime = Runtime::new();
+		runtime.balances.set_balance(&DevKey::Alice.account(), 100);
+		runtime
+	}
+
+	#[test]
+	fn dry_run_leaves_state_untouched() {
+		let runtime = runtime();
+		let state_root = runtime.state_root();
+
+		let dry_run = runtime.dry_run(transfer(DevKey::Alice, 0, 1_000));
+		assert_eq!(dry_run.result, Ok(Err("Not enough funds.")));
+		assert_eq!(dry_run.fee, 12);
+		assert!(dry_run.events.is_empty());
+
+		let dry_run = runtime.dry_run(transfer(DevKey::Alice, 0, 50));
+		assert_eq!(dry_run.result, Ok(Ok(())));
+		assert!(matches!(
+			dry_run.events.as_slice(),
+			[RuntimeEvent::balances(balances::Event::Transfer { amount: 50, .. })]
+		));
+
+		assert_eq!(runtime.dry_run(transfer(DevKey::Charlie, 0, 50)).result, Err("cannot pay the fee"));
+
+		assert_eq!(runtime.state_root(), state_root);
+		assert_eq!(runtime.balances.balance(&DevKey::Alice.account()), 100);
+		assert_eq!(runtime.system.get_nonce(&DevKey::Alice.account()), 0);
+	}
+
+	/// The encoding of `depth` scheduled calls, each nested in the next, around a timestamp call.
+	fn nested_call(depth: usize) -> Vec<u8> {
+		let inner = || RuntimeCall::timestamp(timestamp::Call::set { now: 0 });
+		let call = Box::new(inner());
+		let schedule = scheduler::Call::schedule { when: 1, periodic: None, call };
+		let schedule = RuntimeCall::scheduler(schedule);
+		let (schedule, inner) = (schedule.encode(), inner().encode());
+		[schedule[..schedule.len() - inner.len()].repeat(depth), inner].concat()
+	}
+
+	#[test]
+	fn deeply_nested_calls_fail_to_decode() {
+		assert!(RuntimeCall::decode(&nested_call(10)).is_ok());
+		let too_deep = Some("value is nested too deeply");
+		assert_eq!(RuntimeCall::decode(&nested_call(100)).err(), too_deep);
+		// This would overflow the stack without a limit on the depth.
+		let extrinsic = [vec![0], nested_call(60_000)].concat();
+		assert_eq!(types::Extrinsic::decode(&extrinsic).err(), too_deep);
+	}
+
+	#[test]
+	fn failed_calls_are_rolled_back() {
+		use support::{GetWeight, Hooks};
+		let mut runtime = runtime();
+		runtime.balances.set_balance(&DevKey::Bob.account(), 100);
+		let delegate = DevKey::Bob.account();
+		let add_proxy = proxy::Call::add_proxy { delegate, proxy_type: ProxyType::Any, delay: 1 };
+		let transfer = || {
+			let call = balances::Call::transfer { to: DevKey::Bob.account(), amount: 1_000 };
+			Box::new(RuntimeCall::balances(call))
+		};
+		let announce = proxy::Call::announce { real: DevKey::Alice.account(), call: transfer() };
+		let proxy_announced = |nonce| {
+			let real = DevKey::Alice.account();
+			let call = proxy::Call::proxy_announced { real, call: transfer() };
+			signed(DevKey::Bob, nonce, 0, RuntimeCall::proxy(call))
+		};
+		let add_proxy = signed(DevKey::Alice, 0, 0, RuntimeCall::proxy(add_proxy));
+		assert_eq!(runtime.apply_extrinsic(add_proxy), Ok(Ok(())));
+		let announce = signed(DevKey::Bob, 0, 0, RuntimeCall::proxy(announce));
+		assert_eq!(runtime.apply_extrinsic(announce), Ok(Ok(())));
+		runtime.proxy.on_initialize(1);
+
+		// The announced call is charged for when it is dispatched.
+		assert!(proxy_announced(1).call.weight() > support::DEFAULT_WEIGHT);
+
+		// The announcement is removed before the transfer fails, and must be put back.
+		assert_eq!(runtime.apply_extrinsic(proxy_announced(1)), Ok(Err("Not enough funds.")));
+		runtime.balances.set_balance(&DevKey::Alice.account(), 2_000);
+		assert_eq!(runtime.apply_extrinsic(proxy_announced(2)), Ok(Ok(())));
+		assert_eq!(runtime.balances.balance(&DevKey::Alice.account()), 1_000);
+	}
+
+	#[test]
+	fn initialize_block_returns_the_scheduled_receipts() {
+		let mut runtime = runtime();
+		let schedule = |nonce, amount| {
+			let call = balances::Call::transfer { to: DevKey::Bob.account(), amount };
+			let call = Box::new(RuntimeCall::balances(call));
+			let schedule = scheduler::Call::schedule { when: 1, periodic: None, call };
+			signed(DevKey::Alice, nonce, 0, RuntimeCall::scheduler(schedule))
+		};
+		assert_eq!(runtime.apply_extrinsic(schedule(0, 1_000)), Ok(Ok(())));
+		assert_eq!(runtime.apply_extrinsic(schedule(1, 30)), Ok(Ok(())));
+
+		let header = support::Header {
+			block_number: 1,
+			parent_hash: runtime.system.parent_hash(),
+			extrinsics_root: support::Hash::default(),
+			state_root: support::Hash::default(),
+			seal: None,
+		};
+		let receipts = runtime.initialize_block(&header).unwrap();
+		let results = receipts.iter().map(|receipt| receipt.result).collect::<Vec<_>>();
+		assert_eq!(results, vec![Err("Not enough funds."), Ok(())]);
+		assert_eq!(runtime.balances.balance(&DevKey::Bob.account()), 30);
+
+		assert!(receipts.iter().all(|receipt| receipt.weight == support::DEFAULT_WEIGHT));
+		assert!(receipts[0].events.is_empty());
+		assert!(matches!(
+			receipts[1].events.as_slice(),
+			[RuntimeEvent::balances(balances::Event::Transfer { amount: 30, .. })]
+		));
+	}
+
+	#[test]
+	fn restored_state_imports_the_next_block() {
+		let mut author = runtime();
+		let genesis = author.finalize_genesis();
+		let genesis_hash = genesis.hash();
+		let transfer = |nonce, amount| {
+			let call = balances::Call::transfer { to: DevKey::Bob.account(), amount };
+			let call = RuntimeCall::balances(call);
+			support::Extrinsic::new_signed(&DevKey::Alice.pair(), call, nonce, 0, genesis_hash)
+		};
+		let mut builder = BlockBuilder::new(&mut author, 3_000).unwrap();
+		assert_eq!(builder.push(transfer(0, 30)), Ok(Ok(())));
+		let header = builder.build().header;
+		let storage = Decode::decode(&author.storage().encode()).unwrap();
+
+		let mut restored = Runtime::new();
+		assert_eq!(
+			restored.restore(&storage, genesis_hash, &genesis),
+			Err("storage does not match the state root of the header")
+		);
+		assert_eq!(restored.restore(&storage, genesis_hash, &header), Ok(()));
+		assert_eq!(restored.state_root(), author.state_root());
+		assert_eq!(restored.balances.balance(&DevKey::Bob.account()), 30);
+		assert_eq!(restored.system.get_nonce(&DevKey::Alice.account()), 1);
+
+		// The restored runtime builds on the last block, and accepts extrinsics signed for the
+		// chain.
+		let mut builder = BlockBuilder::new(&mut author, 6_000).unwrap();
+		assert_eq!(builder.push(transfer(1, 20)), Ok(Ok(())));
+		let block = builder.build();
+		let report = restored.execute_block(block).unwrap();
+		assert_eq!(report.receipts[1].result, Ok(()));
+		assert_eq!(restored.state_root(), author.state_root());
+		assert_eq!(restored.balances.balance(&DevKey::Bob.account()), 50);
+	}
+
+	#[test]
+	fn execute_block_reports_each_extrinsic() {
+		let claim = |nonce, call| signed(DevKey::Alice, nonce, 0, RuntimeCall::proof_of_existence(call));
+		let genesis = || {
+			let mut runtime = runtime();
+			runtime.balances.set_balance(&DevKey::Bob.account(), 100);
+			runtime
+		};
+
+		let mut author = genesis();
+		let mut builder = BlockBuilder::new(&mut author, 3_000).unwrap();
+		builder.push_all([
+			claim(0, proof_of_existence::Call::create_claim { claim: "aliceClaim".to_string() }),
+			transfer(DevKey::Alice, 1, 30),
+			claim(2, proof_of_existence::Call::revoke_claim { claim: "randomClaim".to_string() }),
+		]);
+		let block = builder.build();
+
+		let report = genesis().execute_block(block.clone()).unwrap();
+		assert_eq!(report.block_hash, block.header.hash());
+		assert!(report.scheduled.is_empty());
+		let results = report.receipts.iter().map(|receipt| receipt.result).collect::<Vec<_>>();
+		assert_eq!(results, vec![Ok(()), Ok(()), Ok(()), Err("claim does not exist")]);
+
+		let receipt = &report.receipts[2];
+		assert_eq!((receipt.weight, receipt.fee), (support::DEFAULT_WEIGHT, 12));
+		assert!(matches!(
+			receipt.events.as_slice(),
+			[RuntimeEvent::balances(balances::Event::Transfer { amount: 30, .. })]
+		));
+		assert!(report.receipts[0].events.is_empty());
+	}
+
+	#[test]
+	fn blocks_must_set_the_timestamp_once() {
+		let claim = |nonce, claim| {
+			let call = proof_of_existence::Call::create_claim { claim: String::from(claim) };
+			signed(DevKey::Alice, nonce, 0, RuntimeCall::proof_of_existence(call))
+		};
+		let set = |now| RuntimeCall::timestamp(timestamp::Call::set { now });
+		let mut author = runtime();
+		let mut replica = runtime();
+
+		let mut builder = BlockBuilder::new(&mut author, 3_000).unwrap();
+		assert_eq!(builder.push(claim(0, "first")), Ok(Ok(())));
+		assert_eq!(builder.push(support::Extrinsic::new_unsigned(set(4_000))), Err("timestamp must be set only once per block"));
+		let block = builder.build();
+		replica.execute_block(block.clone()).unwrap();
+		assert_eq!(replica.proof_of_existence.claimed_at(&"first".to_string()), Some(3_000));
+
+		// The next block is too soon after the first.
+		let mut next = author.clone();
+		assert_eq!(
+			BlockBuilder::new(&mut next, 5_999).err(),
+			Some("timestamp must increment by at least the minimum period")
+		);
+
+		// A block without the timestamp is invalid, even if its header is otherwise correct.
+		let mut next = author.clone();
+		let mut builder = BlockBuilder::new(&mut next, 6_000).unwrap();
+		assert_eq!(builder.push(claim(1, "second")), Ok(Ok(())));
+		let mut block = builder.build();
+		block.extrinsics.remove(0);
+		block.header.extrinsics_root = support::extrinsics_root(&block.extrinsics);
+		assert_eq!(
+			replica.clone().execute_block(block).unwrap_err(),
+			"timestamp must be set in every block"
+		);
+
+		// Nor is a block which sets the timestamp after another extrinsic.
+		let mut next = author.clone();
+		let mut builder = BlockBuilder::new(&mut next, 6_000).unwrap();
+		assert_eq!(builder.push(claim(1, "second")), Ok(Ok(())));
+		let mut block = builder.build();
+		block.extrinsics.swap(0, 1);
+		block.header.extrinsics_root = support::extrinsics_root(&block.extrinsics);
+		assert_eq!(
+			replica.clone().execute_block(block).unwrap_err(),
+			"timestamp must be the first extrinsic"
+		);
+
+		// Nor can it be set by a signed extrinsic.
+		let mut builder = BlockBuilder::new(&mut author, 6_000).unwrap();
+		assert_eq!(
+			builder.push(signed(DevKey::Alice, 1, 0, set(9_000))),
+			Ok(Err("call can only be dispatched unsigned"))
+		);
+		assert_eq!(builder.push(claim(2, "second")), Ok(Ok(())));
+		replica.execute_block(builder.build()).unwrap();
+		assert_eq!(replica.timestamp.now(), 6_000);
+		assert_eq!(replica.proof_of_existence.claimed_at(&"second".to_string()), Some(6_000));
+	}
+
+	#[test]
+	fn blocks_are_sealed_by_the_slot_author() {
+		let mut author = runtime();
+		author.authorities.set_authorities(vec![DevKey::Alice.public(), DevKey::Bob.public()]);
+		author.authorities.set_governor(DevKey::Alice.account());
+		let mut replica = author.clone();
+		let govern = |nonce, call| signed(DevKey::Alice, nonce, 0, RuntimeCall::authorities(call));
+
+		// Block 1 is in the slot of Bob.
+		let mut builder = BlockBuilder::new(&mut author, 3_000).unwrap();
+		builder.push_all([
+			govern(0, authorities::Call::add_authority { authority: DevKey::Charlie.public() }),
+			govern(1, authorities::Call::remove_authority { authority: DevKey::Bob.public() }),
+		]);
+		let mut block = builder.build();
+		assert_eq!(replica.clone().execute_block(block.clone()).unwrap_err(), "block is not sealed");
+		block.header.seal_with(&DevKey::Alice.pair());
+		assert_eq!(
+			replica.clone().execute_block(block.clone()).unwrap_err(),
+			"block is not sealed by the slot author"
+		);
+		block.header.seal_with(&DevKey::Bob.pair());
+		replica.execute_block(block).unwrap();
+
+		// The changes take effect in block 10, at the start of the next session.
+		for block_number in 2..=11 {
+			let now = block_number as u64 * 3_000;
+			let mut block = BlockBuilder::new(&mut author, now).unwrap().build();
+			let slot_authors = match block_number {
+				..10 => [DevKey::Alice, DevKey::Bob],
+				_ => [DevKey::Alice, DevKey::Charlie],
+			};
+			if block_number == 11 {
+				block.header.seal_with(&DevKey::Bob.pair());
+				assert!(replica.clone().execute_block(block.clone()).is_err());
+			}
+			block.header.seal_with(&slot_authors[block_number % 2].pair());
+			replica.execute_block(block).unwrap();
+		}
+		let expected = [DevKey::Alice.public(), DevKey::Charlie.public()];
+		assert_eq!(replica.authorities.authorities(), expected);
+	}
+
+	#[test]
+	fn extrinsics_must_be_signed_by_the_caller() {
+		let mut runtime = runtime();
+
+		// Bob signs an extrinsic, and claims it is from alice.
+		let mut forged = transfer(DevKey::Bob, 0, 50);
+		forged.signed.as_mut().unwrap().signer = DevKey::Alice.pair().public();
+		assert_eq!(runtime.apply_extrinsic(forged), Err("invalid signature"));
+
+		// The extrinsic is signed for another chain.
+		let call = RuntimeCall::balances(balances::Call::transfer { to: DevKey::Bob.account(), amount: 50 });
+		let replayed = support::Extrinsic::new_signed(&DevKey::Alice.pair(), call.clone(), 0, 0, [1; 32]);
+		assert_eq!(runtime.apply_extrinsic(replayed), Err("invalid signature"));
+
+		// Only inherents can be unsigned.
+		let unsigned = support::Extrinsic::new_unsigned(call);
+		assert_eq!(runtime.validate_transaction(&unsigned), Err("unsigned extrinsics are not transactions"));
+		assert_eq!(runtime.apply_extrinsic(unsigned), Err("call cannot be dispatched unsigned"));
+
+		assert_eq!(runtime.apply_extrinsic(transfer(DevKey::Alice, 0, 50)), Ok(Ok(())));
+		assert_eq!(runtime.balances.balance(&DevKey::Bob.account()), 50);
+	}
+}
@@ -0,0 +1,313 @@
+//! The metadata of a runtime, which describes its pallets so clients can discover which calls
+//! exist and how to build them, without being compiled against the runtime.
+//!
+//! The metadata is generated by the macros: `Runtime::metadata()` by `#[macros::runtime]`, which
+//! collects the calls and errors of each pallet from `#[macros::call]`, and the events of pallets
+//! from `#[macros::event]`.
+//!
+//! Each argument and field also describes how its type is encoded, with a [`TypeDef`] given by the
+//! [`TypeInfo`] trait. This is enough for a client to encode a call from JSON, see `dynamic`.
+
+use crate::json::Value;
+
+/// The metadata of a runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeMetadata {
+	/// The pallets of the runtime, except system, in the order they are declared.
+	pub pallets: Vec<PalletMetadata>,
+}
+
+/// The metadata of a pallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PalletMetadata {
+	/// The name of the pallet, which is the name of its field in the runtime.
+	pub name: String,
+	/// The index of the pallet, which comes first in the encoding of its calls.
+	pub index: u8,
+	/// The callable functions of the pallet, in the order they are declared.
+	pub calls: Vec<CallMetadata>,
+	/// The errors the calls of the pallet can return.
+	pub errors: Vec<String>,
+	/// The events the pallet emits.
+	pub events: Vec<EventMetadata>,
+}
+
+/// The metadata of a callable function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallMetadata {
+	pub name: String,
+	/// The index of the call within its pallet, which comes after the index of the pallet in the
+	/// encoding of the call.
+	pub index: u8,
+	/// The arguments of the call, not including the caller, in the order they are encoded.
+	pub args: Vec<FieldMetadata>,
+	/// The lines of the doc comments of the call.
+	pub docs: Vec<String>,
+	/// Whether the call can only be made in an unsigned extrinsic.
+	pub unsigned: bool,
+}
+
+/// The metadata of an event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventMetadata {
+	pub name: String,
+	pub fields: Vec<FieldMetadata>,
+	/// The lines of the doc comments of the event.
+	pub docs: Vec<String>,
+}
+
+/// The metadata of an argument of a call, or a field of an event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMetadata {
+	pub name: String,
+	/// The type as it is written in the pallet, like `T::Balance`.
+	pub ty: String,
+	/// The concrete type in the runtime, like `u128`, as given by `core::any::type_name`.
+	pub type_name: String,
+	/// How the concrete type is encoded.
+	pub type_def: TypeDef,
+}
+
+/// The shape of a type, which tells how it is encoded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeDef {
+	Bool,
+	/// An unsigned integer of the given number of bytes.
+	Uint(usize),
+	String,
+	/// An account, which is shown as its address.
+	AccountId,
+	/// A fixed number of bytes, like a public key.
+	Bytes(usize),
+	Option(Box<TypeDef>),
+	Tuple(Vec<TypeDef>),
+	Vec(Box<TypeDef>),
+	/// A call of the runtime, which is encoded as described by the metadata of the pallets.
+	Call,
+	/// An enum without fields, which is encoded as the index of its variant. These are the names
+	/// of the variants.
+	Enum(Vec<String>),
+}
+
+/// A trait for types which can describe their encoding in the metadata.
+pub trait TypeInfo {
+	fn type_def() -> TypeDef;
+}
+
+impl RuntimeMetadata {
+	/// Get the metadata of the pallet called `name`.
+	pub fn pallet(&self, name: &str) -> Option<&PalletMetadata> {
+		self.pallets.iter().find(|pallet| pallet.name == name)
+	}
+
+	pub fn to_json(&self) -> Value {
+		let pallets = self.pallets.iter().map(PalletMetadata::to_json).collect::<Vec<_>>();
+		Value::object([("pallets", pallets.into())])
+	}
+}
+
+impl PalletMetadata {
+	/// Get the metadata of the call called `name`.
+	pub fn call(&self, name: &str) -> Option<&CallMetadata> {
+		self.calls.iter().find(|call| call.name == name)
+	}
+
+	pub fn to_json(&self) -> Value {
+		Value::object([
+			("name", self.name.clone().into()),
+			("index", self.index.into()),
+			("calls", self.calls.iter().map(CallMetadata::to_json).collect::<Vec<_>>().into()),
+			("errors", self.errors.clone().into()),
+			("events", self.events.iter().map(EventMetadata::to_json).collect::<Vec<_>>().into()),
+		])
+	}
+}
+
+impl CallMetadata {
+	pub fn to_json(&self) -> Value {
+		Value::object([
+			("name", self.name.clone().into()),
+			("index", self.index.into()),
+			("args", self.args.iter().map(FieldMetadata::to_json).collect::<Vec<_>>().into()),
+			("docs", self.docs.clone().into()),
+			("unsigned", self.unsigned.into()),
+		])
+	}
+}
+
+impl EventMetadata {
+	pub fn to_json(&self) -> Value {
+		Value::object([
+			("name", self.name.clone().into()),
+			("fields", self.fields.iter().map(FieldMetadata::to_json).collect::<Vec<_>>().into()),
+			("docs", self.docs.clone().into()),
+		])
+	}
+}
+
+impl FieldMetadata {
+	pub fn to_json(&self) -> Value {
+		Value::object([
+			("name", self.name.clone().into()),
+			("type", self.ty.clone().into()),
+			("typeName", self.type_name.clone().into()),
+			("typeDef", self.type_def.to_json()),
+		])
+	}
+}
+
+impl TypeDef {
+	/// Simple types are shown as a string like `"u32"`, and the others as an object with a single
+	/// key, like `{"option":"u32"}`.
+	pub fn to_json(&self) -> Value {
+		let tuple = |items: &[TypeDef]| items.iter().map(TypeDef::to_json).collect::<Vec<_>>();
+		match self {
+			TypeDef::Bool => "bool".into(),
+			TypeDef::Uint(bytes) => format!("u{}", bytes * 8).into(),
+			TypeDef::String => "string".into(),
+			TypeDef::AccountId => "accountId".into(),
+			TypeDef::Call => "call".into(),
+			TypeDef::Bytes(len) => Value::object([("bytes", (*len).into())]),
+			TypeDef::Option(inner) => Value::object([("option", inner.to_json())]),
+			TypeDef::Tuple(items) => Value::object([("tuple", tuple(items).into())]),
+			TypeDef::Vec(inner) => Value::object([("vec", inner.to_json())]),
+			TypeDef::Enum(variants) => Value::object([("enum", variants.clone().into())]),
+		}
+	}
+}
+
+macro_rules! impl_type_info_for_int {
+	( $( $t:ty ),* ) => {
+		$(
+			impl TypeInfo for $t {
+				fn type_def() -> TypeDef {
+					TypeDef::Uint(core::mem::size_of::<$t>())
+				}
+			}
+		)*
+	};
+}
+
+impl_type_info_for_int!(u8, u16, u32, u64, u128);
+
+impl TypeInfo for bool {
+	fn type_def() -> TypeDef {
+		TypeDef::Bool
+	}
+}
+
+impl TypeInfo for str {
+	fn type_def() -> TypeDef {
+		TypeDef::String
+	}
+}
+
+impl TypeInfo for String {
+	fn type_def() -> TypeDef {
+		TypeDef::String
+	}
+}
+
+impl<T: TypeInfo + ?Sized> TypeInfo for &T {
+	fn type_def() -> TypeDef {
+		T::type_def()
+	}
+}
+
+impl<T: TypeInfo + ?Sized> TypeInfo for Box<T> {
+	fn type_def() -> TypeDef {
+		T::type_def()
+	}
+}
+
+impl<const N: usize> TypeInfo for [u8; N] {
+	fn type_def() -> TypeDef {
+		TypeDef::Bytes(N)
+	}
+}
+
+impl<T: TypeInfo> TypeInfo for Vec<T> {
+	fn type_def() -> TypeDef {
+		TypeDef::Vec(Box::new(T::type_def()))
+	}
+}
+
+impl<T: TypeInfo> TypeInfo for Option<T> {
+	fn type_def() -> TypeDef {
+		TypeDef::Option(Box::new(T::type_def()))
+	}
+}
+
+impl<A: TypeInfo, B: TypeInfo> TypeInfo for (A, B) {
+	fn type_def() -> TypeDef {
+		TypeDef::Tuple(vec![A::type_def(), B::type_def()])
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::TypeDef;
+	use crate::{codec::Encode, json::Value, proof_of_existence, Runtime, RuntimeCall};
+
+	#[test]
+	fn runtime_metadata() {
+		let metadata = Runtime::metadata();
+		let names = metadata.pallets.iter().map(|pallet| pallet.name.as_str()).collect::<Vec<_>>();
+		assert_eq!(
+			names,
+			[
+				"balances",
+				"proof_of_existence",
+				"proxy",
+				"scheduler",
+				"assets",
+				"timestamp",
+				"authorities"
+			]
+		);
+
+		let balances = metadata.pallet("balances").unwrap();
+		assert_eq!(balances.index, 0);
+		let transfer = balances.call("transfer").unwrap();
+		assert_eq!(transfer.index, 0);
+		let args = transfer.args.iter().map(|arg| (arg.name.as_str(), arg.ty.as_str()));
+		assert_eq!(args.collect::<Vec<_>>(), [("to", "T::AccountId"), ("amount", "T::Balance")]);
+		assert_eq!(transfer.args[1].type_name, "u128");
+		assert_eq!(transfer.args[0].type_def, TypeDef::AccountId);
+		assert_eq!(transfer.args[1].type_def, TypeDef::Uint(16));
+		assert_eq!(transfer.docs[0], "Transfer `amount` from one account to another.");
+		assert!(balances.errors.contains(&"Not enough funds.".to_string()));
+		assert_eq!(balances.events[0].name, "Transfer");
+		assert_eq!(balances.events[0].fields[2].name, "amount");
+
+		// Calls are encoded with the indices given by `#[pallet_index(..)]` and `#[call_index(..)]`.
+		let revoke = metadata.pallet("proof_of_existence").unwrap().call("revoke_claim").unwrap();
+		let call = RuntimeCall::proof_of_existence(proof_of_existence::Call::revoke_claim {
+			claim: "claim".to_string(),
+		});
+		assert_eq!(call.encode()[..2], [1, revoke.index]);
+
+		let timestamp = metadata.pallet("timestamp").unwrap();
+		assert!(timestamp.call("set").unwrap().unsigned);
+		assert!(timestamp.events.is_empty());
+		let proxy = metadata.pallet("proxy").unwrap().call("proxy").unwrap();
+		assert_eq!(proxy.args[1].ty, "Box<T::RuntimeCall>");
+		assert_eq!(proxy.args[1].type_def, TypeDef::Call);
+		let add_proxy = metadata.pallet("proxy").unwrap().call("add_proxy").unwrap();
+		let variants = ["Any", "BalancesOnly", "ProofOfExistenceOnly"].map(String::from);
+		assert_eq!(add_proxy.args[1].type_def, TypeDef::Enum(variants.to_vec()));
+		let schedule = metadata.pallet("scheduler").unwrap().call("schedule").unwrap();
+		let periodic = schedule.args.iter().find(|arg| arg.name == "periodic").unwrap();
+		assert_eq!(periodic.type_def.to_json().to_string(), r#"{"option":{"tuple":["u32","u32"]}}"#);
+
+		// The metadata survives a round trip through JSON text.
+		let json = metadata.to_json();
+		assert_eq!(Value::parse(&json.to_string()), Ok(json.clone()));
+		let pallets = json.get("pallets").unwrap().as_array().unwrap();
+		let call = &pallets[0].get("calls").unwrap().as_array().unwrap()[0];
+		assert_eq!(call.get("name").unwrap().as_str(), Some("transfer"));
+		let amount = &call.get("args").unwrap().as_array().unwrap()[1];
+		assert_eq!(amount.get("typeName").unwrap().as_str(), Some("u128"));
+		assert_eq!(amount.get("typeDef").unwrap().as_str(), Some("u128"));
+	}
+}
@@ -0,0 +1,192 @@
+//! The entrypoint of a node on the development chain, started with `node` as the first argument.
+//!
+//! A node imports blocks into a `client::Client`, keeps a `transaction_pool::TransactionPool`,
+//! syncs with its peers over `p2p` and serves `rpc` requests. Given keys of the authorities, it
+//! also authors a block in each of their slots. The options are:
+//! - `--p2p <address>`, to listen for peers on, `127.0.0.1:30333` by default.
+//! - `--rpc <address>`, to serve JSON-RPC requests on, `127.0.0.1:9933` by default.
+//! - `--peer <address>`, of a node to connect to, which can be given more than once.
+//! - `--author <suri>`, a secret URI like `//Alice`, of a key to author blocks with.
+//! - `--keystore <path>` and `--password <password>`, to author blocks with the keys in a keystore.
+//!
+//! For example, `node --author //Alice` and `node --p2p 127.0.0.1:30334 --rpc 127.0.0.1:9934
+//! --peer 127.0.0.1:30333 --author //Bob` run the two authorities of the chain.
+//!
+//! Keys are added to a keystore with `key generate <path> <password>`, which prints the mnemonic of
+//! a new key, or `key insert <path> <password> <suri>`.
+
+use crate::{
+	client::Confirmations,
+	ed25519,
+	json::to_hex,
+	keystore::{self, DevKey, Keystore},
+	p2p, rpc,
+	support::{IdentifyAccount, Pair},
+	types, Runtime,
+};
+use std::{
+	thread,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The time between two slots, at which authorities author blocks.
+pub const SLOT_DURATION: types::Moment = 6_000;
+
+/// The number of blocks built on top of a block before the node finalizes it.
+const FINALITY_DEPTH: types::BlockNumber = 5;
+
+/// The balance of each development account at genesis.
+const DEV_BALANCE: types::Balance = 1_000_000;
+
+/// The options of a node, parsed from the command line.
+pub struct NodeConfig {
+	pub p2p: String,
+	pub rpc: String,
+	pub peers: Vec<String>,
+	/// The keys to author blocks with. Only keys of the authorities author blocks.
+	pub keys: Vec<ed25519::Pair>,
+}
+
+impl NodeConfig {
+	/// Parse the options of a node, which follow the `node` argument.
+	pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, &'static str> {
+		let mut config = Self {
+			p2p: "127.0.0.1:30333".into(),
+			rpc: "127.0.0.1:9933".into(),
+			peers: Vec::new(),
+			keys: Vec::new(),
+		};
+		let (mut keystore, mut password) = (None, None);
+		while let Some(option) = args.next() {
+			let value = args.next().ok_or("missing value of option")?;
+			match option.as_str() {
+				"--p2p" => config.p2p = value,
+				"--rpc" => config.rpc = value,
+				"--peer" => config.peers.push(value),
+				"--author" => config.keys.push(keystore::pair_from_uri(&value)?),
+				"--keystore" => keystore = Some(value),
+				"--password" => password = Some(value),
+				_ => return Err("unknown option"),
+			}
+		}
+		if let Some(path) = keystore {
+			let keystore = Keystore::open(path, &password.unwrap_or_default())?;
+			let keys = keystore.public_keys().into_iter().filter_map(|key| keystore.pair(&key));
+			config.keys.extend(keys);
+		}
+		Ok(config)
+	}
+}
+
+/// Add a key to a keystore, given the arguments which follow the `key` argument: `generate <path>
+/// <password>` or `insert <path> <password> <suri>`.
+pub fn key_command(mut args: impl Iterator<Item = String>) -> Result<(), &'static str> {
+	let command = args.next().ok_or("missing key command")?;
+	let path = args.next().ok_or("missing keystore path")?;
+	let password = args.next().ok_or("missing keystore password")?;
+	let mut keystore = Keystore::open(path, &password)?;
+	let public = match (command.as_str(), args.next()) {
+		("generate", None) => {
+			let (phrase, public) = keystore.generate()?;
+			println!("Secret phrase: {}", phrase);
+			public
+		},
+		("insert", Some(suri)) => keystore.insert(&suri)?,
+		_ => return Err("unknown key command, expected `generate` or `insert`"),
+	};
+	println!("Account: {}", public.into_account());
+	Ok(())
+}
+
+/// The genesis state of the development chain, on which every node must agree. The development
+/// accounts are funded, and Alice and Bob are the authorities, with Alice as their governor.
+pub fn dev_genesis() -> Runtime {
+	let mut genesis = Runtime::new();
+	let keys = [DevKey::Alice, DevKey::Bob, DevKey::Charlie, DevKey::Dave, DevKey::Eve];
+	for key in keys.into_iter().chain([DevKey::Ferdie]) {
+		genesis.balances.set_balance(&key.account(), DEV_BALANCE);
+	}
+	genesis.authorities.set_authorities(vec![DevKey::Alice.public(), DevKey::Bob.public()]);
+	genesis.authorities.set_governor(DevKey::Alice.account());
+	genesis.finalize_genesis();
+	genesis
+}
+
+/// The current time in milliseconds since the unix epoch.
+fn now() -> types::Moment {
+	let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH);
+	since_epoch.expect("the clock is after the unix epoch").as_millis() as types::Moment
+}
+
+/// Run a node with `config` until the process is stopped. Returns an error if it cannot start.
+pub fn run(config: NodeConfig) -> Result<(), &'static str> {
+	let node =
+		p2p::Node::start(dev_genesis(), &config.p2p).map_err(|_| "cannot listen for peers")?;
+	let server =
+		rpc::Server::start(node.state(), &config.rpc).map_err(|_| "cannot listen for RPC")?;
+	let (p2p, rpc) = (node.local_addr(), server.local_addr());
+	println!("Listening for peers on {}, and for RPC on {}", p2p, rpc);
+	for peer in &config.peers {
+		if let Err(e) = node.connect(peer) {
+			eprintln!("Cannot connect to {}: {}", peer, e);
+		}
+	}
+
+	let finality = Confirmations { depth: FINALITY_DEPTH };
+	let mut slot = now() / SLOT_DURATION + 1;
+	loop {
+		let start = slot * SLOT_DURATION;
+		thread::sleep(Duration::from_millis(start.saturating_sub(now())));
+		slot += 1;
+
+		let (block_number, slot_author) = {
+			let state = node.lock();
+			let block_number = state.client.best_header().block_number + 1;
+			(block_number, state.client.best_state().authorities.slot_author(block_number))
+		};
+		if let Some(key) = config.keys.iter().find(|key| Some(key.public()) == slot_author) {
+			match node.author(key, start) {
+				Ok(hash) => println!("Authored block {} ({})", block_number, to_hex(&hash)),
+				Err(e) => eprintln!("Cannot author block {}: {}", block_number, e),
+			}
+		}
+
+		let peers = node.peer_count();
+		let mut state = node.lock();
+		let _pruned = finality.finalize(&mut state.client);
+		println!(
+			"Best block {}, finalized {} ({}), {} peers, {} extrinsics in the pool",
+			state.client.best_header().block_number,
+			state.client.finalized_header().block_number,
+			to_hex(&state.client.finalized_hash()),
+			peers,
+			state.pool.len(),
+		);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::NodeConfig;
+	use crate::{keystore::DevKey, support::Pair};
+
+	fn parse(args: &[&str]) -> Result<NodeConfig, &'static str> {
+		NodeConfig::parse(args.iter().map(|arg| arg.to_string()))
+	}
+
+	#[test]
+	fn parses_options() {
+		let config = parse(&["--rpc", "127.0.0.1:0", "--peer", "a:1", "--peer", "b:2"]).unwrap();
+		assert_eq!(config.p2p, "127.0.0.1:30333");
+		assert_eq!(config.rpc, "127.0.0.1:0");
+		assert_eq!(config.peers, vec!["a:1", "b:2"]);
+		assert!(config.keys.is_empty());
+
+		let config = parse(&["--author", "//Alice"]).unwrap();
+		let keys = config.keys.iter().map(Pair::public).collect::<Vec<_>>();
+		assert_eq!(keys, vec![DevKey::Alice.public()]);
+
+		assert_eq!(parse(&["--author"]).err(), Some("missing value of option"));
+		assert_eq!(parse(&["--seal", "x"]).err(), Some("unknown option"));
+	}
+}
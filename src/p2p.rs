@@ -0,0 +1,541 @@
+//! Peer-to-peer networking over TCP, so nodes in separate threads or processes can exchange blocks
+//! and extrinsics.
+//!
+//! Messages are encoded with the binary codec, and each is sent as a frame prefixed with its length
+//! as a `u32`. When two nodes connect, they each send a handshake with the genesis hash of their
+//! chain and their best block number. Peers on another chain are disconnected, and peers which are
+//! ahead are asked for the blocks we are missing, by range of block numbers. After that, nodes
+//! announce the blocks they import and gossip the extrinsics they accept into their pool.
+
+use crate::{
+	client::{Client, LongestChain},
+	codec::{Decode, Encode},
+	ed25519,
+	support::Hash,
+	transaction_pool::TransactionPool,
+	types, Runtime,
+};
+use std::{
+	collections::BTreeMap,
+	io::{self, Read, Write},
+	net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::{self, Receiver, Sender},
+		Arc, Mutex, MutexGuard,
+	},
+	thread,
+	time::Duration,
+};
+
+/// The maximum size of a frame, so a peer cannot make us allocate arbitrary amounts of memory.
+const MAX_FRAME_SIZE: u32 = 16 * 1024 * 1024;
+
+/// The maximum number of blocks sent in response to a single request.
+const MAX_BLOCKS_PER_REQUEST: u32 = 128;
+
+/// How long to wait for the handshake of a new peer.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The maximum number of extrinsics in the transaction pool of a node.
+const POOL_LIMIT: usize = 1_000;
+
+/// The messages nodes send each other.
+#[derive(Debug, Clone)]
+pub enum Message {
+	/// The first message on a connection, identifying the chain of the sender and how far it is.
+	Handshake { genesis_hash: Hash, best_number: types::BlockNumber },
+	/// A block the sender has just imported.
+	BlockAnnounce(types::Block),
+	/// A request for `count` blocks of the best chain of the receiver, starting at block `from`.
+	BlockRequest { from: types::BlockNumber, count: u32 },
+	/// The blocks requested by a `BlockRequest`, in order. There are fewer than requested if the
+	/// chain of the sender is not long enough.
+	BlockResponse(Vec<types::Block>),
+	/// An extrinsic to add to the transaction pool.
+	Transaction(types::Extrinsic),
+}
+
+impl Encode for Message {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		match self {
+			Message::Handshake { genesis_hash, best_number } => {
+				0u8.encode_to(dest);
+				genesis_hash.encode_to(dest);
+				best_number.encode_to(dest);
+			},
+			Message::BlockAnnounce(block) => {
+				1u8.encode_to(dest);
+				block.encode_to(dest);
+			},
+			Message::BlockRequest { from, count } => {
+				2u8.encode_to(dest);
+				from.encode_to(dest);
+				count.encode_to(dest);
+			},
+			Message::BlockResponse(blocks) => {
+				3u8.encode_to(dest);
+				blocks.encode_to(dest);
+			},
+			Message::Transaction(extrinsic) => {
+				4u8.encode_to(dest);
+				extrinsic.encode_to(dest);
+			},
+		}
+	}
+}
+
+impl Decode for Message {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		match u8::decode_from(input)? {
+			0 => Ok(Message::Handshake {
+				genesis_hash: Decode::decode_from(input)?,
+				best_number: Decode::decode_from(input)?,
+			}),
+			1 => Ok(Message::BlockAnnounce(Decode::decode_from(input)?)),
+			2 => Ok(Message::BlockRequest {
+				from: Decode::decode_from(input)?,
+				count: Decode::decode_from(input)?,
+			}),
+			3 => Ok(Message::BlockResponse(Decode::decode_from(input)?)),
+			4 => Ok(Message::Transaction(Decode::decode_from(input)?)),
+			_ => Err("invalid message tag"),
+		}
+	}
+}
+
+/// Write `message` to `stream` as a length prefixed frame.
+fn write_message(stream: &mut impl Write, message: &Message) -> io::Result<()> {
+	let bytes = message.encode();
+	stream.write_all(&(bytes.len() as u32).to_le_bytes())?;
+	stream.write_all(&bytes)?;
+	stream.flush()
+}
+
+/// Read the next length prefixed frame from `stream` and decode the message in it.
+fn read_message(stream: &mut impl Read) -> Result<Message, &'static str> {
+	let mut len = [0u8; 4];
+	stream.read_exact(&mut len).map_err(|_| "connection closed")?;
+	let len = u32::from_le_bytes(len);
+	if len > MAX_FRAME_SIZE {
+		return Err("frame is too large");
+	}
+	let mut bytes = vec![0u8; len as usize];
+	stream.read_exact(&mut bytes).map_err(|_| "connection closed")?;
+	Message::decode(&bytes)
+}
+
+/// A connected peer.
+struct Peer {
+	/// The queue of messages to send to the peer, which its writer thread drains, so sending never
+	/// blocks while the state of the node is locked.
+	sender: Sender<Message>,
+	/// The connection, kept to shut it down when the peer is disconnected.
+	stream: TcpStream,
+}
+
+/// The state of a node, shared by the threads serving its peers.
+pub struct NodeState {
+	pub client: Client,
+	pub pool: TransactionPool,
+	/// The connected peers, by an identifier local to this node.
+	peers: BTreeMap<usize, Peer>,
+	/// The identifier of the next peer to connect.
+	next_peer: usize,
+	/// The subscribers to new best blocks, such as RPC clients.
+	head_subscribers: Vec<Sender<types::Header>>,
+}
+
+impl NodeState {
+	/// Send `message` to `peer`. Messages to peers which have disconnected are dropped.
+	fn send(&self, peer: usize, message: Message) {
+		if let Some(peer) = self.peers.get(&peer) {
+			let _res = peer.sender.send(message);
+		}
+	}
+
+	/// Send `message` to all peers, except `except`, which the message came from.
+	fn broadcast(&self, message: Message, except: Option<usize>) {
+		let peers = self.peers.iter().filter(|(id, _)| Some(**id) != except);
+		for (_, peer) in peers {
+			let _res = peer.sender.send(message.clone());
+		}
+	}
+
+	/// Submit an extrinsic to the transaction pool, and gossip it to all peers.
+	pub fn submit(&mut self, extrinsic: types::Extrinsic) -> Result<(), &'static str> {
+		self.pool.submit(self.client.best_state(), extrinsic.clone())?;
+		self.broadcast(Message::Transaction(extrinsic), None);
+		Ok(())
+	}
+
+	/// Subscribe to the headers of new best blocks, from now on. The subscription ends when the
+	/// receiver is dropped.
+	pub fn subscribe_new_heads(&mut self) -> Receiver<types::Header> {
+		let (sender, receiver) = mpsc::channel();
+		self.head_subscribers.push(sender);
+		receiver
+	}
+
+	/// Import a block, update the transaction pool, and notify the subscribers if it is the new
+	/// best block. Returns whether the block was imported.
+	fn import(&mut self, block: types::Block) -> bool {
+		let header = block.header.clone();
+		let Ok(outcome) = self.client.import_block(block) else { return false };
+		self.pool.maintain(&self.client, &outcome);
+		if outcome.is_new_best {
+			self.head_subscribers.retain(|subscriber| subscriber.send(header.clone()).is_ok());
+		}
+		true
+	}
+
+	/// Ask `peer` for the blocks we may be missing from its best chain: all the blocks since the
+	/// last finalized block, since any of them may be on a fork we do not have.
+	fn request_blocks(&self, peer: usize, to: types::BlockNumber) {
+		let from = self.client.finalized_header().block_number + 1;
+		let count = to.saturating_sub(from).saturating_add(1).min(MAX_BLOCKS_PER_REQUEST);
+		self.send(peer, Message::BlockRequest { from, count });
+	}
+
+	/// Handle a message from `peer`.
+	fn handle(&mut self, peer: usize, message: Message) -> Result<(), &'static str> {
+		match message {
+			Message::Handshake { .. } => return Err("unexpected handshake"),
+			Message::BlockAnnounce(block) => {
+				let hash = block.header.hash();
+				if self.client.block(&hash).is_some() {
+					return Ok(());
+				}
+				if self.client.block(&block.header.parent_hash).is_none() {
+					self.request_blocks(peer, block.header.block_number);
+				} else if self.import(block.clone()) {
+					self.broadcast(Message::BlockAnnounce(block), Some(peer));
+				}
+			},
+			Message::BlockRequest { from, count } => {
+				let count = count.min(MAX_BLOCKS_PER_REQUEST);
+				let blocks = (from..from.saturating_add(count))
+					.map_while(|number| self.client.hash_at(number))
+					.filter_map(|hash| self.client.block(&hash).cloned())
+					.collect();
+				self.send(peer, Message::BlockResponse(blocks));
+			},
+			Message::BlockResponse(blocks) => {
+				let full = blocks.len() as u32 == MAX_BLOCKS_PER_REQUEST;
+				let last = blocks.last().map(|block| block.header.block_number);
+				for block in blocks {
+					let hash = block.header.hash();
+					if self.client.block(&hash).is_none() && self.import(block.clone()) {
+						self.broadcast(Message::BlockAnnounce(block), Some(peer));
+					}
+				}
+				// The peer may have more blocks than fit in a single response.
+				if let Some(last) = last.filter(|_| full) {
+					let count = MAX_BLOCKS_PER_REQUEST;
+					self.send(peer, Message::BlockRequest { from: last + 1, count });
+				}
+			},
+			Message::Transaction(extrinsic) => {
+				if self.pool.submit(self.client.best_state(), extrinsic.clone()).is_ok() {
+					self.broadcast(Message::Transaction(extrinsic), Some(peer));
+				}
+			},
+		}
+		Ok(())
+	}
+
+	/// Disconnect `peer`, if it is still connected.
+	fn disconnect(&mut self, peer: usize) {
+		if let Some(peer) = self.peers.remove(&peer) {
+			let _res = peer.stream.shutdown(std::net::Shutdown::Both);
+		}
+	}
+}
+
+/// A node which listens for peers on a TCP socket, and keeps its chain in sync with theirs.
+///
+/// Each peer is served by a reader thread, which handles the messages it sends, and a writer
+/// thread, which sends it the messages queued by the node. Dropping the node disconnects all its
+/// peers and stops listening.
+pub struct Node {
+	state: Arc<Mutex<NodeState>>,
+	local_addr: SocketAddr,
+	/// Set when the node is dropped, to stop accepting peers.
+	stopped: Arc<AtomicBool>,
+}
+
+impl Node {
+	/// Start a node with the `genesis` state, listening for peers on `addr`, such as
+	/// `127.0.0.1:0` for any free port on localhost.
+	pub fn start(genesis: Runtime, addr: impl ToSocketAddrs) -> io::Result<Self> {
+		let listener = TcpListener::bind(addr)?;
+		let local_addr = listener.local_addr()?;
+		let state = Arc::new(Mutex::new(NodeState {
+			client: Client::new(genesis, LongestChain),
+			pool: TransactionPool::new(POOL_LIMIT),
+			peers: BTreeMap::new(),
+			next_peer: 0,
+			head_subscribers: Vec::new(),
+		}));
+		let stopped = Arc::new(AtomicBool::new(false));
+
+		let (accept_state, accept_stopped) = (state.clone(), stopped.clone());
+		thread::spawn(move || {
+			for stream in listener.incoming() {
+				if accept_stopped.load(Ordering::SeqCst) {
+					break;
+				}
+				if let Ok(stream) = stream {
+					let state = accept_state.clone();
+					thread::spawn(move || {
+						let _res = Self::serve(state, stream);
+					});
+				}
+			}
+		});
+
+		Ok(Self { state, local_addr, stopped })
+	}
+
+	/// The address the node listens on.
+	pub fn local_addr(&self) -> SocketAddr {
+		self.local_addr
+	}
+
+	/// Lock the state of the node, to read its chain and pool.
+	pub fn lock(&self) -> MutexGuard<'_, NodeState> {
+		self.state.lock().expect("threads of the node do not panic while holding the lock")
+	}
+
+	/// The state of the node, to share with services like the RPC server.
+	pub fn state(&self) -> Arc<Mutex<NodeState>> {
+		self.state.clone()
+	}
+
+	/// The number of connected peers.
+	pub fn peer_count(&self) -> usize {
+		self.lock().peers.len()
+	}
+
+	/// Connect to the node listening on `addr`. Returns an error if the connection fails, or if
+	/// the peer is on another chain.
+	pub fn connect(&self, addr: impl ToSocketAddrs) -> Result<(), &'static str> {
+		let stream = TcpStream::connect(addr).map_err(|_| "failed to connect to peer")?;
+		let (peer, stream) = Self::handshake(&self.state, stream)?;
+		let state = self.state.clone();
+		thread::spawn(move || Self::read_loop(state, peer, stream));
+		Ok(())
+	}
+
+	/// Submit an extrinsic to the pool of the node, and gossip it to its peers.
+	#[allow(dead_code, reason = "the node submits extrinsics through RPC, into its state")]
+	pub fn submit(&self, extrinsic: types::Extrinsic) -> Result<(), &'static str> {
+		self.lock().submit(extrinsic)
+	}
+
+	/// Author a block at time `now` on top of the best chain with the ready extrinsics of the
+	/// pool, seal it with `key`, and announce it to the peers. Returns the hash of the block.
+	pub fn author(&self, key: &ed25519::Pair, now: types::Moment) -> Result<Hash, &'static str> {
+		let mut state = self.lock();
+		let ready = state.pool.ready(state.client.best_state());
+		let mut block = state.client.build_block(&state.client.best_hash(), now, ready)?;
+		block.header.seal_with(key);
+		let hash = block.header.hash();
+		if !state.import(block.clone()) {
+			return Err("authored block could not be imported");
+		}
+		state.broadcast(Message::BlockAnnounce(block), None);
+		Ok(hash)
+	}
+
+	/// Serve a peer which connected to us.
+	fn serve(state: Arc<Mutex<NodeState>>, stream: TcpStream) -> Result<(), &'static str> {
+		let (peer, stream) = Self::handshake(&state, stream)?;
+		Self::read_loop(state, peer, stream);
+		Ok(())
+	}
+
+	/// Exchange handshakes with a new peer, and register it if it is on our chain. Returns the
+	/// identifier of the peer and the stream to read its messages from.
+	fn handshake(
+		state: &Mutex<NodeState>,
+		mut stream: TcpStream,
+	) -> Result<(usize, TcpStream), &'static str> {
+		let (genesis_hash, best_number) = {
+			let state = state.lock().expect("threads of the node do not panic");
+			(state.client.genesis_hash(), state.client.best_header().block_number)
+		};
+		let handshake = Message::Handshake { genesis_hash, best_number };
+		write_message(&mut stream, &handshake).map_err(|_| "connection closed")?;
+
+		stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|_| "connection closed")?;
+		let Message::Handshake { genesis_hash: theirs, best_number: their_best } =
+			read_message(&mut stream)?
+		else {
+			return Err("expected a handshake");
+		};
+		if theirs != genesis_hash {
+			let _res = stream.shutdown(std::net::Shutdown::Both);
+			return Err("peer is on another chain");
+		}
+		stream.set_read_timeout(None).map_err(|_| "connection closed")?;
+
+		let mut writer = stream.try_clone().map_err(|_| "connection closed")?;
+		let (sender, outgoing) = mpsc::channel::<Message>();
+		thread::spawn(move || {
+			for message in outgoing {
+				if write_message(&mut writer, &message).is_err() {
+					break;
+				}
+			}
+		});
+
+		let mut state = state.lock().expect("threads of the node do not panic");
+		let peer = state.next_peer;
+		state.next_peer += 1;
+		let registered = stream.try_clone().map_err(|_| "connection closed")?;
+		state.peers.insert(peer, Peer { sender, stream: registered });
+		if their_best > state.client.best_header().block_number {
+			state.request_blocks(peer, their_best);
+		}
+		Ok((peer, stream))
+	}
+
+	/// Handle the messages of `peer` until it disconnects or misbehaves.
+	fn read_loop(state: Arc<Mutex<NodeState>>, peer: usize, mut stream: TcpStream) {
+		loop {
+			let result = read_message(&mut stream).and_then(|message| {
+				let mut state = state.lock().expect("threads of the node do not panic");
+				state.handle(peer, message)
+			});
+			if result.is_err() {
+				break;
+			}
+		}
+		state.lock().expect("threads of the node do not panic").disconnect(peer);
+	}
+}
+
+impl Drop for Node {
+	fn drop(&mut self) {
+		self.stopped.store(true, Ordering::SeqCst);
+		// Wake up the listener, so it sees that the node is stopped.
+		let _res = TcpStream::connect(self.local_addr);
+		if let Ok(mut state) = self.state.lock() {
+			let peers = state.peers.keys().copied().collect::<Vec<_>>();
+			peers.into_iter().for_each(|peer| state.disconnect(peer));
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{Message, Node};
+	use crate::{
+		balances, codec::Decode, codec::Encode, keystore::DevKey, support, types, Runtime,
+		RuntimeCall,
+	};
+	use std::{thread, time::Duration};
+
+	fn genesis() -> Runtime {
+		let mut genesis = Runtime::new();
+		genesis.balances.set_balance(&DevKey::Dave.account(), 1_000);
+		genesis
+	}
+
+	fn start() -> Node {
+		Node::start(genesis(), "127.0.0.1:0").unwrap()
+	}
+
+	fn transfer(node: &Node, nonce: types::Nonce) -> types::Extrinsic {
+		let to = DevKey::Eve.account();
+		let call = RuntimeCall::balances(balances::Call::transfer { to, amount: 10 });
+		let genesis_hash = node.lock().client.genesis_hash();
+		support::Extrinsic::new_signed(&DevKey::Dave.pair(), call, nonce, 0, genesis_hash)
+	}
+
+	/// Wait until `condition` holds, failing the test if it does not within a few seconds.
+	fn wait_until(mut condition: impl FnMut() -> bool) {
+		for _ in 0..500 {
+			if condition() {
+				return;
+			}
+			thread::sleep(Duration::from_millis(10));
+		}
+		panic!("condition did not hold in time");
+	}
+
+	fn best_hash(node: &Node) -> support::Hash {
+		node.lock().client.best_hash()
+	}
+
+	#[test]
+	fn messages_round_trip() {
+		let node = start();
+		let ready = vec![transfer(&node, 0)];
+		let block = {
+			let client = &node.lock().client;
+			client.build_block(&client.best_hash(), 3_000, ready).unwrap()
+		};
+		let encoded = Message::BlockResponse(vec![block.clone()]).encode();
+		let Ok(Message::BlockResponse(decoded)) = Message::decode(&encoded) else {
+			panic!("message did not decode");
+		};
+		assert_eq!(decoded[0].header, block.header);
+		assert_eq!(decoded[0].encode(), block.encode());
+		let truncated = &encoded[..encoded.len() - 1];
+		assert_eq!(Message::decode(truncated).err(), Some("unexpected end of input"));
+		assert_eq!(Message::decode(&[9]).err(), Some("invalid message tag"));
+	}
+
+	#[test]
+	fn nodes_sync_blocks_and_gossip_transactions() {
+		let alice = start();
+		let bob = start();
+		let charlie = start();
+
+		// Alice authors a few blocks before anyone connects.
+		for slot in 1..=3 {
+			alice.author(&DevKey::Alice.pair(), slot * 6_000).unwrap();
+		}
+
+		// Bob catches up by requesting the range of blocks he is missing.
+		bob.connect(alice.local_addr()).unwrap();
+		wait_until(|| best_hash(&bob) == best_hash(&alice));
+		assert_eq!(bob.lock().client.best_header().block_number, 3);
+
+		// Charlie is only connected to Bob, so he hears of Alice's blocks through him.
+		charlie.connect(bob.local_addr()).unwrap();
+		wait_until(|| best_hash(&charlie) == best_hash(&alice));
+
+		// A transaction submitted to Charlie reaches Alice, who includes it in her next block.
+		charlie.submit(transfer(&charlie, 0)).unwrap();
+		wait_until(|| alice.lock().pool.len() == 1);
+		let hash = alice.author(&DevKey::Alice.pair(), 24_000).unwrap();
+		wait_until(|| best_hash(&charlie) == hash);
+
+		for node in [&alice, &bob, &charlie] {
+			let state = node.lock();
+			assert_eq!(state.client.best_state().balances.balance(&DevKey::Eve.account()), 10);
+			assert!(state.pool.is_empty());
+		}
+	}
+
+	#[test]
+	fn peers_on_another_chain_are_rejected() {
+		let alice = start();
+		let mut other = genesis();
+		other.balances.set_balance(&DevKey::Eve.account(), 1);
+		let eve = Node::start(other, "127.0.0.1:0").unwrap();
+
+		assert_eq!(eve.connect(alice.local_addr()), Err("peer is on another chain"));
+		assert_eq!(eve.peer_count(), 0);
+		// Alice drops the connection once she sees Eve's handshake.
+		wait_until(|| alice.peer_count() == 0);
+
+		let bob = start();
+		bob.connect(alice.local_addr()).unwrap();
+		wait_until(|| alice.peer_count() == 1);
+		assert_eq!(bob.peer_count(), 1);
+	}
+}
@@ -1,32 +1,50 @@
+use crate::codec::{Decode, Encode};
+use crate::storage::StorageMap;
 use crate::support::DispatchResult;
 use core::fmt::Debug;
-use std::collections::BTreeMap;
+use num::traits::Zero;
 
 pub trait Config: crate::system::Config {
     /// The type which represents the content that can be claimed using this pallet.
     /// Could be the content directly as bytes, or better yet the hash of that content.
     /// We leave that decision to the runtime developer.
-    type Content: Debug + Ord;
+    type Content: Debug + Ord + Encode + Decode;
+    /// The type used to represent time, which claims record when they are made.
+    type Moment: Zero + Copy + Debug + Encode + Decode;
 }
 
 /// This is the Proof of Existence Module.
 /// It is a simple module that allows accounts to claim existence of some data.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[macros::storage]
 pub struct Pallet<T: Config> {
     /// A simple storage map from content to the owner of that content.
     /// Accounts can make multiple different claims, but each claim can only have one owner.
-    claims: BTreeMap<T::Content, T::AccountId>,
+    /// Each claim also records the time it was made.
+    claims: StorageMap<T::Content, (T::AccountId, T::Moment)>,
+    /// The time of the current block, which the runtime sets from the timestamp inherent.
+    now: T::Moment,
 }
 
 impl<T: Config> Pallet<T> {
     /// Create a new instance of the Proof of Existence Module.
     pub fn new() -> Self {
-        Self { claims: BTreeMap::new() }
+        Self { claims: StorageMap::new(), now: T::Moment::zero() }
     }
 
     /// Get the owner (if any) of a claim.
-    pub fn get_claim(&self, claim: &T::Content) -> Option<&T::AccountId> {
-        self.claims.get(claim)
+    pub fn get_claim(&self, claim: &T::Content) -> Option<T::AccountId> {
+        self.claims.get(claim).map(|(owner, _)| owner)
+    }
+
+    /// Get the time (if any) a claim was made.
+    pub fn claimed_at(&self, claim: &T::Content) -> Option<T::Moment> {
+        self.claims.get(claim).map(|(_, time)| time)
+    }
+
+    /// Set the time of the current block, which new claims record.
+    pub fn set_now(&mut self, now: T::Moment) {
+        self.now = now;
     }
 }
 
@@ -34,20 +52,22 @@ impl<T: Config> Pallet<T> {
 impl<T: Config> Pallet<T> {
     /// Create a new claim on behalf of the `caller`.
     /// This function will return an error if someone already has claimed that content.
+    #[call_index(0)]
     pub fn create_claim(&mut self, caller: T::AccountId, claim: T::Content) -> DispatchResult {
         if self.claims.contains_key(&claim) {
             return Err("this content is already claimed");
         }
-        self.claims.insert(claim, caller);
+        self.claims.insert(&claim, &(caller, self.now));
         Ok(())
     }
 
     /// Revoke an existing claim on some content.
     /// This function should only succeed if the caller is the owner of an existing claim.
     /// It will return an error if the claim does not exist, or if the caller is not the owner.
+    #[call_index(1)]
     pub fn revoke_claim(&mut self, caller: T::AccountId, claim: T::Content) -> DispatchResult {
         let owner = self.get_claim(&claim).ok_or("claim does not exist")?;
-        if caller != *owner {
+        if caller != owner {
             return Err("this content belongs someone else");
         }
         self.claims.remove(&claim);
@@ -83,18 +103,22 @@ impl<T: Config> Pallet<T> {
 //	}
 //}
 
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {}
+
 #[cfg(test)]
 mod test {
     struct TestConfig;
 
     impl super::Config for TestConfig {
-        type Content = &'static str;
+        type Content = String;
+        type Moment = u64;
     }
 
     impl crate::system::Config for TestConfig {
-        type AccountId = &'static str;
+        type AccountId = String;
         type BlockNumber = u32;
         type Nonce = u32;
+        type Signature = crate::ed25519::Signature;
     }
 
     #[test]
@@ -106,12 +130,26 @@ mod test {
 				- Check that all functions work successfully.
 				- Check that all error conditions error as expected.
 		*/
+		let (jae, jun) = ("jae".to_string(), "jun".to_string());
+		let (claim, later) = ("claim".to_string(), "later".to_string());
+		let token2 = "token2".to_string();
 		let mut proof_of_existence = super::Pallet::<TestConfig>::new();
-		assert_eq!(proof_of_existence.get_claim(&"jae"), None);
-		assert_eq!(proof_of_existence.create_claim("jae", "claim"), Ok(()));
-		assert_eq!(proof_of_existence.create_claim("jun", "token2"), Ok(()));
-		assert_eq!(proof_of_existence.revoke_claim("jae", "token2"), Err("this content belongs someone else"));
-		assert_eq!(proof_of_existence.revoke_claim("foo", "token3"), Err("claim does not exist"));
+		assert_eq!(proof_of_existence.get_claim(&jae), None);
+		assert_eq!(proof_of_existence.create_claim(jae.clone(), claim.clone()), Ok(()));
+		assert_eq!(proof_of_existence.create_claim(jun, token2.clone()), Ok(()));
+		assert_eq!(
+			proof_of_existence.revoke_claim(jae.clone(), token2),
+			Err("this content belongs someone else")
+		);
+		assert_eq!(
+			proof_of_existence.revoke_claim("charlie".to_string(), "token3".to_string()),
+			Err("claim does not exist")
+		);
+
+		assert_eq!(proof_of_existence.claimed_at(&claim), Some(0));
+		proof_of_existence.set_now(6_000);
+		assert_eq!(proof_of_existence.create_claim(jae, later.clone()), Ok(()));
+		assert_eq!(proof_of_existence.claimed_at(&later), Some(6_000));
 
     }
 }
@@ -0,0 +1,419 @@
+use crate::codec::{Decode, Encode};
+use crate::storage::{StorageMap, StorageValue};
+use crate::support::{hash, DispatchResult, GetWeight, Hash, InstanceFilter, DEFAULT_WEIGHT};
+use core::fmt::Debug;
+use num::traits::{CheckedAdd, Zero};
+
+pub trait Config: crate::system::Config {
+	/// The overarching call type of the runtime, which proxies can dispatch on behalf of an
+	/// account.
+	type RuntimeCall: GetWeight + Debug + Encode + Decode;
+	/// The kinds of proxy an account can register. Each kind filters which calls the proxy is
+	/// allowed to make.
+	type ProxyType: InstanceFilter<Self::RuntimeCall> + Debug + Copy + PartialEq + Encode + Decode;
+}
+
+/// A proxy registered by some account.
+#[derive(Debug, Clone)]
+pub struct ProxyDefinition<T: Config> {
+	/// The account which can dispatch calls on behalf of the registering account.
+	pub delegate: T::AccountId,
+	/// Which calls the `delegate` is allowed to make.
+	pub proxy_type: T::ProxyType,
+	/// The number of blocks a call must be announced for before the `delegate` can dispatch it.
+	/// When zero, the `delegate` can dispatch calls directly.
+	pub delay: T::BlockNumber,
+}
+
+impl<T: Config> Encode for ProxyDefinition<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.delegate.encode_to(dest);
+		self.proxy_type.encode_to(dest);
+		self.delay.encode_to(dest);
+	}
+}
+
+impl<T: Config> Decode for ProxyDefinition<T> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self {
+			delegate: Decode::decode_from(input)?,
+			proxy_type: Decode::decode_from(input)?,
+			delay: Decode::decode_from(input)?,
+		})
+	}
+}
+
+/// A call which a proxy has announced it will dispatch on behalf of `real`.
+#[derive(Debug, Clone)]
+pub struct Announcement<T: Config> {
+	/// The account the call will be dispatched on behalf of.
+	pub real: T::AccountId,
+	/// The hash of the encoding of the announced call. The call itself is given again when it is
+	/// dispatched, so its weight is charged then.
+	pub call_hash: Hash,
+	/// The block number the call was announced at.
+	pub height: T::BlockNumber,
+}
+
+impl<T: Config> Encode for Announcement<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.real.encode_to(dest);
+		self.call_hash.encode_to(dest);
+		self.height.encode_to(dest);
+	}
+}
+
+impl<T: Config> Decode for Announcement<T> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self {
+			real: Decode::decode_from(input)?,
+			call_hash: Decode::decode_from(input)?,
+			height: Decode::decode_from(input)?,
+		})
+	}
+}
+
+/// This is the Proxy Module.
+/// It allows accounts to register other accounts which can dispatch calls on their behalf.
+#[derive(Debug, Clone)]
+#[macros::storage]
+pub struct Pallet<T: Config> {
+	/// The proxies registered by each account.
+	proxies: StorageMap<T::AccountId, Vec<ProxyDefinition<T>>>,
+	/// The calls announced by each delegate, waiting for their delay to pass.
+	announcements: StorageMap<T::AccountId, Vec<Announcement<T>>>,
+	/// The current block number, used to check announcement delays.
+	block_number: StorageValue<T::BlockNumber>,
+	/// A call which has been authorized, and is waiting for the runtime to dispatch it.
+	pending: StorageValue<(T::AccountId, T::RuntimeCall)>,
+}
+
+impl<T: Config> Pallet<T> {
+	/// Create a new instance of the Proxy Module.
+	pub fn new() -> Self {
+		Self {
+			proxies: StorageMap::new(),
+			announcements: StorageMap::new(),
+			block_number: StorageValue::new(),
+			pending: StorageValue::new(),
+		}
+	}
+
+	/// Get the proxies registered by `real`.
+	pub fn proxies(&self, real: &T::AccountId) -> Vec<ProxyDefinition<T>> {
+		self.proxies.get(real).unwrap_or_default()
+	}
+
+	/// Take the call which has been authorized by the last `proxy` or `proxy_announced` call,
+	/// along with the account it should be dispatched on behalf of.
+	pub fn take_pending(&mut self) -> Option<(T::AccountId, T::RuntimeCall)> {
+		self.pending.take()
+	}
+
+	/// The current block number, which announcement delays are checked against.
+	fn block_number(&self) -> T::BlockNumber {
+		self.block_number.get().unwrap_or(T::BlockNumber::zero())
+	}
+
+	/// Find a proxy of `real` which allows `delegate` to make `call`.
+	fn find_proxy(
+		&self,
+		real: &T::AccountId,
+		delegate: &T::AccountId,
+		call: &T::RuntimeCall,
+	) -> Result<ProxyDefinition<T>, &'static str> {
+		let proxies = self
+			.proxies(real)
+			.into_iter()
+			.filter(|proxy| proxy.delegate == *delegate)
+			.collect::<Vec<_>>();
+		if proxies.is_empty() {
+			return Err("not a proxy");
+		}
+		proxies
+			.into_iter()
+			.find(|proxy| proxy.proxy_type.filter(call))
+			.ok_or("call is not allowed for this proxy")
+	}
+}
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+	/// Register `delegate` as a proxy of the `caller`, allowed to make calls permitted by
+	/// `proxy_type`. If `delay` is not zero, the `delegate` must announce calls that many blocks
+	/// before dispatching them.
+	#[call_index(0)]
+	pub fn add_proxy(
+		&mut self,
+		caller: T::AccountId,
+		delegate: T::AccountId,
+		proxy_type: T::ProxyType,
+		delay: T::BlockNumber,
+	) -> DispatchResult {
+		if caller == delegate {
+			return Err("an account cannot be its own proxy");
+		}
+		let mut proxies = self.proxies(&caller);
+		if proxies.iter().any(|proxy| proxy.delegate == delegate && proxy.proxy_type == proxy_type) {
+			return Err("proxy already exists");
+		}
+		proxies.push(ProxyDefinition { delegate, proxy_type, delay });
+		self.proxies.insert(&caller, &proxies);
+		Ok(())
+	}
+
+	/// Unregister `delegate` as a proxy of the `caller` with the given `proxy_type`.
+	#[call_index(1)]
+	pub fn remove_proxy(
+		&mut self,
+		caller: T::AccountId,
+		delegate: T::AccountId,
+		proxy_type: T::ProxyType,
+	) -> DispatchResult {
+		let mut proxies = self.proxies(&caller);
+		let index = proxies
+			.iter()
+			.position(|proxy| proxy.delegate == delegate && proxy.proxy_type == proxy_type)
+			.ok_or("proxy does not exist")?;
+		proxies.remove(index);
+		if proxies.is_empty() {
+			self.proxies.remove(&caller);
+		} else {
+			self.proxies.insert(&caller, &proxies);
+		}
+		Ok(())
+	}
+
+	/// Dispatch `call` on behalf of `real`, where the `caller` is a proxy of `real` without a
+	/// delay. The call is handed back to the runtime to be dispatched, see `take_pending`.
+	#[call_index(2)]
+	#[weight(DEFAULT_WEIGHT + call.weight())]
+	pub fn proxy(
+		&mut self,
+		caller: T::AccountId,
+		real: T::AccountId,
+		call: Box<T::RuntimeCall>,
+	) -> DispatchResult {
+		let proxy = self.find_proxy(&real, &caller, &call)?;
+		if !proxy.delay.is_zero() {
+			return Err("this proxy must announce calls first");
+		}
+		self.pending.put(&(real, *call));
+		Ok(())
+	}
+
+	/// Announce that the `caller` will dispatch `call` on behalf of `real` once the delay of their
+	/// proxy has passed.
+	#[call_index(3)]
+	pub fn announce(
+		&mut self,
+		caller: T::AccountId,
+		real: T::AccountId,
+		call: Box<T::RuntimeCall>,
+	) -> DispatchResult {
+		self.find_proxy(&real, &caller, &call)?;
+		let height = self.block_number();
+		let mut announcements = self.announcements.get(&caller).unwrap_or_default();
+		announcements.push(Announcement { real, call_hash: hash(&call.encode()), height });
+		self.announcements.insert(&caller, &announcements);
+		Ok(())
+	}
+
+	/// Remove all the calls announced by `delegate` on behalf of the `caller`.
+	#[call_index(4)]
+	pub fn reject_announcement(&mut self, caller: T::AccountId, delegate: T::AccountId) -> DispatchResult {
+		let mut announcements =
+			self.announcements.get(&delegate).ok_or("announcement does not exist")?;
+		let before = announcements.len();
+		announcements.retain(|announcement| announcement.real != caller);
+		if announcements.len() == before {
+			return Err("announcement does not exist");
+		}
+		self.announcements.insert(&delegate, &announcements);
+		Ok(())
+	}
+
+	/// Dispatch `call`, which the `caller` announced on behalf of `real`, once the delay of their
+	/// proxy has passed. If the same call was announced more than once, the oldest announcement is
+	/// used. The call is handed back to the runtime to be dispatched, see `take_pending`.
+	#[call_index(5)]
+	#[weight(DEFAULT_WEIGHT + call.weight())]
+	pub fn proxy_announced(
+		&mut self,
+		caller: T::AccountId,
+		real: T::AccountId,
+		call: Box<T::RuntimeCall>,
+	) -> DispatchResult {
+		let mut announcements =
+			self.announcements.get(&caller).ok_or("announcement does not exist")?;
+		let call_hash = hash(&call.encode());
+		let index = announcements
+			.iter()
+			.position(|announced| announced.real == real && announced.call_hash == call_hash)
+			.ok_or("announcement does not exist")?;
+
+		// The proxy may have been removed or changed since the call was announced.
+		let proxy = self.find_proxy(&real, &caller, &call)?;
+		let ready_at = announcements[index].height.checked_add(&proxy.delay).ok_or("Overflow")?;
+		if ready_at > self.block_number() {
+			return Err("announcement delay has not passed");
+		}
+
+		announcements.remove(index);
+		self.announcements.insert(&caller, &announcements);
+		self.pending.put(&(real, *call));
+		Ok(())
+	}
+}
+
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {
+	/// Keep track of the current block number, which announcement delays are checked against.
+	fn on_initialize(&mut self, block_number: T::BlockNumber) {
+		self.block_number.put(&block_number);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::codec::{Decode, Encode};
+	use crate::support::{Hooks, InstanceFilter};
+
+	#[derive(Debug)]
+	struct TestConfig;
+
+	/// A stand-in for the runtime call type: just the name of the pallet being called.
+	#[derive(Debug, PartialEq)]
+	struct TestCall(String);
+
+	impl Encode for TestCall {
+		fn encode_to(&self, dest: &mut Vec<u8>) {
+			self.0.encode_to(dest);
+		}
+	}
+
+	impl Decode for TestCall {
+		fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+			Ok(TestCall(Decode::decode_from(input)?))
+		}
+	}
+
+	impl crate::support::GetWeight for TestCall {
+		fn weight(&self) -> crate::support::Weight {
+			crate::support::DEFAULT_WEIGHT
+		}
+	}
+
+	#[derive(Debug, Clone, Copy, PartialEq)]
+	enum TestProxyType {
+		Any,
+		BalancesOnly,
+	}
+
+	impl Encode for TestProxyType {
+		fn encode_to(&self, dest: &mut Vec<u8>) {
+			(*self as u8).encode_to(dest);
+		}
+	}
+
+	impl Decode for TestProxyType {
+		fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+			match u8::decode_from(input)? {
+				0 => Ok(TestProxyType::Any),
+				1 => Ok(TestProxyType::BalancesOnly),
+				_ => Err("invalid proxy type"),
+			}
+		}
+	}
+
+	impl InstanceFilter<TestCall> for TestProxyType {
+		fn filter(&self, call: &TestCall) -> bool {
+			match self {
+				TestProxyType::Any => true,
+				TestProxyType::BalancesOnly => call.0 == "balances",
+			}
+		}
+	}
+
+	impl crate::system::Config for TestConfig {
+		type AccountId = String;
+		type BlockNumber = u32;
+		type Nonce = u32;
+		type Signature = crate::ed25519::Signature;
+	}
+
+	impl super::Config for TestConfig {
+		type RuntimeCall = TestCall;
+		type ProxyType = TestProxyType;
+	}
+
+	/// A call to `pallet`.
+	fn call(pallet: &str) -> Box<TestCall> {
+		Box::new(TestCall(pallet.to_string()))
+	}
+
+	#[test]
+	fn proxy_filters_calls() {
+		let (alice, bob) = ("alice".to_string(), "bob".to_string());
+		let mut proxy = super::Pallet::<TestConfig>::new();
+		assert_eq!(proxy.proxy(bob.clone(), alice.clone(), call("balances")), Err("not a proxy"));
+
+		let balances_only = TestProxyType::BalancesOnly;
+		assert_eq!(proxy.add_proxy(alice.clone(), bob.clone(), balances_only, 0), Ok(()));
+		assert_eq!(
+			proxy.add_proxy(alice.clone(), bob.clone(), balances_only, 0),
+			Err("proxy already exists")
+		);
+
+		assert_eq!(
+			proxy.proxy(bob.clone(), alice.clone(), call("proof_of_existence")),
+			Err("call is not allowed for this proxy")
+		);
+		assert_eq!(proxy.take_pending(), None);
+
+		assert_eq!(proxy.proxy(bob.clone(), alice.clone(), call("balances")), Ok(()));
+		assert_eq!(proxy.take_pending(), Some((alice.clone(), *call("balances"))));
+
+		assert_eq!(proxy.remove_proxy(alice.clone(), bob.clone(), balances_only), Ok(()));
+		assert_eq!(proxy.proxy(bob, alice, call("balances")), Err("not a proxy"));
+	}
+
+	#[test]
+	fn announced_proxy_waits_for_delay() {
+		let (alice, bob) = ("alice".to_string(), "bob".to_string());
+		let mut proxy = super::Pallet::<TestConfig>::new();
+		assert_eq!(proxy.add_proxy(alice.clone(), bob.clone(), TestProxyType::Any, 2), Ok(()));
+		assert_eq!(
+			proxy.proxy(bob.clone(), alice.clone(), call("balances")),
+			Err("this proxy must announce calls first")
+		);
+
+		proxy.on_initialize(1);
+		assert_eq!(proxy.announce(bob.clone(), alice.clone(), call("balances")), Ok(()));
+
+		proxy.on_initialize(2);
+		assert_eq!(
+			proxy.proxy_announced(bob.clone(), alice.clone(), call("balances")),
+			Err("announcement delay has not passed")
+		);
+
+		proxy.on_initialize(3);
+		assert_eq!(
+			proxy.proxy_announced(bob.clone(), alice.clone(), call("proof_of_existence")),
+			Err("announcement does not exist")
+		);
+		assert_eq!(proxy.proxy_announced(bob.clone(), alice.clone(), call("balances")), Ok(()));
+		assert_eq!(proxy.take_pending(), Some((alice.clone(), *call("balances"))));
+		assert_eq!(
+			proxy.proxy_announced(bob.clone(), alice.clone(), call("balances")),
+			Err("announcement does not exist")
+		);
+
+		assert_eq!(proxy.announce(bob.clone(), alice.clone(), call("balances")), Ok(()));
+		assert_eq!(proxy.reject_announcement(alice.clone(), bob.clone()), Ok(()));
+		proxy.on_initialize(5);
+		assert_eq!(
+			proxy.proxy_announced(bob, alice, call("balances")),
+			Err("announcement does not exist")
+		);
+	}
+}
@@ -0,0 +1,606 @@
+//! A JSON-RPC 2.0 server over HTTP, so external tools can query the chain and submit extrinsics.
+//!
+//! Requests are sent with `POST`, one per connection, either alone or in a batch. Hashes and
+//! encoded extrinsics are hex strings, and accounts are addresses. The server is backed by the
+//! state of a `p2p::Node`, so submitted extrinsics are gossiped to its peers.
+//!
+//! The methods are:
+//! - `chain_getHeader([hash])` and `chain_getBlock([hash])`, of the best block by default.
+//! - `state_getBalance(address, [hash])` and `state_getClaim(content, [hash])`, in the state after
+//!   the best block by default.
+//! - `state_getReadProof(key, [hash])`, the nodes of the state trie proving the value of a storage
+//!   key, which can be checked against the state root of the header with `trie::verify_proof`.
+//! - `state_getMetadata()`, the metadata of the runtime, see `metadata::RuntimeMetadata`.
+//! - `system_accountNonce(address)`, the next nonce of the account, after those in the pool.
+//! - `author_submitExtrinsic(extrinsic)`, which returns the hash of the extrinsic.
+//! - `chain_subscribeNewHeads()`, which keeps the connection open and streams a `chain_newHead`
+//!   notification for each new best block, one per line, after the response with the id of the
+//!   subscription.
+
+use crate::{
+	codec::{Decode, Encode},
+	json::{from_hex, to_hex, Value},
+	p2p::NodeState,
+	support::{self, Hash},
+	trie::Trie,
+	types, Runtime,
+};
+use std::{
+	io::{self, BufRead, BufReader, Write},
+	net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+	sync::{
+		atomic::{AtomicBool, AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+	thread,
+};
+
+/// The maximum size of the body of a request.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// The error codes defined by JSON-RPC 2.0, and those of this server.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The call was valid, but failed, for example because the state of the block is pruned.
+const CALL_ERROR: i64 = -32000;
+/// The submitted extrinsic was rejected by the transaction pool.
+const INVALID_TRANSACTION: i64 = 1010;
+
+/// An error returned to the client in place of a result.
+#[derive(Debug, Clone, PartialEq)]
+struct Error {
+	code: i64,
+	message: &'static str,
+}
+
+impl Error {
+	fn new(code: i64, message: &'static str) -> Self {
+		Self { code, message }
+	}
+}
+
+type RpcResult = Result<Value, Error>;
+
+/// A JSON-RPC server listening on a TCP socket. Each connection is served by its own thread.
+/// Dropping the server stops listening, but lets open subscriptions run until they are closed.
+pub struct Server {
+	local_addr: SocketAddr,
+	/// Set when the server is dropped, to stop accepting connections.
+	stopped: Arc<AtomicBool>,
+}
+
+impl Server {
+	/// Start a server for the node with `state`, listening on `addr`, such as `127.0.0.1:9933`.
+	pub fn start(state: Arc<Mutex<NodeState>>, addr: impl ToSocketAddrs) -> io::Result<Self> {
+		let listener = TcpListener::bind(addr)?;
+		let local_addr = listener.local_addr()?;
+		let stopped = Arc::new(AtomicBool::new(false));
+		let next_subscription = Arc::new(AtomicU64::new(0));
+
+		let accept_stopped = stopped.clone();
+		thread::spawn(move || {
+			for stream in listener.incoming() {
+				if accept_stopped.load(Ordering::SeqCst) {
+					break;
+				}
+				if let Ok(stream) = stream {
+					let (state, next_subscription) = (state.clone(), next_subscription.clone());
+					thread::spawn(move || {
+						let _res = serve(&state, &next_subscription, stream);
+					});
+				}
+			}
+		});
+
+		Ok(Self { local_addr, stopped })
+	}
+
+	/// The address the server listens on.
+	pub fn local_addr(&self) -> SocketAddr {
+		self.local_addr
+	}
+}
+
+impl Drop for Server {
+	fn drop(&mut self) {
+		self.stopped.store(true, Ordering::SeqCst);
+		// Wake up the listener, so it sees that the server is stopped.
+		let _res = TcpStream::connect(self.local_addr);
+	}
+}
+
+/// Serve a single HTTP request.
+fn serve(
+	state: &Mutex<NodeState>,
+	next_subscription: &AtomicU64,
+	mut stream: TcpStream,
+) -> io::Result<()> {
+	let mut reader = BufReader::new(stream.try_clone()?);
+	let body = match read_request(&mut reader) {
+		Ok(body) => body,
+		Err(status) => return write_response(&mut stream, status, ""),
+	};
+	let request = match std::str::from_utf8(&body).map_err(|_| "invalid utf-8") {
+		Ok(text) => Value::parse(text),
+		Err(err) => Err(err),
+	};
+	let request = match request {
+		Ok(request) => request,
+		Err(message) => {
+			let response = response(Value::Null, Err(Error::new(PARSE_ERROR, message)));
+			return write_response(&mut stream, "200 OK", &response.to_string());
+		},
+	};
+
+	if request.get("method").and_then(Value::as_str) == Some("chain_subscribeNewHeads") {
+		return subscribe_new_heads(state, next_subscription, stream, request);
+	}
+	let response = match request {
+		Value::Array(requests) if !requests.is_empty() => {
+			let responses = requests.iter().filter_map(|request| handle(state, request));
+			Some(Value::Array(responses.collect()))
+				.filter(|responses| responses.as_array().is_some_and(|r| !r.is_empty()))
+		},
+		request => handle(state, &request),
+	};
+	match response {
+		Some(response) => write_response(&mut stream, "200 OK", &response.to_string()),
+		// Notifications get no response.
+		None => write_response(&mut stream, "204 No Content", ""),
+	}
+}
+
+/// Read an HTTP request, returning its body, or the status to respond with if it is not a valid
+/// JSON-RPC request.
+fn read_request(reader: &mut impl BufRead) -> Result<Vec<u8>, &'static str> {
+	let mut line = String::new();
+	reader.read_line(&mut line).map_err(|_| "400 Bad Request")?;
+	if !line.starts_with("POST ") {
+		return Err("405 Method Not Allowed");
+	}
+	let mut content_length = None;
+	loop {
+		line.clear();
+		reader.read_line(&mut line).map_err(|_| "400 Bad Request")?;
+		let line = line.trim_end();
+		if line.is_empty() {
+			break;
+		}
+		if let Some((name, value)) = line.split_once(':') {
+			if name.trim().eq_ignore_ascii_case("content-length") {
+				content_length = Some(value.trim().parse().map_err(|_| "400 Bad Request")?);
+			}
+		}
+	}
+	let len: usize = content_length.ok_or("411 Length Required")?;
+	if len > MAX_BODY_SIZE {
+		return Err("413 Payload Too Large");
+	}
+	let mut body = vec![0u8; len];
+	reader.read_exact(&mut body).map_err(|_| "400 Bad Request")?;
+	Ok(body)
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+	write!(
+		stream,
+		"HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+		 Connection: close\r\n\r\n{}",
+		status,
+		body.len(),
+		body
+	)?;
+	stream.flush()
+}
+
+/// The response to the request with `id`.
+fn response(id: Value, result: RpcResult) -> Value {
+	let outcome = match result {
+		Ok(result) => ("result", result),
+		Err(Error { code, message }) => {
+			("error", Value::object([("code", code.into()), ("message", message.into())]))
+		},
+	};
+	Value::object([("jsonrpc", "2.0".into()), ("id", id), outcome])
+}
+
+/// Handle a single request, returning the response, or `None` if the request is a notification.
+fn handle(state: &Mutex<NodeState>, request: &Value) -> Option<Value> {
+	let id = request.get("id").cloned();
+	let (method, params) = match parse_request(request) {
+		Ok(parsed) => parsed,
+		// Invalid requests get a response even without an id, since we cannot tell whether they
+		// were meant as notifications.
+		Err(err) => return Some(response(id.unwrap_or(Value::Null), Err(err))),
+	};
+	let result = call(&mut state.lock().expect("threads of the node do not panic"), method, params);
+	id.map(|id| response(id, result))
+}
+
+/// Check the structure of a request, returning its method and parameters.
+fn parse_request(request: &Value) -> Result<(&str, &[Value]), Error> {
+	let invalid = || Error::new(INVALID_REQUEST, "invalid request");
+	if request.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+		return Err(invalid());
+	}
+	let method = request.get("method").and_then(Value::as_str).ok_or_else(invalid)?;
+	let params = match request.get("params") {
+		None => &[][..],
+		Some(params) => params
+			.as_array()
+			.ok_or(Error::new(INVALID_PARAMS, "params must be an array"))?,
+	};
+	Ok((method, params))
+}
+
+/// Call `method` with `params` on the node.
+fn call(state: &mut NodeState, method: &str, params: &[Value]) -> RpcResult {
+	match method {
+		"chain_getHeader" => {
+			let block = block_param(state, params.first())?;
+			Ok(block.map(|block| header_json(&block.header)).unwrap_or(Value::Null))
+		},
+		"chain_getBlock" => {
+			let block = block_param(state, params.first())?;
+			Ok(block.map(block_json).unwrap_or(Value::Null))
+		},
+		"state_getBalance" => {
+			let who = account_param(params.first())?;
+			with_state(state, params.get(1), |runtime| runtime.balances.balance(&who).into())
+		},
+		"state_getClaim" => {
+			let content = params.first().and_then(Value::as_str);
+			let content = content.ok_or(Error::new(INVALID_PARAMS, "content must be a string"))?;
+			let content = types::Content::from(content);
+			with_state(state, params.get(1), |runtime| {
+				let poe = &runtime.proof_of_existence;
+				let owner = poe.get_claim(&content).map(|owner| owner.to_string().into());
+				let claimed_at = poe.claimed_at(&content).map(Value::from);
+				match (owner, claimed_at) {
+					(Some(owner), Some(at)) => {
+						Value::object([("owner", owner), ("claimedAt", at)])
+					},
+					_ => Value::Null,
+				}
+			})
+		},
+		"state_getReadProof" => {
+			let hex = params.first().and_then(Value::as_str);
+			let hex = hex.ok_or(Error::new(INVALID_PARAMS, "key must be a hex string"))?;
+			let key = from_hex(hex).map_err(|message| Error::new(INVALID_PARAMS, message))?;
+			with_state(state, params.get(1), |runtime| {
+				let proof = Trie::new(&runtime.storage()).prove(&key);
+				proof.nodes.iter().map(|node| Value::from(to_hex(node))).collect::<Vec<_>>().into()
+			})
+		},
+		"state_getMetadata" => Ok(Runtime::metadata().to_json()),
+		"system_accountNonce" => {
+			let who = account_param(params.first())?;
+			Ok(state.pool.next_nonce(state.client.best_state(), &who).into())
+		},
+		"author_submitExtrinsic" => {
+			let hex = params.first().and_then(Value::as_str);
+			let hex = hex.ok_or(Error::new(INVALID_PARAMS, "extrinsic must be a hex string"))?;
+			let bytes = from_hex(hex).map_err(|message| Error::new(INVALID_PARAMS, message))?;
+			let extrinsic = types::Extrinsic::decode(&bytes)
+				.map_err(|message| Error::new(INVALID_PARAMS, message))?;
+			state.submit(extrinsic).map_err(|message| Error::new(INVALID_TRANSACTION, message))?;
+			Ok(to_hex(&support::hash(&bytes)).into())
+		},
+		"chain_subscribeNewHeads" => {
+			Err(Error::new(INVALID_REQUEST, "subscriptions cannot be made in a batch"))
+		},
+		_ => Err(Error::new(METHOD_NOT_FOUND, "method not found")),
+	}
+}
+
+/// The block with the hash in `param`, or the best block if it is missing or null.
+fn block_param<'a>(
+	state: &'a NodeState,
+	param: Option<&Value>,
+) -> Result<Option<&'a types::Block>, Error> {
+	let hash = match param.filter(|param| !param.is_null()) {
+		Some(param) => hash_param(param)?,
+		None => state.client.best_hash(),
+	};
+	Ok(state.client.block(&hash))
+}
+
+fn hash_param(param: &Value) -> Result<Hash, Error> {
+	let bytes = param.as_str().map(from_hex);
+	let hash = bytes.and_then(|bytes| bytes.ok()?.try_into().ok());
+	hash.ok_or(Error::new(INVALID_PARAMS, "hash must be 32 bytes in hex"))
+}
+
+fn account_param(param: Option<&Value>) -> Result<types::AccountId, Error> {
+	let address = param.and_then(Value::as_str);
+	let address = address.ok_or(Error::new(INVALID_PARAMS, "account must be an address"))?;
+	address.parse().map_err(|message| Error::new(INVALID_PARAMS, message))
+}
+
+/// Query the state after the block with the hash in `at`, or after the best block if it is
+/// missing or null.
+fn with_state(
+	state: &NodeState,
+	at: Option<&Value>,
+	query: impl FnOnce(&Runtime) -> Value,
+) -> RpcResult {
+	match at.filter(|at| !at.is_null()) {
+		None => Ok(query(state.client.best_state())),
+		Some(at) => {
+			let runtime = state.client.state_at(&hash_param(at)?);
+			let runtime = runtime.map_err(|message| Error::new(CALL_ERROR, message))?;
+			Ok(query(&runtime))
+		},
+	}
+}
+
+fn header_json(header: &types::Header) -> Value {
+	Value::object([
+		("hash", to_hex(&header.hash()).into()),
+		("number", header.block_number.into()),
+		("parentHash", to_hex(&header.parent_hash).into()),
+		("extrinsicsRoot", to_hex(&header.extrinsics_root).into()),
+		("stateRoot", to_hex(&header.state_root).into()),
+		("seal", header.seal.as_ref().map(|seal| to_hex(&seal.encode())).into()),
+	])
+}
+
+/// Blocks are returned with their extrinsics encoded, since clients decode them with the codec.
+fn block_json(block: &types::Block) -> Value {
+	let extrinsics = block.extrinsics.iter().map(|extrinsic| to_hex(&extrinsic.encode()));
+	Value::object([
+		("header", header_json(&block.header)),
+		("extrinsics", extrinsics.collect::<Vec<_>>().into()),
+	])
+}
+
+/// Respond to a `chain_subscribeNewHeads` request with the id of the subscription, and then send
+/// a notification with each new best header until the client disconnects.
+fn subscribe_new_heads(
+	state: &Mutex<NodeState>,
+	next_subscription: &AtomicU64,
+	mut stream: TcpStream,
+	request: Value,
+) -> io::Result<()> {
+	let Some(id) = request.get("id").cloned() else { return Ok(()) };
+	if let Err(err) = parse_request(&request) {
+		return write_response(&mut stream, "200 OK", &response(id, Err(err)).to_string());
+	}
+	let heads = state.lock().expect("threads of the node do not panic").subscribe_new_heads();
+	let subscription = next_subscription.fetch_add(1, Ordering::SeqCst);
+
+	// The length of the body is unknown, so it ends when the connection is closed.
+	write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n")?;
+	write!(stream, "Connection: close\r\n\r\n")?;
+	writeln!(stream, "{}", response(id, Ok(subscription.into())))?;
+	for header in heads {
+		let result = header_json(&header);
+		let params = Value::object([("subscription", subscription.into()), ("result", result)]);
+		let notification = Value::object([
+			("jsonrpc", "2.0".into()),
+			("method", "chain_newHead".into()),
+			("params", params),
+		]);
+		writeln!(stream, "{}", notification)?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use super::Server;
+	use crate::{
+		balances,
+		codec::{Decode, Encode},
+		json::{from_hex, to_hex, Value},
+		keystore::DevKey,
+		p2p::Node,
+		proof_of_existence, scheduler, storage,
+		support::{self, Hash},
+		timestamp, trie, types, Runtime, RuntimeCall,
+	};
+	use std::{
+		io::{BufRead, BufReader, Read, Write},
+		net::{SocketAddr, TcpStream},
+	};
+
+	fn start() -> (Node, Server) {
+		let mut genesis = Runtime::new();
+		genesis.balances.set_balance(&DevKey::Dave.account(), 1_000);
+		let node = Node::start(genesis, "127.0.0.1:0").unwrap();
+		let server = Server::start(node.state(), "127.0.0.1:0").unwrap();
+		(node, server)
+	}
+
+	fn signed(node: &Node, nonce: types::Nonce, call: RuntimeCall) -> types::Extrinsic {
+		let genesis_hash = node.lock().client.genesis_hash();
+		support::Extrinsic::new_signed(&DevKey::Dave.pair(), call, nonce, 0, genesis_hash)
+	}
+
+	fn http_request(body: &str) -> String {
+		format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+	}
+
+	/// Send an HTTP request with `body`, returning the status line and the body of the response.
+	fn post(addr: SocketAddr, body: &str) -> (String, String) {
+		let mut stream = TcpStream::connect(addr).unwrap();
+		stream.write_all(http_request(body).as_bytes()).unwrap();
+		let mut response = String::new();
+		stream.read_to_string(&mut response).unwrap();
+		let (head, body) = response.split_once("\r\n\r\n").unwrap();
+		(head.lines().next().unwrap().to_string(), body.to_string())
+	}
+
+	/// Call `method`, returning its result, or the code and message of its error.
+	fn call(addr: SocketAddr, method: &str, params: Vec<Value>) -> Result<Value, (i128, String)> {
+		let request = Value::object([
+			("jsonrpc", "2.0".into()),
+			("id", 7.into()),
+			("method", method.into()),
+			("params", params.into()),
+		]);
+		let response = Value::parse(&post(addr, &request.to_string()).1).unwrap();
+		assert_eq!(response.get("id"), Some(&Value::Number(7)));
+		match response.get("error") {
+			Some(error) => Err((
+				error.get("code").unwrap().as_i128().unwrap(),
+				error.get("message").unwrap().as_str().unwrap().to_string(),
+			)),
+			None => Ok(response.get("result").unwrap().clone()),
+		}
+	}
+
+	fn address(key: DevKey) -> Value {
+		key.account().to_string().into()
+	}
+
+	#[test]
+	fn query_state_and_submit_extrinsics() {
+		let (node, server) = start();
+		let addr = server.local_addr();
+		assert_eq!(call(addr, "state_getBalance", vec![address(DevKey::Dave)]), Ok(1_000.into()));
+		assert_eq!(call(addr, "system_accountNonce", vec![address(DevKey::Dave)]), Ok(0.into()));
+		assert_eq!(call(addr, "state_getMetadata", vec![]), Ok(Runtime::metadata().to_json()));
+
+		// Subscribe to new heads before authoring any blocks.
+		let mut subscription = TcpStream::connect(addr).unwrap();
+		let request = r#"{"jsonrpc":"2.0","id":1,"method":"chain_subscribeNewHeads"}"#;
+		subscription.write_all(http_request(request).as_bytes()).unwrap();
+		let mut lines = BufReader::new(subscription).lines().map(Result::unwrap);
+		assert_eq!(lines.next().unwrap(), "HTTP/1.1 200 OK");
+		let mut lines = lines.skip_while(|line| !line.is_empty()).skip(1);
+		let response = Value::parse(&lines.next().unwrap()).unwrap();
+		let subscription_id = response.get("result").unwrap().clone();
+
+		let to = DevKey::Eve.account();
+		let transfer = RuntimeCall::balances(balances::Call::transfer { to, amount: 10 });
+		let content = "dave's claim".to_string();
+		let claim = RuntimeCall::proof_of_existence(proof_of_existence::Call::create_claim {
+			claim: content.clone(),
+		});
+		for (nonce, runtime_call) in [(0, transfer.clone()), (1, claim)] {
+			let extrinsic = signed(&node, nonce, runtime_call).encode();
+			let hash = to_hex(&support::hash(&extrinsic));
+			let params = vec![to_hex(&extrinsic).into()];
+			assert_eq!(call(addr, "author_submitExtrinsic", params), Ok(hash.into()));
+		}
+		assert_eq!(call(addr, "system_accountNonce", vec![address(DevKey::Dave)]), Ok(2.into()));
+		let duplicate = signed(&node, 0, transfer).encode();
+		assert_eq!(
+			call(addr, "author_submitExtrinsic", vec![to_hex(&duplicate).into()]),
+			Err((1010, "a transaction with this nonce is already in the pool".to_string()))
+		);
+
+		let hash = to_hex(&node.author(&DevKey::Alice.pair(), 6_000).unwrap());
+		let notification = Value::parse(&lines.next().unwrap()).unwrap();
+		assert_eq!(notification.get("method").unwrap().as_str(), Some("chain_newHead"));
+		let params = notification.get("params").unwrap();
+		assert_eq!(params.get("subscription"), Some(&subscription_id));
+		assert_eq!(params.get("result").unwrap().get("hash"), Some(&hash.clone().into()));
+
+		let header = call(addr, "chain_getHeader", vec![]).unwrap();
+		assert_eq!(header.get("number"), Some(&1.into()));
+		assert_eq!(header.get("hash"), Some(&hash.clone().into()));
+		let block = call(addr, "chain_getBlock", vec![hash.into()]).unwrap();
+		assert_eq!(block.get("extrinsics").unwrap().as_array().unwrap().len(), 3);
+
+		assert_eq!(call(addr, "state_getBalance", vec![address(DevKey::Eve)]), Ok(10.into()));
+		let genesis_hash = header.get("parentHash").unwrap().clone();
+		let at_genesis = vec![address(DevKey::Eve), genesis_hash];
+		assert_eq!(call(addr, "state_getBalance", at_genesis), Ok(0.into()));
+
+		let claim = call(addr, "state_getClaim", vec![content.clone().into()]).unwrap();
+		assert_eq!(claim.get("owner"), Some(&address(DevKey::Dave)));
+		assert_eq!(claim.get("claimedAt"), Some(&6_000.into()));
+		assert_eq!(call(addr, "state_getClaim", vec!["unclaimed".into()]), Ok(Value::Null));
+		assert_eq!(call(addr, "chain_getBlock", vec![to_hex(&[0; 32]).into()]), Ok(Value::Null));
+
+		// A light client only needs the state root of the header to check a proof.
+		let state_root = from_hex(header.get("stateRoot").unwrap().as_str().unwrap()).unwrap();
+		let state_root = Hash::try_from(state_root).unwrap();
+		let prove = |key: &[u8]| {
+			let nodes = call(addr, "state_getReadProof", vec![to_hex(key).into()]).unwrap();
+			let nodes = nodes.as_array().unwrap().iter();
+			let nodes = nodes.map(|node| from_hex(node.as_str().unwrap()).unwrap()).collect();
+			trie::verify_proof(&state_root, key, &trie::StorageProof { nodes }).unwrap()
+		};
+		let balance = prove(&storage::storage_key("balances", "balances", &DevKey::Eve.account()));
+		assert_eq!(balance.map(|balance| types::Balance::decode(&balance)), Some(Ok(10)));
+		let claim = prove(&storage::storage_key("proof_of_existence", "claims", &content));
+		let claim = <(types::AccountId, types::Moment)>::decode(&claim.unwrap()).unwrap();
+		assert_eq!(claim, (DevKey::Dave.account(), 6_000));
+		let unclaimed = "unclaimed".to_string();
+		assert_eq!(prove(&storage::storage_key("proof_of_existence", "claims", &unclaimed)), None);
+	}
+
+	#[test]
+	fn invalid_requests() {
+		let (_node, server) = start();
+		let addr = server.local_addr();
+		let error = |code: i128, message: &str| Err((code, message.to_string()));
+
+		assert_eq!(call(addr, "state_getNothing", vec![]), error(-32601, "method not found"));
+		assert_eq!(
+			call(addr, "state_getBalance", vec!["not an address".into()]),
+			error(-32602, "invalid base58 character")
+		);
+		assert_eq!(
+			call(addr, "chain_getHeader", vec!["0x00".into()]),
+			error(-32602, "hash must be 32 bytes in hex")
+		);
+		assert_eq!(
+			call(addr, "author_submitExtrinsic", vec!["0x00".into()]),
+			error(-32602, "unexpected end of input")
+		);
+
+		// An unsigned extrinsic with tens of thousands of nested calls fits in a request, but must
+		// not overflow the stack of the node when decoded.
+		let inner = || RuntimeCall::timestamp(timestamp::Call::set { now: 0 });
+		let call_box = Box::new(inner());
+		let schedule = scheduler::Call::schedule { when: 1, periodic: None, call: call_box };
+		let (schedule, inner) = (RuntimeCall::scheduler(schedule).encode(), inner().encode());
+		let nested = schedule[..schedule.len() - inner.len()].repeat(60_000);
+		let extrinsic = to_hex(&[vec![0], nested, inner].concat());
+		assert_eq!(
+			call(addr, "author_submitExtrinsic", vec![extrinsic.into()]),
+			error(-32602, "value is nested too deeply")
+		);
+		assert!(call(addr, "chain_getHeader", vec![]).is_ok());
+
+		let (status, body) = post(addr, "[1,");
+		assert_eq!(status, "HTTP/1.1 200 OK");
+		let error = r#""error":{"code":-32700,"message":"unexpected end of JSON"}"#;
+		assert_eq!(body, format!(r#"{{"jsonrpc":"2.0","id":null,{}}}"#, error));
+		let (_, body) = post(addr, r#"{"id":1,"method":"chain_getHeader"}"#);
+		assert_eq!(
+			body,
+			r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32600,"message":"invalid request"}}"#
+		);
+
+		// Batches get a response for each request which is not a notification.
+		let batch = Value::Array(vec![
+			Value::parse(r#"{"jsonrpc":"2.0","id":1,"method":"system_accountNonce"}"#).unwrap(),
+			Value::parse(r#"{"jsonrpc":"2.0","method":"chain_getHeader"}"#).unwrap(),
+			Value::parse(r#"{"jsonrpc":"2.0","id":2,"method":"chain_subscribeNewHeads"}"#).unwrap(),
+		]);
+		let responses = Value::parse(&post(addr, &batch.to_string()).1).unwrap();
+		let responses = responses.as_array().unwrap();
+		assert_eq!(responses.len(), 2);
+		let message = responses[0].get("error").unwrap().get("message").unwrap();
+		assert_eq!(message.as_str(), Some("account must be an address"));
+		let message = responses[1].get("error").unwrap().get("message").unwrap();
+		assert_eq!(message.as_str(), Some("subscriptions cannot be made in a batch"));
+
+		let (status, body) = post(addr, r#"{"jsonrpc":"2.0","method":"chain_getHeader"}"#);
+		assert_eq!((status.as_str(), body.as_str()), ("HTTP/1.1 204 No Content", ""));
+
+		let mut stream = TcpStream::connect(addr).unwrap();
+		stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+		let mut response = String::new();
+		stream.read_to_string(&mut response).unwrap();
+		assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed"));
+	}
+}
@@ -0,0 +1,374 @@
+use crate::codec::{Decode, Encode};
+use crate::storage::{StorageMap, StorageValue};
+use crate::support::{DispatchResult, GetWeight, Weight, DEFAULT_WEIGHT};
+use core::fmt::Debug;
+use num::traits::{CheckedAdd, Zero};
+
+pub trait Config: crate::system::Config {
+	/// The overarching call type of the runtime, which can be scheduled.
+	type RuntimeCall: GetWeight + Clone + Debug + Encode + Decode;
+	/// The type used to name scheduled tasks, so they can be cancelled.
+	type TaskName: Ord + Clone + Debug + Encode + Decode;
+	/// The maximum total weight of scheduled calls dispatched in a single block. Calls which do not
+	/// fit are postponed to the next block.
+	const MAX_WEIGHT_PER_BLOCK: Weight;
+	/// The maximum number of times a periodic task can be dispatched. Scheduling pays for every
+	/// dispatch up front, so this bounds the weight of scheduling a task.
+	const MAX_PERIODIC_COUNT: u32;
+}
+
+/// The weight of scheduling `call`, which pays for each time it will be dispatched: `count` times
+/// if `periodic` is `(period, count)`, and once otherwise.
+fn schedule_weight<T: Config>(
+	call: &T::RuntimeCall,
+	periodic: &Option<(T::BlockNumber, u32)>,
+) -> Weight {
+	let count = periodic.map_or(1, |(_, count)| count);
+	DEFAULT_WEIGHT.saturating_add(call.weight().saturating_mul(count.into()))
+}
+
+/// A call which is scheduled to be dispatched at some future block.
+#[derive(Debug, Clone)]
+pub struct Scheduled<T: Config> {
+	/// The name of the task, if it was scheduled with one.
+	pub name: Option<T::TaskName>,
+	/// The account the call is dispatched on behalf of.
+	pub origin: T::AccountId,
+	/// The scheduled call.
+	pub call: T::RuntimeCall,
+	/// If the task is periodic: the number of blocks between each dispatch, and the number of
+	/// dispatches remaining after the next one.
+	pub periodic: Option<(T::BlockNumber, u32)>,
+}
+
+impl<T: Config> Encode for Scheduled<T> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.name.encode_to(dest);
+		self.origin.encode_to(dest);
+		self.call.encode_to(dest);
+		self.periodic.encode_to(dest);
+	}
+}
+
+impl<T: Config> Decode for Scheduled<T> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self {
+			name: Decode::decode_from(input)?,
+			origin: Decode::decode_from(input)?,
+			call: Decode::decode_from(input)?,
+			periodic: Decode::decode_from(input)?,
+		})
+	}
+}
+
+/// The key of a block number in the agenda. Block numbers are encoded little-endian, which does not
+/// sort by value, so the key reverses the encoding into big-endian. This assumes block numbers are
+/// fixed-width integers, and the key must be the last thing encoded, since decoding takes the whole
+/// input.
+#[derive(Debug, Clone, Copy)]
+struct AgendaKey<BlockNumber>(BlockNumber);
+
+impl<BlockNumber: Encode> Encode for AgendaKey<BlockNumber> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		dest.extend(self.0.encode().into_iter().rev());
+	}
+}
+
+impl<BlockNumber: Decode> Decode for AgendaKey<BlockNumber> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		let encoded = input.iter().rev().copied().collect::<Vec<_>>();
+		*input = &[];
+		BlockNumber::decode(&encoded).map(AgendaKey)
+	}
+}
+
+/// This is the Scheduler Module.
+/// It allows accounts to schedule calls to be dispatched at a future block, optionally repeating.
+#[derive(Debug, Clone)]
+#[macros::storage]
+pub struct Pallet<T: Config> {
+	/// The calls scheduled for each block number, in the order they were scheduled. The keys sort
+	/// by block number.
+	agenda: StorageMap<AgendaKey<T::BlockNumber>, Vec<Scheduled<T>>>,
+	/// The block number each named task is next scheduled for.
+	lookup: StorageMap<T::TaskName, T::BlockNumber>,
+	/// The last block the agenda was run for. New tasks must be scheduled after it.
+	block_number: StorageValue<T::BlockNumber>,
+}
+
+impl<T: Config> Pallet<T> {
+	/// Create a new instance of the Scheduler Module.
+	pub fn new() -> Self {
+		Self {
+			agenda: StorageMap::new(),
+			lookup: StorageMap::new(),
+			block_number: StorageValue::new(),
+		}
+	}
+
+	/// Get the block number a named task is next scheduled for, if it exists.
+	pub fn lookup(&self, name: &T::TaskName) -> Option<T::BlockNumber> {
+		self.lookup.get(name)
+	}
+
+	/// Take all the calls which are due at block `now`, along with the account to dispatch each of
+	/// them on behalf of. Calls are taken in the order they were scheduled, until the next call
+	/// would exceed `T::MAX_WEIGHT_PER_BLOCK`; the rest are left for the next block.
+	///
+	/// Periodic tasks are scheduled again for their next period.
+	pub fn take_due(&mut self, now: T::BlockNumber) -> Vec<(T::AccountId, T::RuntimeCall)> {
+		self.block_number.put(&now);
+		let mut due = Vec::new();
+		let mut weight: Weight = 0;
+
+		let due_blocks = self
+			.agenda
+			.iter()
+			.map(|(when, _)| when)
+			.take_while(|when| when.0 <= now)
+			.collect::<Vec<_>>();
+		'blocks: for when in due_blocks {
+			let AgendaKey(block) = when;
+			let mut tasks = self.agenda.remove(&when).unwrap_or_default().into_iter();
+			while let Some(task) = tasks.next() {
+				let call_weight = task.call.weight();
+				// We always take at least one call, so a call heavier than the limit cannot block the
+				// agenda forever.
+				if !due.is_empty() && weight.saturating_add(call_weight) > T::MAX_WEIGHT_PER_BLOCK {
+					// Put this task back along with everything after it, to retry next block.
+					let remaining = core::iter::once(task).chain(tasks).collect();
+					self.agenda.insert(&when, &remaining);
+					break 'blocks;
+				}
+				weight += call_weight;
+
+				if let Some(name) = &task.name {
+					self.lookup.remove(name);
+				}
+				due.push((task.origin.clone(), task.call.clone()));
+
+				if let Some((period, remaining)) = task.periodic {
+					if let (Some(next), false) = (block.checked_add(&period), remaining.is_zero()) {
+						let periodic = Some((period, remaining - 1));
+						self.insert(next, Scheduled { periodic, ..task });
+					}
+				}
+			}
+		}
+
+		due
+	}
+
+	/// Add a task to the agenda at block `when`.
+	fn insert(&mut self, when: T::BlockNumber, task: Scheduled<T>) {
+		if let Some(name) = &task.name {
+			self.lookup.insert(name, &when);
+		}
+		let mut tasks = self.agenda.get(&AgendaKey(when)).unwrap_or_default();
+		tasks.push(task);
+		self.agenda.insert(&AgendaKey(when), &tasks);
+	}
+
+	/// Check that a new task is scheduled in the future, and that its period is valid. Returns the
+	/// period as `(period, remaining)`, where `remaining` is the number of dispatches left after the
+	/// first one.
+	fn check_schedule(
+		&self,
+		when: T::BlockNumber,
+		periodic: Option<(T::BlockNumber, u32)>,
+	) -> Result<Option<(T::BlockNumber, u32)>, &'static str> {
+		if when <= self.block_number.get().unwrap_or(T::BlockNumber::zero()) {
+			return Err("task must be scheduled in the future");
+		}
+		match periodic {
+			Some((period, count)) if period.is_zero() || count.is_zero() => Err("invalid period"),
+			Some((_, count)) if count > T::MAX_PERIODIC_COUNT => Err("too many periods"),
+			Some((period, count)) => Ok(Some((period, count - 1))),
+			None => Ok(None),
+		}
+	}
+}
+
+// Scheduled calls are boxed in the arguments, since `RuntimeCall` contains this pallet's `Call`.
+#[allow(clippy::boxed_local)]
+#[macros::call]
+impl<T: Config> Pallet<T> {
+	/// Schedule `call` to be dispatched on behalf of the `caller` at block `when`. If `periodic` is
+	/// given as `(period, count)`, the call is dispatched `count` times, every `period` blocks, up
+	/// to `T::MAX_PERIODIC_COUNT` times.
+	#[call_index(0)]
+	#[weight(schedule_weight::<T>(call, periodic))]
+	pub fn schedule(
+		&mut self,
+		caller: T::AccountId,
+		when: T::BlockNumber,
+		periodic: Option<(T::BlockNumber, u32)>,
+		call: Box<T::RuntimeCall>,
+	) -> DispatchResult {
+		let periodic = self.check_schedule(when, periodic)?;
+		self.insert(when, Scheduled { name: None, origin: caller, call: *call, periodic });
+		Ok(())
+	}
+
+	/// Same as `schedule`, but the task is given a unique `name` so it can be cancelled.
+	#[call_index(1)]
+	#[weight(schedule_weight::<T>(call, periodic))]
+	pub fn schedule_named(
+		&mut self,
+		caller: T::AccountId,
+		name: T::TaskName,
+		when: T::BlockNumber,
+		periodic: Option<(T::BlockNumber, u32)>,
+		call: Box<T::RuntimeCall>,
+	) -> DispatchResult {
+		if self.lookup.contains_key(&name) {
+			return Err("a task with this name already exists");
+		}
+		let periodic = self.check_schedule(when, periodic)?;
+		self.insert(when, Scheduled { name: Some(name), origin: caller, call: *call, periodic });
+		Ok(())
+	}
+
+	/// Cancel a named task scheduled by the `caller`, including any future periods.
+	#[call_index(2)]
+	pub fn cancel_named(&mut self, caller: T::AccountId, name: T::TaskName) -> DispatchResult {
+		let when = AgendaKey(self.lookup(&name).ok_or("task does not exist")?);
+		let mut tasks = self.agenda.get(&when).ok_or("task does not exist")?;
+		let index = tasks
+			.iter()
+			.position(|task| task.name.as_ref() == Some(&name))
+			.ok_or("task does not exist")?;
+		if tasks[index].origin != caller {
+			return Err("this task belongs to someone else");
+		}
+		tasks.remove(index);
+		if tasks.is_empty() {
+			self.agenda.remove(&when);
+		} else {
+			self.agenda.insert(&when, &tasks);
+		}
+		self.lookup.remove(&name);
+		Ok(())
+	}
+}
+
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {}
+
+#[cfg(test)]
+mod test {
+	use crate::codec::{Decode, Encode};
+	use crate::support::{GetWeight, Weight};
+
+	struct TestConfig;
+
+	/// A stand-in for the runtime call type, with a given weight.
+	#[derive(Debug, Clone, PartialEq)]
+	struct TestCall(Weight);
+
+	impl Encode for TestCall {
+		fn encode_to(&self, dest: &mut Vec<u8>) {
+			self.0.encode_to(dest);
+		}
+	}
+
+	impl Decode for TestCall {
+		fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+			Ok(TestCall(Decode::decode_from(input)?))
+		}
+	}
+
+	impl GetWeight for TestCall {
+		fn weight(&self) -> Weight {
+			self.0
+		}
+	}
+
+	impl crate::system::Config for TestConfig {
+		type AccountId = String;
+		type BlockNumber = u32;
+		type Nonce = u32;
+		type Signature = crate::ed25519::Signature;
+	}
+
+	impl super::Config for TestConfig {
+		type RuntimeCall = TestCall;
+		type TaskName = String;
+		const MAX_WEIGHT_PER_BLOCK: Weight = 100;
+		const MAX_PERIODIC_COUNT: u32 = 10;
+	}
+
+	#[test]
+	fn periodic_named_task() {
+		let (alice, bob, task) = ("alice".to_string(), "bob".to_string(), "task".to_string());
+		let mut scheduler = super::Pallet::<TestConfig>::new();
+		let call = || Box::new(TestCall(1));
+		assert_eq!(
+			scheduler.schedule_named(alice.clone(), task.clone(), 0, None, call()),
+			Err("task must be scheduled in the future")
+		);
+		assert_eq!(
+			scheduler.schedule_named(alice.clone(), task.clone(), 2, Some((3, 3)), call()),
+			Ok(())
+		);
+		assert_eq!(
+			scheduler.schedule_named(alice.clone(), task.clone(), 5, None, call()),
+			Err("a task with this name already exists")
+		);
+
+		assert_eq!(scheduler.take_due(1), vec![]);
+		assert_eq!(scheduler.take_due(2), vec![(alice.clone(), TestCall(1))]);
+		assert_eq!(scheduler.lookup(&task), Some(5));
+		assert_eq!(scheduler.take_due(5), vec![(alice.clone(), TestCall(1))]);
+
+		assert_eq!(
+			scheduler.cancel_named(bob, task.clone()),
+			Err("this task belongs to someone else")
+		);
+		assert_eq!(scheduler.cancel_named(alice, task.clone()), Ok(()));
+		assert_eq!(scheduler.lookup(&task), None);
+		assert_eq!(scheduler.take_due(8), vec![]);
+	}
+
+	#[test]
+	fn weight_limit_postpones_tasks() {
+		let (alice, bob) = ("alice".to_string(), "bob".to_string());
+		let mut scheduler = super::Pallet::<TestConfig>::new();
+		assert_eq!(scheduler.schedule(alice.clone(), 1, None, Box::new(TestCall(60))), Ok(()));
+		assert_eq!(scheduler.schedule(bob.clone(), 1, None, Box::new(TestCall(60))), Ok(()));
+
+		assert_eq!(scheduler.take_due(1), vec![(alice, TestCall(60))]);
+		assert_eq!(scheduler.take_due(2), vec![(bob, TestCall(60))]);
+		assert_eq!(scheduler.take_due(3), vec![]);
+	}
+
+	#[test]
+	fn earlier_blocks_are_taken_first() {
+		let (alice, bob) = ("alice".to_string(), "bob".to_string());
+		let mut scheduler = super::Pallet::<TestConfig>::new();
+		// 256 is encoded as [0, 1, 0, 0], which sorts before [2, 0, 0, 0] unless it is reversed.
+		assert_eq!(scheduler.schedule(alice.clone(), 256, None, Box::new(TestCall(60))), Ok(()));
+		assert_eq!(scheduler.schedule(bob.clone(), 2, None, Box::new(TestCall(60))), Ok(()));
+
+		assert_eq!(scheduler.take_due(1), vec![]);
+		assert_eq!(scheduler.take_due(300), vec![(bob, TestCall(60))]);
+		assert_eq!(scheduler.take_due(301), vec![(alice, TestCall(60))]);
+	}
+
+	#[test]
+	fn periodic_tasks_pay_for_each_dispatch() {
+		let alice = "alice".to_string();
+		let mut scheduler = super::Pallet::<TestConfig>::new();
+		let schedule = |periodic| super::Call::<TestConfig>::schedule {
+			when: 1,
+			periodic,
+			call: Box::new(TestCall(5)),
+		};
+		assert_eq!(schedule(None).weight(), crate::support::DEFAULT_WEIGHT + 5);
+		assert_eq!(schedule(Some((1, 10))).weight(), crate::support::DEFAULT_WEIGHT + 50);
+		assert_eq!(
+			scheduler.schedule(alice.clone(), 1, Some((1, 11)), Box::new(TestCall(5))),
+			Err("too many periods")
+		);
+		assert_eq!(scheduler.schedule(alice, 1, Some((1, 10)), Box::new(TestCall(5))), Ok(()));
+	}
+}
@@ -0,0 +1,404 @@
+//! An in-process network simulator, for testing block production and propagation without real
+//! networking.
+//!
+//! Each node runs its own `Client` and transaction pool, and receives messages from the other nodes
+//! through a channel. The network delivers messages after a random latency, drops some of them,
+//! and can be partitioned. Time is simulated, so tests are fast and deterministic for a given seed.
+
+use crate::{
+	client::{Client, LongestChain},
+	ed25519,
+	support::{Hash, Pair},
+	transaction_pool::TransactionPool,
+	types, Runtime,
+};
+use std::{
+	collections::BTreeMap,
+	ops::Range,
+	sync::mpsc::{self, Receiver, Sender},
+};
+
+/// The time between two slots, at which authorities author blocks.
+pub const SLOT_DURATION: types::Moment = 6_000;
+
+/// The maximum number of extrinsics in the transaction pool of each node.
+const POOL_LIMIT: usize = 1_000;
+
+/// The messages nodes send each other.
+#[derive(Debug, Clone)]
+pub enum Message {
+	/// An extrinsic to add to the transaction pool.
+	Transaction(types::Extrinsic),
+	/// A block, either newly imported by the sender, the head of its best chain, or the response to
+	/// a request.
+	Block(types::Block),
+	/// A request for the block with the given hash.
+	BlockRequest(Hash),
+}
+
+/// How the network delivers messages.
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+	/// The range of latencies of messages, in milliseconds.
+	pub latency: Range<types::Moment>,
+	/// The probability that a message is dropped, between 0 and 1.
+	pub drop_rate: f64,
+	/// The seed of the random number generator, which decides latencies and drops.
+	pub seed: u64,
+}
+
+/// A node of the simulated network.
+pub struct Node {
+	pub client: Client,
+	pub pool: TransactionPool,
+	/// The key the node authors blocks with, if it is an authority.
+	key: Option<ed25519::Pair>,
+	/// The messages delivered to the node, along with the index of their sender.
+	inbox: Receiver<(usize, Message)>,
+	/// Blocks whose parent has not been imported yet, by hash.
+	orphans: BTreeMap<Hash, types::Block>,
+}
+
+/// Where a node sends a message.
+enum Destination {
+	/// A single peer.
+	Peer(usize),
+	/// All peers, except the given one, which the message came from.
+	AllExcept(Option<usize>),
+}
+
+impl Node {
+	/// Handle all the messages in the inbox, returning the messages to send in response.
+	fn handle_inbox(&mut self) -> Vec<(Destination, Message)> {
+		let mut outgoing = Vec::new();
+		while let Ok((from, message)) = self.inbox.try_recv() {
+			match message {
+				Message::Transaction(extrinsic) => {
+					if self.pool.submit(self.client.best_state(), extrinsic.clone()).is_ok() {
+						let message = Message::Transaction(extrinsic);
+						outgoing.push((Destination::AllExcept(Some(from)), message));
+					}
+				},
+				Message::Block(block) => {
+					let hash = block.header.hash();
+					if self.client.block(&hash).is_some() || self.orphans.contains_key(&hash) {
+						continue;
+					}
+					if self.client.block(&block.header.parent_hash).is_none() {
+						// Ask the sender for the earliest ancestor we are missing.
+						let mut missing = block.header.parent_hash;
+						while let Some(orphan) = self.orphans.get(&missing) {
+							missing = orphan.header.parent_hash;
+						}
+						self.orphans.insert(hash, block);
+						outgoing.push((Destination::Peer(from), Message::BlockRequest(missing)));
+						continue;
+					}
+					for block in self.import_with_orphans(block) {
+						outgoing.push((Destination::AllExcept(Some(from)), Message::Block(block)));
+					}
+				},
+				Message::BlockRequest(hash) => {
+					if let Some(block) = self.client.block(&hash) {
+						outgoing.push((Destination::Peer(from), Message::Block(block.clone())));
+					}
+				},
+			}
+		}
+		outgoing
+	}
+
+	/// Import `block`, and then any orphans which build on it. Returns the imported blocks.
+	fn import_with_orphans(&mut self, block: types::Block) -> Vec<types::Block> {
+		let mut imported = Vec::new();
+		let mut queue = vec![block];
+		while let Some(block) = queue.pop() {
+			let hash = block.header.hash();
+			if !self.import(block.clone()) {
+				continue;
+			}
+			imported.push(block);
+			let children = self
+				.orphans
+				.iter()
+				.filter(|(_, orphan)| orphan.header.parent_hash == hash)
+				.map(|(hash, _)| *hash)
+				.collect::<Vec<_>>();
+			queue.extend(children.iter().filter_map(|child| self.orphans.remove(child)));
+		}
+		imported
+	}
+
+	/// Import a block and update the transaction pool: the extrinsics of blocks retracted by a
+	/// reorg are submitted again, and those which are no longer valid are removed. Returns whether
+	/// the block was imported.
+	fn import(&mut self, block: types::Block) -> bool {
+		let Ok(outcome) = self.client.import_block(block) else { return false };
+		self.pool.maintain(&self.client, &outcome);
+		true
+	}
+
+	/// Author a block at time `now` on top of the best chain, if the node is the author of the next
+	/// block. Without authorities, every node with a key authors blocks.
+	fn author(&mut self, now: types::Moment) -> Option<types::Block> {
+		let key = self.key.as_ref()?;
+		let state = self.client.best_state();
+		let block_number = self.client.best_header().block_number + 1;
+		let slot_author = state.authorities.slot_author(block_number);
+		if slot_author.is_some_and(|author| author != key.public()) {
+			return None;
+		}
+		let ready = self.pool.ready(state);
+		let mut block = self.client.build_block(&self.client.best_hash(), now, ready).ok()?;
+		block.header.seal_with(key);
+		self.import(block.clone()).then_some(block)
+	}
+}
+
+/// A simulated network of nodes, which author blocks and gossip blocks and extrinsics.
+pub struct Network {
+	nodes: Vec<Node>,
+	/// The senders of the inbox of each node.
+	inboxes: Vec<Sender<(usize, Message)>>,
+	pub config: NetworkConfig,
+	/// The current simulated time.
+	now: types::Moment,
+	/// The time of the next slot.
+	next_slot: types::Moment,
+	/// The messages in flight, by delivery time and order of sending: `(from, to, message)`.
+	in_flight: BTreeMap<(types::Moment, u64), (usize, usize, Message)>,
+	/// The number of messages sent so far, used to order messages delivered at the same time.
+	sent: u64,
+	/// The partition each node is in. Messages between partitions are dropped.
+	partitions: Vec<usize>,
+	/// Whether nodes author blocks at each slot.
+	authoring: bool,
+	/// The state of the random number generator.
+	rng: u64,
+}
+
+impl Network {
+	/// Create a network with a node for each of `keys`, all starting from the `genesis` state.
+	/// Nodes with a key author blocks in their slots.
+	pub fn new(genesis: Runtime, keys: Vec<Option<ed25519::Pair>>, config: NetworkConfig) -> Self {
+		let (nodes, inboxes) = keys
+			.into_iter()
+			.map(|key| {
+				let (sender, inbox) = mpsc::channel();
+				let client = Client::new(genesis.clone(), LongestChain);
+				let pool = TransactionPool::new(POOL_LIMIT);
+				(Node { client, pool, key, inbox, orphans: BTreeMap::new() }, sender)
+			})
+			.unzip::<_, _, Vec<_>, Vec<_>>();
+		let partitions = vec![0; nodes.len()];
+		let rng = config.seed.max(1);
+		Self {
+			nodes,
+			inboxes,
+			config,
+			now: 0,
+			next_slot: SLOT_DURATION,
+			in_flight: BTreeMap::new(),
+			sent: 0,
+			partitions,
+			authoring: true,
+			rng,
+		}
+	}
+
+	/// Get the node with index `index`.
+	pub fn node(&self, index: usize) -> &Node {
+		&self.nodes[index]
+	}
+
+	/// The current simulated time.
+	pub fn now(&self) -> types::Moment {
+		self.now
+	}
+
+	/// Submit an extrinsic to the node with index `index`, which gossips it to its peers.
+	pub fn submit(
+		&mut self,
+		index: usize,
+		extrinsic: types::Extrinsic,
+	) -> Result<(), &'static str> {
+		let node = &mut self.nodes[index];
+		node.pool.submit(node.client.best_state(), extrinsic.clone())?;
+		self.send(index, Destination::AllExcept(None), Message::Transaction(extrinsic));
+		Ok(())
+	}
+
+	/// Split the network into `groups` of node indices. Messages between groups are dropped,
+	/// including those already in flight. Nodes not in any group are in a group of their own.
+	pub fn partition(&mut self, groups: &[&[usize]]) {
+		for (index, partition) in self.partitions.iter_mut().enumerate() {
+			*partition = groups
+				.iter()
+				.position(|group| group.contains(&index))
+				.map_or(groups.len() + index, |group| group);
+		}
+	}
+
+	/// Remove all partitions.
+	pub fn heal(&mut self) {
+		self.partitions.iter_mut().for_each(|partition| *partition = 0);
+	}
+
+	/// Start or stop authoring blocks. Nodes keep announcing their best blocks at each slot.
+	pub fn set_authoring(&mut self, authoring: bool) {
+		self.authoring = authoring;
+	}
+
+	/// Run the network for `duration` milliseconds of simulated time, delivering messages and
+	/// authoring blocks at each slot.
+	pub fn run_for(&mut self, duration: types::Moment) {
+		let end = self.now + duration;
+		loop {
+			let next_delivery = self.in_flight.keys().next().map(|(time, _)| *time);
+			match next_delivery {
+				Some(time) if time <= self.next_slot && time <= end => {
+					self.now = time;
+					let (_, (from, to, message)) =
+						self.in_flight.pop_first().expect("a message is in flight");
+					self.deliver(from, to, message);
+				},
+				_ if self.next_slot <= end => {
+					self.now = self.next_slot;
+					self.next_slot += SLOT_DURATION;
+					self.slot();
+				},
+				_ => break,
+			}
+		}
+		self.now = end;
+	}
+
+	/// Whether all nodes have the same best block and state.
+	pub fn converged(&self) -> bool {
+		let best = |node: &Node| (node.client.best_hash(), node.client.best_state().state_root());
+		self.nodes.windows(2).all(|pair| best(&pair[0]) == best(&pair[1]))
+	}
+
+	/// At each slot, every node authors a block if it is its turn, and announces its best block.
+	fn slot(&mut self) {
+		for index in 0..self.nodes.len() {
+			let node = &mut self.nodes[index];
+			if self.authoring {
+				node.author(self.now);
+			}
+			let best = node.client.block(&node.client.best_hash()).expect("best block is imported");
+			let announcement = Message::Block(best.clone());
+			self.send(index, Destination::AllExcept(None), announcement);
+		}
+	}
+
+	/// Deliver a message to the inbox of a node, unless the network dropped it, and let the node
+	/// handle it.
+	fn deliver(&mut self, from: usize, to: usize, message: Message) {
+		if self.partitions[from] != self.partitions[to] || self.random() < self.config.drop_rate {
+			return;
+		}
+		self.inboxes[to].send((from, message)).expect("nodes live as long as the network");
+		for (destination, message) in self.nodes[to].handle_inbox() {
+			self.send(to, destination, message);
+		}
+	}
+
+	/// Send a message from a node, to be delivered after a random latency.
+	fn send(&mut self, from: usize, destination: Destination, message: Message) {
+		let peers = match destination {
+			Destination::Peer(peer) => vec![peer],
+			Destination::AllExcept(except) => (0..self.nodes.len())
+				.filter(|peer| *peer != from && Some(*peer) != except)
+				.collect(),
+		};
+		for to in peers {
+			let Range { start, end } = self.config.latency;
+			let spread = end.saturating_sub(start) as f64;
+			let latency = start + (self.random() * spread) as types::Moment;
+			self.in_flight.insert((self.now + latency, self.sent), (from, to, message.clone()));
+			self.sent += 1;
+		}
+	}
+
+	/// A random number between 0 and 1, from a xorshift generator.
+	fn random(&mut self) -> f64 {
+		self.rng ^= self.rng << 13;
+		self.rng ^= self.rng >> 7;
+		self.rng ^= self.rng << 17;
+		(self.rng >> 11) as f64 / (1u64 << 53) as f64
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{Network, NetworkConfig, SLOT_DURATION};
+	use crate::{balances, keystore::DevKey, support, types, Runtime, RuntimeCall};
+
+	const AUTHORITIES: [DevKey; 3] = [DevKey::Alice, DevKey::Bob, DevKey::Charlie];
+
+	/// A network of the three authorities and an observer, which does not author blocks.
+	fn network(latency: std::ops::Range<types::Moment>, drop_rate: f64) -> Network {
+		let mut genesis = Runtime::new();
+		genesis.balances.set_balance(&DevKey::Dave.account(), 1_000);
+		genesis.authorities.set_authorities(AUTHORITIES.iter().map(|key| key.public()).collect());
+		let keys = AUTHORITIES.iter().map(|key| Some(key.pair())).chain([None]).collect();
+		Network::new(genesis, keys, NetworkConfig { latency, drop_rate, seed: 42 })
+	}
+
+	fn transfer(network: &Network, nonce: types::Nonce) -> types::Extrinsic {
+		let to = DevKey::Eve.account();
+		let call = RuntimeCall::balances(balances::Call::transfer { to, amount: 10 });
+		let genesis_hash = network.node(0).client.genesis_hash();
+		support::Extrinsic::new_signed(&DevKey::Dave.pair(), call, nonce, 0, genesis_hash)
+	}
+
+	fn eve(network: &Network, index: usize) -> types::Balance {
+		network.node(index).client.best_state().balances.balance(&DevKey::Eve.account())
+	}
+
+	#[test]
+	fn nodes_converge_despite_latency_and_drops() {
+		let mut network = network(100..2_000, 0.2);
+		for nonce in 0..5 {
+			// Transactions submitted to the observer reach the authorities through gossip.
+			network.submit(3, transfer(&network, nonce)).unwrap();
+			network.run_for(2 * SLOT_DURATION);
+		}
+		network.run_for(10 * SLOT_DURATION);
+		network.set_authoring(false);
+		network.run_for(10 * SLOT_DURATION);
+
+		assert!(network.converged());
+		assert!(network.node(3).client.best_header().block_number >= 15);
+		for index in 0..4 {
+			assert_eq!(eve(&network, index), 50);
+			assert!(network.node(index).pool.is_empty());
+		}
+	}
+
+	#[test]
+	fn partitions_stall_and_heal() {
+		let mut network = network(50..500, 0.0);
+		network.run_for(3 * SLOT_DURATION + SLOT_DURATION / 2);
+		assert_eq!(network.now(), 3 * SLOT_DURATION + SLOT_DURATION / 2);
+		assert!(network.converged());
+		assert_eq!(network.node(0).client.best_header().block_number, 3);
+
+		// Charlie is cut off, so no one can author the blocks in his slots.
+		network.partition(&[&[0, 1, 3], &[2]]);
+		network.submit(0, transfer(&network, 0)).unwrap();
+		network.run_for(5 * SLOT_DURATION);
+		assert_eq!(network.node(0).client.best_header().block_number, 4);
+		assert_eq!(network.node(2).client.best_header().block_number, 3);
+
+		network.heal();
+		network.run_for(5 * SLOT_DURATION);
+		network.set_authoring(false);
+		network.run_for(SLOT_DURATION);
+		assert!(network.converged());
+		// Charlie catches up in the first slot after healing, and authors block 5 in the next.
+		assert_eq!(network.node(0).client.best_header().block_number, 8);
+		assert_eq!(eve(&network, 2), 10);
+	}
+}
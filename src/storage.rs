@@ -0,0 +1,637 @@
+//! The storage of the runtime: a key-value map of bytes, which pallets access through typed storage
+//! items.
+//!
+//! Each pallet declares its storage items as fields of type [`StorageValue`], [`StorageMap`] or
+//! [`StorageDoubleMap`], and the `#[macros::storage]` attribute on the pallet struct lists them.
+//! Items keep their values encoded, so the state of every pallet is a set of key-value pairs. The
+//! runtime attaches the items of all pallets to one [`SharedStorage`], where the keys of each item
+//! are prefixed with the name of its pallet and its own name, see [`item_prefix`]. That is the
+//! state which is hashed into a trie, persisted and rolled back, the same way for every pallet.
+//!
+//! The values in storage are always written by the items, so they are expected to decode. Storage
+//! restored with [`SharedStorage::set_storage`] must come from the same runtime.
+
+use crate::{
+	codec::{Decode, Encode},
+	support::{hash, Hash},
+	trie::Trie,
+};
+use std::{
+	collections::BTreeMap,
+	fmt,
+	marker::PhantomData,
+	sync::{Arc, Mutex, MutexGuard},
+};
+
+/// The length of the prefix of the keys of a storage item.
+pub const PREFIX_LEN: usize = 32;
+
+/// A key-value map of bytes, ordered by key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Storage {
+	entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Storage {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn get(&self, key: &[u8]) -> Option<&[u8]> {
+		self.entries.get(key).map(Vec::as_slice)
+	}
+
+	pub fn insert(&mut self, key: Vec<u8>, value: Vec<u8>) {
+		self.entries.insert(key, value);
+	}
+
+	pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+		self.entries.remove(key)
+	}
+
+	pub fn contains_key(&self, key: &[u8]) -> bool {
+		self.entries.contains_key(key)
+	}
+
+	/// Iterate over all the pairs, in the order of their keys.
+	pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+		self.entries.iter().map(|(key, value)| (key.as_slice(), value.as_slice()))
+	}
+
+	/// Iterate over the pairs whose key starts with `prefix`, in the order of their keys.
+	pub fn iter_prefix(&self, prefix: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+		let prefix = prefix.to_vec();
+		self.entries
+			.range(prefix.clone()..)
+			.take_while(move |(key, _)| key.starts_with(&prefix))
+			.map(|(key, value)| (key.as_slice(), value.as_slice()))
+	}
+
+	/// Remove all the pairs whose key starts with `prefix`.
+	pub fn remove_prefix(&mut self, prefix: &[u8]) {
+		let keys = self.iter_prefix(prefix).map(|(key, _)| key.to_vec()).collect::<Vec<_>>();
+		for key in keys {
+			self.entries.remove(&key);
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// The root of the trie of all the pairs, which changes whenever any of them does. See
+	/// [`Trie`] for proving the pairs against it.
+	pub fn root(&self) -> Hash {
+		Trie::new(self).root()
+	}
+}
+
+impl FromIterator<(Vec<u8>, Vec<u8>)> for Storage {
+	fn from_iter<I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>>(iter: I) -> Self {
+		Self { entries: iter.into_iter().collect() }
+	}
+}
+
+// Storage is encoded as the list of its pairs, in the order of their keys.
+impl Encode for Storage {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		(self.entries.len() as u32).encode_to(dest);
+		for (key, value) in &self.entries {
+			key.encode_to(dest);
+			value.encode_to(dest);
+		}
+	}
+}
+
+impl Decode for Storage {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		let pairs = Vec::<(Vec<u8>, Vec<u8>)>::decode_from(input)?;
+		let len = pairs.len();
+		let storage = Storage::from_iter(pairs);
+		if storage.len() != len {
+			return Err("duplicate key in storage");
+		}
+		Ok(storage)
+	}
+}
+
+/// The prefix of the keys of the storage item `item` of `pallet`: the first half of the hash of
+/// each name. This keeps the keys of different items apart, and of the same length.
+pub fn item_prefix(pallet: &str, item: &str) -> Vec<u8> {
+	let half = PREFIX_LEN / 2;
+	[&hash(pallet.as_bytes())[..half], &hash(item.as_bytes())[..half]].concat()
+}
+
+/// The key of `key` in the storage map `item` of `pallet`, which is also its key in the state trie.
+/// The key in a `StorageDoubleMap` is the pair of its keys. A `StorageValue` is at its prefix.
+pub fn storage_key(pallet: &str, item: &str, key: &impl Encode) -> Vec<u8> {
+	let mut storage_key = item_prefix(pallet, item);
+	key.encode_to(&mut storage_key);
+	storage_key
+}
+
+/// The storage of a runtime, shared by the storage items of all its pallets. Cloning it gives
+/// another handle to the same storage.
+///
+/// Changes can be made in transactions, which are committed or rolled back as a whole. Each open
+/// transaction keeps the previous value of the keys it changes, so rolling back only touches those
+/// keys rather than copying the rest of the storage.
+#[derive(Clone, Default)]
+pub struct SharedStorage(Arc<Mutex<Overlay>>);
+
+/// The storage behind a [`SharedStorage`], along with a changeset for each open transaction: the
+/// value each key it changed had before, or `None` if the key had no value.
+#[derive(Default)]
+struct Overlay {
+	storage: Storage,
+	changesets: Vec<BTreeMap<Vec<u8>, Option<Vec<u8>>>>,
+}
+
+impl Overlay {
+	/// Set the value of `key`, or remove it if `value` is `None`, recording the previous value in
+	/// the innermost transaction. Returns the previous value.
+	fn set(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) -> Option<Vec<u8>> {
+		let previous = match value {
+			Some(value) => self.storage.entries.insert(key.clone(), value),
+			None => self.storage.entries.remove(&key),
+		};
+		if let Some(changeset) = self.changesets.last_mut() {
+			changeset.entry(key).or_insert_with(|| previous.clone());
+		}
+		previous
+	}
+}
+
+impl SharedStorage {
+	pub fn new(storage: Storage) -> Self {
+		Self(Arc::new(Mutex::new(Overlay { storage, changesets: Vec::new() })))
+	}
+
+	fn lock(&self) -> MutexGuard<'_, Overlay> {
+		self.0.lock().expect("the storage is only locked by methods which do not panic")
+	}
+
+	pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.lock().storage.get(key).map(<[u8]>::to_vec)
+	}
+
+	pub fn insert(&self, key: Vec<u8>, value: Vec<u8>) -> Option<Vec<u8>> {
+		self.lock().set(key, Some(value))
+	}
+
+	pub fn remove(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.lock().set(key.to_vec(), None)
+	}
+
+	pub fn contains_key(&self, key: &[u8]) -> bool {
+		self.lock().storage.contains_key(key)
+	}
+
+	/// The pairs whose key starts with `prefix`, in the order of their keys.
+	pub fn iter_prefix(&self, prefix: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+		let overlay = self.lock();
+		let pairs = overlay.storage.iter_prefix(prefix);
+		pairs.map(|(key, value)| (key.to_vec(), value.to_vec())).collect()
+	}
+
+	/// Remove all the pairs whose key starts with `prefix`.
+	pub fn remove_prefix(&self, prefix: &[u8]) {
+		let mut overlay = self.lock();
+		let keys = overlay.storage.iter_prefix(prefix).map(|(key, _)| key.to_vec());
+		let keys = keys.collect::<Vec<_>>();
+		for key in keys {
+			overlay.set(key, None);
+		}
+	}
+
+	/// A copy of all the pairs in storage.
+	pub fn storage(&self) -> Storage {
+		self.lock().storage.clone()
+	}
+
+	/// Replace all the pairs in storage with those of `storage`.
+	pub fn set_storage(&self, storage: &Storage) {
+		let mut overlay = self.lock();
+		let removed = overlay
+			.storage
+			.iter()
+			.filter(|(key, _)| !storage.contains_key(key))
+			.map(|(key, _)| key.to_vec())
+			.collect::<Vec<_>>();
+		for key in removed {
+			overlay.set(key, None);
+		}
+		for (key, value) in storage.iter() {
+			overlay.set(key.to_vec(), Some(value.to_vec()));
+		}
+	}
+
+	/// The root of the trie of all the pairs in storage.
+	pub fn root(&self) -> Hash {
+		self.lock().storage.root()
+	}
+
+	/// Start a transaction, nested in any which is already open.
+	pub fn start_transaction(&self) {
+		self.lock().changesets.push(BTreeMap::new());
+	}
+
+	/// Keep the changes of the innermost transaction. If it is nested, its changes are rolled back
+	/// along with the outer transaction.
+	pub fn commit_transaction(&self) {
+		let mut overlay = self.lock();
+		let changeset = overlay.changesets.pop().expect("a transaction is open");
+		if let Some(outer) = overlay.changesets.last_mut() {
+			for (key, previous) in changeset {
+				outer.entry(key).or_insert(previous);
+			}
+		}
+	}
+
+	/// Undo the changes of the innermost transaction.
+	pub fn rollback_transaction(&self) {
+		let mut overlay = self.lock();
+		let changeset = overlay.changesets.pop().expect("a transaction is open");
+		for (key, previous) in changeset {
+			match previous {
+				Some(value) => overlay.storage.insert(key, value),
+				None => drop(overlay.storage.remove(&key)),
+			}
+		}
+	}
+}
+
+impl fmt::Debug for SharedStorage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.lock().storage.fmt(f)
+	}
+}
+
+/// A typed storage item, which keeps its values in a [`SharedStorage`] under a prefix of its own.
+/// A new item has storage of its own and an empty prefix, until it is attached to the storage of a
+/// runtime.
+pub trait StorageItem {
+	/// The storage the item keeps its values in.
+	fn shared_storage(&self) -> &SharedStorage;
+
+	/// The pairs of the item, without its prefix.
+	fn pairs(&self) -> Storage;
+
+	/// Keep the values of the item in `storage` under `prefix` from now on, copying the pairs it
+	/// holds there.
+	fn attach(&mut self, storage: &SharedStorage, prefix: Vec<u8>);
+}
+
+/// A trait for pallets whose state is kept in storage items. This is implemented by
+/// `#[macros::storage]`.
+pub trait PalletStorage {
+	/// The storage items of the pallet, along with their names.
+	fn storage_items(&self) -> Vec<(&'static str, &dyn StorageItem)>;
+
+	/// The storage items of the pallet, along with their names, to be attached.
+	fn storage_items_mut(&mut self) -> Vec<(&'static str, &mut dyn StorageItem)>;
+
+	/// Attach the storage items of this pallet to `storage`, where the pallet is named `pallet`, so
+	/// the keys of each item are prefixed by [`item_prefix`].
+	fn attach_storage(&mut self, pallet: &str, storage: &SharedStorage) {
+		for (item, storage_item) in self.storage_items_mut() {
+			storage_item.attach(storage, item_prefix(pallet, item));
+		}
+	}
+
+	/// The storage the items of this pallet are kept in, if it has any items.
+	fn shared_storage(&self) -> Option<SharedStorage> {
+		self.storage_items().first().map(|(_, item)| item.shared_storage().clone())
+	}
+}
+
+fn decode<T: Decode>(bytes: &[u8]) -> T {
+	T::decode(bytes).expect("storage items only hold values they encoded")
+}
+
+/// A storage item holding a single value, at its prefix.
+pub struct StorageValue<V> {
+	storage: SharedStorage,
+	prefix: Vec<u8>,
+	_types: PhantomData<V>,
+}
+
+impl<V: Encode + Decode> StorageValue<V> {
+	pub fn new() -> Self {
+		Self { storage: SharedStorage::default(), prefix: Vec::new(), _types: PhantomData }
+	}
+
+	pub fn get(&self) -> Option<V> {
+		self.storage.get(&self.prefix).map(|value| decode(&value))
+	}
+
+	pub fn put(&mut self, value: &V) {
+		self.storage.insert(self.prefix.clone(), value.encode());
+	}
+
+	pub fn take(&mut self) -> Option<V> {
+		self.storage.remove(&self.prefix).map(|value| decode(&value))
+	}
+
+	#[allow(dead_code, reason = "part of the API of every value, though no pallet needs it yet")]
+	pub fn kill(&mut self) {
+		self.storage.remove(&self.prefix);
+	}
+
+	#[allow(dead_code, reason = "part of the API of every value, though no pallet needs it yet")]
+	pub fn exists(&self) -> bool {
+		self.storage.contains_key(&self.prefix)
+	}
+}
+
+/// A storage item mapping keys to values. Keys are stored encoded after the prefix, so they are
+/// iterated in the order of their encoding.
+pub struct StorageMap<K, V> {
+	storage: SharedStorage,
+	prefix: Vec<u8>,
+	_types: PhantomData<(K, V)>,
+}
+
+impl<K: Encode, V: Encode + Decode> StorageMap<K, V> {
+	pub fn new() -> Self {
+		Self { storage: SharedStorage::default(), prefix: Vec::new(), _types: PhantomData }
+	}
+
+	fn key(&self, key: &K) -> Vec<u8> {
+		let mut storage_key = self.prefix.clone();
+		key.encode_to(&mut storage_key);
+		storage_key
+	}
+
+	pub fn get(&self, key: &K) -> Option<V> {
+		self.storage.get(&self.key(key)).map(|value| decode(&value))
+	}
+
+	pub fn insert(&mut self, key: &K, value: &V) {
+		self.storage.insert(self.key(key), value.encode());
+	}
+
+	pub fn remove(&mut self, key: &K) -> Option<V> {
+		self.storage.remove(&self.key(key)).map(|value| decode(&value))
+	}
+
+	pub fn contains_key(&self, key: &K) -> bool {
+		self.storage.contains_key(&self.key(key))
+	}
+
+	/// Iterate over all the keys and values.
+	pub fn iter(&self) -> impl Iterator<Item = (K, V)> + '_
+	where
+		K: Decode,
+	{
+		let len = self.prefix.len();
+		self.storage
+			.iter_prefix(&self.prefix)
+			.into_iter()
+			.map(move |(key, value)| (decode(&key[len..]), decode(&value)))
+	}
+}
+
+/// A storage item mapping pairs of keys to values. The values of each first key can be iterated
+/// or removed together.
+pub struct StorageDoubleMap<K1, K2, V> {
+	storage: SharedStorage,
+	prefix: Vec<u8>,
+	_types: PhantomData<(K1, K2, V)>,
+}
+
+impl<K1: Encode, K2: Encode, V: Encode + Decode> StorageDoubleMap<K1, K2, V> {
+	pub fn new() -> Self {
+		Self { storage: SharedStorage::default(), prefix: Vec::new(), _types: PhantomData }
+	}
+
+	/// The key of the values under `key1`, which is the prefix of the key of each of them.
+	fn prefix(&self, key1: &K1) -> Vec<u8> {
+		let mut prefix = self.prefix.clone();
+		key1.encode_to(&mut prefix);
+		prefix
+	}
+
+	/// The key of a pair of keys, which starts with the encoding of the first key. Encodings never
+	/// start with another encoding of the same type, so the first keys cannot be confused.
+	fn key(&self, key1: &K1, key2: &K2) -> Vec<u8> {
+		let mut key = self.prefix(key1);
+		key2.encode_to(&mut key);
+		key
+	}
+
+	pub fn get(&self, key1: &K1, key2: &K2) -> Option<V> {
+		self.storage.get(&self.key(key1, key2)).map(|value| decode(&value))
+	}
+
+	pub fn insert(&mut self, key1: &K1, key2: &K2, value: &V) {
+		self.storage.insert(self.key(key1, key2), value.encode());
+	}
+
+	pub fn remove(&mut self, key1: &K1, key2: &K2) -> Option<V> {
+		self.storage.remove(&self.key(key1, key2)).map(|value| decode(&value))
+	}
+
+	pub fn contains_key(&self, key1: &K1, key2: &K2) -> bool {
+		self.storage.contains_key(&self.key(key1, key2))
+	}
+
+	/// Iterate over the second keys and values under `key1`.
+	pub fn iter_prefix(&self, key1: &K1) -> impl Iterator<Item = (K2, V)> + '_
+	where
+		K2: Decode,
+	{
+		let prefix = self.prefix(key1);
+		let len = prefix.len();
+		self.storage
+			.iter_prefix(&prefix)
+			.into_iter()
+			.map(move |(key, value)| (decode(&key[len..]), decode(&value)))
+	}
+
+	/// Remove all the values under `key1`.
+	pub fn remove_prefix(&mut self, key1: &K1) {
+		self.storage.remove_prefix(&self.prefix(key1));
+	}
+}
+
+macro_rules! impl_storage_item {
+	( $( $item:ident < $( $param:ident ),* > ),* ) => {
+		$(
+			impl< $( $param ),* > StorageItem for $item< $( $param ),* > {
+				fn shared_storage(&self) -> &SharedStorage {
+					&self.storage
+				}
+
+				fn pairs(&self) -> Storage {
+					let len = self.prefix.len();
+					self.storage
+						.iter_prefix(&self.prefix)
+						.into_iter()
+						.map(|(key, value)| (key[len..].to_vec(), value))
+						.collect()
+				}
+
+				fn attach(&mut self, storage: &SharedStorage, prefix: Vec<u8>) {
+					for (key, value) in self.pairs().iter() {
+						storage.insert([prefix.as_slice(), key].concat(), value.to_vec());
+					}
+					self.storage = storage.clone();
+					self.prefix = prefix;
+				}
+			}
+
+			// These are not derived, since that would require every type parameter to implement
+			// them. A clone has its own copy of the pairs, rather than sharing the storage.
+			impl< $( $param ),* > Clone for $item< $( $param ),* > {
+				fn clone(&self) -> Self {
+					let storage = SharedStorage::new(self.pairs());
+					Self { storage, prefix: Vec::new(), _types: PhantomData }
+				}
+			}
+
+			impl< $( $param ),* > fmt::Debug for $item< $( $param ),* > {
+				fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+					self.pairs().fmt(f)
+				}
+			}
+		)*
+	};
+}
+
+impl_storage_item!(StorageValue<V>, StorageMap<K, V>, StorageDoubleMap<K1, K2, V>);
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[derive(Debug, Clone)]
+	#[macros::storage]
+	struct TestPallet {
+		total: StorageValue<u32>,
+		balances: StorageMap<String, u32>,
+		allowances: StorageDoubleMap<String, String, u32>,
+		not_storage: u32,
+	}
+
+	impl TestPallet {
+		fn new() -> Self {
+			Self {
+				total: StorageValue::new(),
+				balances: StorageMap::new(),
+				allowances: StorageDoubleMap::new(),
+				not_storage: 0,
+			}
+		}
+	}
+
+	#[test]
+	fn storage_items() {
+		let mut pallet = TestPallet::new();
+		assert_eq!(pallet.total.get(), None);
+		pallet.total.put(&10);
+		assert!(pallet.total.exists());
+		assert_eq!(pallet.total.take(), Some(10));
+		assert!(!pallet.total.exists());
+
+		pallet.balances.insert(&"bob".to_string(), &2);
+		pallet.balances.insert(&"alice".to_string(), &1);
+		assert_eq!(pallet.balances.get(&"alice".to_string()), Some(1));
+		assert_eq!(pallet.balances.remove(&"bob".to_string()), Some(2));
+		assert_eq!(pallet.balances.iter().collect::<Vec<_>>(), vec![("alice".to_string(), 1)]);
+
+		let (alice, bob, charlie) = ("alice".to_string(), "bob".to_string(), "charlie".to_string());
+		pallet.allowances.insert(&alice, &bob, &5);
+		pallet.allowances.insert(&alice, &charlie, &6);
+		pallet.allowances.insert(&bob, &alice, &7);
+		assert_eq!(pallet.allowances.get(&alice, &charlie), Some(6));
+		assert_eq!(
+			pallet.allowances.iter_prefix(&alice).collect::<Vec<_>>(),
+			vec![(bob.clone(), 5), (charlie.clone(), 6)]
+		);
+		pallet.allowances.remove_prefix(&alice);
+		assert!(!pallet.allowances.contains_key(&alice, &bob));
+		assert_eq!(pallet.allowances.get(&bob, &alice), Some(7));
+	}
+
+	#[test]
+	fn attach_to_shared_storage() {
+		let mut pallet = TestPallet::new();
+		pallet.total.put(&3);
+		pallet.balances.insert(&"alice".to_string(), &1);
+		pallet.not_storage = 4;
+
+		// Attaching copies the pairs the items already hold, under their prefixes.
+		let storage = SharedStorage::default();
+		pallet.attach_storage("test", &storage);
+		pallet.allowances.insert(&"alice".to_string(), &"bob".to_string(), &2);
+		let pairs = storage.storage();
+		assert_eq!(pairs.len(), 3);
+		let prefix = item_prefix("test", "total");
+		assert_eq!(pairs.get(&prefix), Some(3u32.encode().as_slice()));
+		assert_eq!(pairs.iter_prefix(&item_prefix("test", "balances")).count(), 1);
+		assert_eq!(pairs.iter_prefix(&item_prefix("other", "balances")).count(), 0);
+		assert_eq!(pallet.balances.iter().collect::<Vec<_>>(), vec![("alice".to_string(), 1)]);
+
+		let decoded = Storage::decode(&pairs.encode()).unwrap();
+		assert_eq!(decoded, pairs);
+		assert_eq!(decoded.root(), storage.root());
+
+		// A clone has its own copy of the storage.
+		let mut cloned = pallet.clone();
+		cloned.total.put(&5);
+		assert_eq!((pallet.total.get(), cloned.total.get()), (Some(3), Some(5)));
+		assert_eq!(cloned.allowances.get(&"alice".to_string(), &"bob".to_string()), Some(2));
+		assert_eq!(cloned.not_storage, 4);
+
+		// Restoring storage replaces what the items held before.
+		let mut restored = TestPallet::new();
+		let other = SharedStorage::default();
+		restored.attach_storage("test", &other);
+		restored.balances.insert(&"bob".to_string(), &7);
+		other.set_storage(&decoded);
+		assert_eq!(restored.total.get(), Some(3));
+		assert_eq!(restored.balances.get(&"bob".to_string()), None);
+		assert_eq!(other.root(), storage.root());
+	}
+
+	#[test]
+	fn transactions() {
+		let mut pallet = TestPallet::new();
+		let storage = SharedStorage::default();
+		pallet.attach_storage("test", &storage);
+		pallet.total.put(&1);
+		pallet.balances.insert(&"alice".to_string(), &1);
+		let root = storage.root();
+
+		storage.start_transaction();
+		pallet.total.put(&2);
+		pallet.balances.remove(&"alice".to_string());
+		pallet.balances.insert(&"bob".to_string(), &2);
+		storage.rollback_transaction();
+		assert_eq!(pallet.total.get(), Some(1));
+		assert_eq!(pallet.balances.iter().collect::<Vec<_>>(), vec![("alice".to_string(), 1)]);
+		assert_eq!(storage.root(), root);
+
+		// A nested transaction which is committed is still rolled back with the outer one.
+		storage.start_transaction();
+		pallet.total.put(&2);
+		storage.start_transaction();
+		pallet.total.put(&3);
+		pallet.balances.insert(&"bob".to_string(), &3);
+		storage.commit_transaction();
+		assert_eq!(pallet.total.get(), Some(3));
+		storage.rollback_transaction();
+		assert_eq!(pallet.total.get(), Some(1));
+		assert_eq!(pallet.balances.get(&"bob".to_string()), None);
+
+		storage.start_transaction();
+		pallet.total.put(&4);
+		storage.commit_transaction();
+		assert_eq!(pallet.total.get(), Some(4));
+	}
+}
@@ -1,4 +1,8 @@
+use crate::codec::{Decode, Encode};
+use sha2::{Digest, Sha256};
+
 /// The most primitive representation of a Blockchain block.
+#[derive(Debug, Clone)]
 pub struct Block<Header, Extrinsic> {
 	/// The block header contains metadata about the block.
 	pub header: Header,
@@ -6,28 +10,372 @@ pub struct Block<Header, Extrinsic> {
 	pub extrinsics: Vec<Extrinsic>,
 }
 
-/// We are using an extremely simplified header which only contains the current block number.
-/// On a real blockchain, you would expect to also find:
-/// - parent block hash
-/// - state root
-/// - extrinsics root
+/// We are using a simplified header which only contains the current block number, the roots
+/// needed to check the block, and the seal of its author. On a real blockchain, you would expect
+/// to also find:
+/// - a digest, with more information for consensus
 /// - etc...
-pub struct Header<BlockNumber> {
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header<BlockNumber, Seal> {
 	pub block_number: BlockNumber,
+	/// The hash of the header of the previous block.
+	pub parent_hash: Hash,
+	/// The hash of the extrinsics in the block, see `extrinsics_root`.
+	pub extrinsics_root: Hash,
+	/// The hash of the state after executing the block.
+	pub state_root: Hash,
+	/// The signature of the block author over the hash of the header, or `None` if the block is
+	/// not sealed.
+	pub seal: Option<Seal>,
+}
+
+impl<BlockNumber: Encode, Seal> Header<BlockNumber, Seal> {
+	/// The hash of this header, which identifies the block. The seal is not included, since it
+	/// signs the hash.
+	pub fn hash(&self) -> Hash {
+		let mut data = Vec::new();
+		self.block_number.encode_to(&mut data);
+		self.parent_hash.encode_to(&mut data);
+		self.extrinsics_root.encode_to(&mut data);
+		self.state_root.encode_to(&mut data);
+		hash(&data)
+	}
+
+	/// Seal the header by signing its hash with `pair`.
+	pub fn seal_with<P: Pair<Signature = Seal>>(&mut self, pair: &P) {
+		self.seal = Some(pair.sign(&self.hash()));
+	}
+}
+
+impl<BlockNumber: Encode, Seal: Encode> Encode for Header<BlockNumber, Seal> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.block_number.encode_to(dest);
+		self.parent_hash.encode_to(dest);
+		self.extrinsics_root.encode_to(dest);
+		self.state_root.encode_to(dest);
+		self.seal.encode_to(dest);
+	}
+}
+
+impl<BlockNumber: Decode, Seal: Decode> Decode for Header<BlockNumber, Seal> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self {
+			block_number: Decode::decode_from(input)?,
+			parent_hash: Decode::decode_from(input)?,
+			extrinsics_root: Decode::decode_from(input)?,
+			state_root: Decode::decode_from(input)?,
+			seal: Decode::decode_from(input)?,
+		})
+	}
+}
+
+impl<Header: Encode, Extrinsic: Encode> Encode for Block<Header, Extrinsic> {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.header.encode_to(dest);
+		self.extrinsics.encode_to(dest);
+	}
+}
+
+impl<Header: Decode, Extrinsic: Decode> Decode for Block<Header, Extrinsic> {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self { header: Decode::decode_from(input)?, extrinsics: Decode::decode_from(input)? })
+	}
+}
+
+/// The hash type used for blocks and roots.
+pub type Hash = [u8; 32];
+
+/// Hash some bytes with SHA-256.
+pub fn hash(data: &[u8]) -> Hash {
+	Sha256::digest(data).into()
+}
+
+/// The root of a list of extrinsics, committed to in the block header.
+pub fn extrinsics_root<Extrinsic: Encode>(extrinsics: &[Extrinsic]) -> Hash {
+	hash(&extrinsics.encode())
 }
 
 /// This is an "extrinsic": literally an external message from outside of the blockchain.
-/// This simplified version of an extrinsic tells us who is making the call, and which call they are
-/// making.
-pub struct Extrinsic<Caller, Call> {
-	pub caller: Caller,
+/// This simplified version of an extrinsic tells us which call is being made, and who is making it.
+///
+/// A signed extrinsic is authenticated by the signature of its caller. An unsigned extrinsic has no
+/// caller, and is only valid as an inherent: data the block author includes in the block, like the
+/// time it was authored.
+#[derive(Debug, Clone)]
+pub struct Extrinsic<Signer, Signature, Call, Nonce, Balance> {
+	/// The signature of the caller, or `None` if the extrinsic is unsigned.
+	pub signed: Option<SignedData<Signer, Signature, Nonce, Balance>>,
 	pub call: Call,
 }
 
+/// The data of a signed extrinsic.
+///
+/// The `nonce` must match the number of extrinsics the caller has made before, so an extrinsic can
+/// only be executed once and in order. The `tip` is paid on top of the fee for the call, to give
+/// the extrinsic a higher priority in the transaction pool.
+#[derive(Debug, Clone)]
+pub struct SignedData<Signer, Signature, Nonce, Balance> {
+	/// The public key of the caller, which identifies their account.
+	pub signer: Signer,
+	/// The signature of the `signing_payload` of the extrinsic.
+	pub signature: Signature,
+	pub nonce: Nonce,
+	pub tip: Balance,
+}
+
+impl<Signer, Signature, Call, Nonce, Balance> Extrinsic<Signer, Signature, Call, Nonce, Balance> {
+	/// Create an extrinsic making `call`, signed by `pair` for the chain with `genesis_hash`.
+	pub fn new_signed<P: Pair<Public = Signer, Signature = Signature>>(
+		pair: &P,
+		call: Call,
+		nonce: Nonce,
+		tip: Balance,
+		genesis_hash: Hash,
+	) -> Self
+	where
+		Call: Encode,
+		Nonce: Encode,
+		Balance: Encode,
+	{
+		let signature = pair.sign(&signing_payload(&call, &nonce, &tip, &genesis_hash));
+		Self { signed: Some(SignedData { signer: pair.public(), signature, nonce, tip }), call }
+	}
+
+	/// Create an unsigned extrinsic making `call`.
+	pub fn new_unsigned(call: Call) -> Self {
+		Self { signed: None, call }
+	}
+
+	/// The account which signed the extrinsic and the nonce it used, or `None` if it is unsigned.
+	pub fn sender(&self) -> Option<(Signer::AccountId, Nonce)>
+	where
+		Signer: IdentifyAccount + Clone,
+		Nonce: Copy,
+	{
+		self.signed.as_ref().map(|signed| (signed.signer.clone().into_account(), signed.nonce))
+	}
+}
+
+/// The payload signed by the caller of an extrinsic. It includes the genesis hash of the chain, so
+/// the extrinsic cannot be replayed on another chain.
+pub fn signing_payload<Call: Encode, Nonce: Encode, Balance: Encode>(
+	call: &Call,
+	nonce: &Nonce,
+	tip: &Balance,
+	genesis_hash: &Hash,
+) -> Vec<u8> {
+	let mut payload = call.encode();
+	nonce.encode_to(&mut payload);
+	tip.encode_to(&mut payload);
+	genesis_hash.encode_to(&mut payload);
+	payload
+}
+
+impl<Signer: Encode, Signature: Encode, Call: Encode, Nonce: Encode, Balance: Encode> Encode
+	for Extrinsic<Signer, Signature, Call, Nonce, Balance>
+{
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.signed.encode_to(dest);
+		self.call.encode_to(dest);
+	}
+}
+
+impl<Signer: Decode, Signature: Decode, Call: Decode, Nonce: Decode, Balance: Decode> Decode
+	for Extrinsic<Signer, Signature, Call, Nonce, Balance>
+{
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self { signed: Decode::decode_from(input)?, call: Decode::decode_from(input)? })
+	}
+}
+
+impl<Signer: Encode, Signature: Encode, Nonce: Encode, Balance: Encode> Encode
+	for SignedData<Signer, Signature, Nonce, Balance>
+{
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.signer.encode_to(dest);
+		self.signature.encode_to(dest);
+		self.nonce.encode_to(dest);
+		self.tip.encode_to(dest);
+	}
+}
+
+impl<Signer: Decode, Signature: Decode, Nonce: Decode, Balance: Decode> Decode
+	for SignedData<Signer, Signature, Nonce, Balance>
+{
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self {
+			signer: Decode::decode_from(input)?,
+			signature: Decode::decode_from(input)?,
+			nonce: Decode::decode_from(input)?,
+			tip: Decode::decode_from(input)?,
+		})
+	}
+}
+
+/// A signature which can be verified against the public key of its signer.
+pub trait Verify {
+	/// The public key of the signer.
+	type Signer: IdentifyAccount;
+
+	/// Check that this is a valid signature of `message` by `signer`.
+	fn verify(&self, message: &[u8], signer: &Self::Signer) -> bool;
+}
+
+/// A public key which identifies an account.
+pub trait IdentifyAccount {
+	/// The account identified by the public key.
+	type AccountId;
+
+	/// The account identified by this public key.
+	fn into_account(self) -> Self::AccountId;
+}
+
+/// A key pair which can sign messages.
+pub trait Pair {
+	/// The public key of the pair.
+	type Public;
+	/// The signatures the pair makes.
+	type Signature;
+
+	/// The public key of the pair.
+	fn public(&self) -> Self::Public;
+
+	/// Sign `message`.
+	fn sign(&self, message: &[u8]) -> Self::Signature;
+}
+
 /// The Result type for our runtime. When everything completes successfully, we return `Ok(())`,
 /// otherwise return a static error message.
 pub type DispatchResult = Result<(), &'static str>;
 
+/// The Result of applying an extrinsic. The outer error means the extrinsic is invalid and could
+/// not be applied at all, while the inner `DispatchResult` is the outcome of its call.
+pub type ApplyExtrinsicResult = Result<DispatchResult, &'static str>;
+
+/// The outcome of executing an extrinsic against a copy of the state, without committing it.
+#[derive(Debug)]
+pub struct DryRunResult<Event, Balance> {
+	/// The result the extrinsic would have if it was applied.
+	pub result: ApplyExtrinsicResult,
+	/// The events the extrinsic would emit.
+	pub events: Vec<Event>,
+	/// The fee the caller would pay, including the tip.
+	pub fee: Balance,
+}
+
+/// The outcome of an extrinsic included in an executed block.
+#[derive(Debug)]
+#[allow(dead_code, reason = "`main` only reads the result of each receipt")]
+pub struct ExtrinsicReceipt<Event, Balance> {
+	/// The outcome of the call of the extrinsic.
+	pub result: DispatchResult,
+	/// The weight consumed by the call.
+	pub weight: Weight,
+	/// The fee paid by the caller, including the tip.
+	pub fee: Balance,
+	/// The events emitted by the call.
+	pub events: Vec<Event>,
+}
+
+/// The outcome of a scheduled call dispatched at the start of a block.
+#[derive(Debug)]
+pub struct ScheduledReceipt<Event> {
+	/// The outcome of the call.
+	pub result: DispatchResult,
+	/// The weight consumed by the call.
+	pub weight: Weight,
+	/// The events emitted by the call.
+	pub events: Vec<Event>,
+}
+
+/// The report of executing a block, with a receipt for each of its scheduled calls and extrinsics,
+/// in order.
+#[derive(Debug)]
+#[allow(dead_code, reason = "`main` only reads the receipts of each report")]
+pub struct BlockReport<Event, Balance> {
+	/// The hash of the header of the executed block.
+	pub block_hash: Hash,
+	/// The receipt of each scheduled call dispatched before the extrinsics.
+	pub scheduled: Vec<ScheduledReceipt<Event>>,
+	/// The receipt of each extrinsic in the block.
+	pub receipts: Vec<ExtrinsicReceipt<Event, Balance>>,
+}
+
+/// An abstract measure of the resources consumed by executing a call.
+pub type Weight = u64;
+
+/// The weight of a call which does not declare one with `#[weight(..)]`.
+pub const DEFAULT_WEIGHT: Weight = 10;
+
+/// The maximum total weight of a block: the scheduled calls dispatched at its start, and its
+/// extrinsics.
+pub const MAX_BLOCK_WEIGHT: Weight = 1_000;
+
+/// A trait for calls which know their own weight.
+pub trait GetWeight {
+	/// The weight consumed by executing this call.
+	fn weight(&self) -> Weight;
+}
+
+/// A trait for charging the fee of an extrinsic before it is dispatched, implemented by the runtime.
+pub trait TransactionPayment {
+	/// The type used to identify who pays the fee.
+	type AccountId;
+	/// The call the fee is paid for.
+	type Call;
+	/// The currency the fee is paid in.
+	type Balance;
+
+	/// The fee for dispatching `call`, including the `tip`.
+	fn compute_fee(call: &Self::Call, tip: Self::Balance) -> Self::Balance;
+
+	/// Check whether `who` can currently pay `fee`.
+	fn can_pay_fee(&self, who: &Self::AccountId, fee: Self::Balance) -> bool;
+
+	/// Withdraw `fee` from `who`.
+	fn withdraw_fee(&mut self, who: &Self::AccountId, fee: Self::Balance) -> DispatchResult;
+}
+
+/// A trait for validating and dispatching unsigned extrinsics, implemented by the runtime.
+///
+/// Unsigned extrinsics have no caller to pay a fee or to dispatch them on behalf of, so the runtime
+/// decides which calls it accepts without a signature, and how to dispatch them.
+pub trait DispatchUnsigned {
+	/// The call of an unsigned extrinsic.
+	type Call;
+
+	/// Check whether `call` can be included in a block without a signature.
+	fn validate_unsigned(&self, call: &Self::Call) -> DispatchResult;
+
+	/// Dispatch an unsigned `call`, which has been validated.
+	fn dispatch_unsigned(&mut self, call: Self::Call) -> DispatchResult;
+
+	/// Check that the inherents every block must include, like the timestamp, have been dispatched.
+	/// This is called after the extrinsics of a block, before it is finalized. `inherents` are the
+	/// calls of the unsigned extrinsics at the start of the block, before any signed extrinsic.
+	fn check_inherents(&self, inherents: &[Self::Call]) -> DispatchResult;
+}
+
+/// A trait for checking the seal of a block header, implemented by the runtime. The seal shows the
+/// block was authored by someone allowed to author it.
+pub trait CheckSeal {
+	/// The header type of the runtime.
+	type Header;
+
+	/// Check the seal of `header`, against the state the block builds on.
+	fn check_seal(&self, header: &Self::Header) -> DispatchResult;
+}
+
+/// A trait for collecting the events emitted by the pallets of a runtime, implemented by the
+/// runtime.
+pub trait Events {
+	/// The overarching event type of the runtime.
+	type Event;
+
+	/// The events emitted by all pallets in the current block, in the order they were emitted.
+	fn events(&self) -> Vec<Self::Event>;
+}
+
 /// A trait which allows us to dispatch an incoming extrinsic to the appropriate state transition
 /// function call.
 pub trait Dispatch {
@@ -40,3 +388,29 @@ pub trait Dispatch {
 	/// based on the outcome of that function call.
 	fn dispatch(&mut self, caller: Self::Caller, call: Self::Call) -> DispatchResult;
 }
+
+/// Hooks which are called by the runtime for each pallet at the start and end of every block, in
+/// the order the pallets are declared in the runtime. Both do nothing by default.
+pub trait Hooks<BlockNumber> {
+	/// Called at the start of block `block_number`, before any calls are dispatched.
+	fn on_initialize(&mut self, _block_number: BlockNumber) {}
+
+	/// Called at the end of block `block_number`, after all the extrinsics have been dispatched.
+	fn on_finalize(&mut self, _block_number: BlockNumber) {}
+}
+
+/// A trait which allows the runtime to dispatch the calls of a pallet with access to the whole
+/// runtime, rather than only the state of that pallet. This is needed for pallets which dispatch
+/// other calls on behalf of some account, like `proxy`.
+pub trait RuntimeDispatch<Call>: Dispatch {
+	/// This function takes a `caller` and the pallet `call` they want to make, and returns a
+	/// `Result` based on the outcome of that function call.
+	fn runtime_dispatch(&mut self, caller: Self::Caller, call: Call) -> DispatchResult;
+}
+
+/// A trait for filtering calls, for example to restrict which calls a proxy can make on behalf of
+/// an account.
+pub trait InstanceFilter<Call> {
+	/// Returns `true` if `call` is allowed by this filter.
+	fn filter(&self, call: &Call) -> bool;
+}
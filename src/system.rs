@@ -1,44 +1,87 @@
-use std:: collections::BTreeMap;
-use std::ops::AddAssign;
+use std::{fmt::Debug, ops::AddAssign};
 use num::traits::{CheckedAdd, CheckedSub, Zero, One};
+use crate::codec::{Decode, Encode};
+use crate::storage::{StorageMap, StorageValue};
+use crate::support::{Hash, Verify};
 
 pub trait Config {
-	type AccountId: Ord + Clone;
-	type BlockNumber: Zero + CheckedAdd + CheckedSub + One + Copy + AddAssign;
-	type Nonce: Ord + Zero + One + Clone + CheckedAdd + Copy;
+	type AccountId: Ord + Clone + Debug + Encode + Decode;
+	type BlockNumber: Zero
+		+ CheckedAdd
+		+ CheckedSub
+		+ One
+		+ Copy
+		+ AddAssign
+		+ Ord
+		+ Encode
+		+ Decode;
+	type Nonce: Ord + Zero + One + Clone + CheckedAdd + Copy + Encode + Decode;
+	/// The signature scheme used to sign extrinsics.
+	type Signature: Verify;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[macros::storage]
 pub struct Pallet<T: Config> {
-	block_number: T::BlockNumber,
-	nonce: BTreeMap<T::AccountId, T::Nonce>
+	block_number: StorageValue<T::BlockNumber>,
+	nonce: StorageMap<T::AccountId, T::Nonce>,
+	// The hashes of headers are not part of the state, since a header commits to the state root
+	// and its hash cannot be known before. They are restored along with the state by the
+	// `restore` function of the runtime.
+	/// The hash of the header of the last finished block.
+	parent_hash: Hash,
+	/// The hash of the genesis header, which extrinsics are signed for.
+	genesis_hash: Hash,
 }
 
 impl <T: Config> Pallet<T> {
 	pub fn new() -> Self {
 		Self {
-			block_number: T::BlockNumber::zero(),
-			nonce: BTreeMap::new()
+			block_number: StorageValue::new(),
+			nonce: StorageMap::new(),
+			parent_hash: Hash::default(),
+			genesis_hash: Hash::default(),
 		}
 	}
 
-	pub fn block_number(&mut self) -> T::BlockNumber {
-		self.block_number
+	pub fn block_number(&self) -> T::BlockNumber {
+		self.block_number.get().unwrap_or(T::BlockNumber::zero())
 	}
 
 	pub fn inc_block_number(&mut self) {
-		self.block_number += T::BlockNumber::one();
+		self.block_number.put(&(self.block_number() + T::BlockNumber::one()));
 		//self.block_number = self.block_number.checked_add(&BlockNumber::one()).unwrap();
 	}
 
+	/// The hash of the header of the last finished block, which the next block must build on.
+	pub fn parent_hash(&self) -> Hash {
+		self.parent_hash
+	}
+
+	/// Set the hash of the header of the last finished block.
+	pub fn set_parent_hash(&mut self, hash: Hash) {
+		self.parent_hash = hash;
+	}
+
+	/// The hash of the genesis header, which identifies the chain.
+	pub fn genesis_hash(&self) -> Hash {
+		self.genesis_hash
+	}
+
+	/// Set the hash of the genesis header.
+	pub fn set_genesis_hash(&mut self, hash: Hash) {
+		self.genesis_hash = hash;
+	}
+
 	pub fn inc_nonce(&mut self, who: &T::AccountId) {
-		let nonce = self.nonce.get(who).cloned().unwrap_or(T::Nonce::zero());
+		let nonce = self.get_nonce(who);
 		let new_nonce = nonce.checked_add(&T::Nonce::one()).unwrap();
-		self.nonce.insert(who.clone(), new_nonce);
+		self.nonce.insert(who, &new_nonce);
 	}
 
-	pub fn get_nonce(&mut self, who: &T::AccountId) -> T::Nonce {
-		*self.nonce.get(who).unwrap_or(&T::Nonce::one())
+	/// The number of extrinsics `who` has made, which is the nonce its next extrinsic must use.
+	pub fn get_nonce(&self, who: &T::AccountId) -> T::Nonce {
+		self.nonce.get(who).unwrap_or(T::Nonce::zero())
 	}
 }
 
@@ -49,11 +92,12 @@ mod test {
 		type AccountId = String;
         type BlockNumber = u32;
         type Nonce = u32;
+        type Signature = crate::ed25519::Signature;
     }
 
 	#[test]
 	fn init_system() {
-		let mut system = super::Pallet::<TestConfig>::new();
+		let system = super::Pallet::<TestConfig>::new();
 		assert_eq!(system.block_number(), 0);
 	}
 
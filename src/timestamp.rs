@@ -0,0 +1,125 @@
+use crate::codec::{Decode, Encode};
+use crate::storage::StorageValue;
+use crate::support::DispatchResult;
+use core::fmt::Debug;
+use num::traits::{CheckedAdd, Zero};
+
+pub trait Config: crate::system::Config {
+	/// The type used to represent time, such as milliseconds since the unix epoch.
+	type Moment: Zero + CheckedAdd + Copy + Ord + Debug + Encode + Decode;
+	/// The minimum time between the timestamps of two blocks.
+	const MINIMUM_PERIOD: Self::Moment;
+}
+
+/// This is the Timestamp Module.
+/// It keeps track of the wall-clock time of the current block, which the block author sets with an
+/// inherent: an unsigned extrinsic which must appear exactly once in every block.
+#[derive(Debug, Clone)]
+#[macros::storage]
+pub struct Pallet<T: Config> {
+	/// The timestamp of the current block, or of the last block once it is finished.
+	now: StorageValue<T::Moment>,
+	/// Whether the timestamp has been set in the current block.
+	did_update: bool,
+}
+
+impl<T: Config> Pallet<T> {
+	/// Create a new instance of the Timestamp Module.
+	pub fn new() -> Self {
+		Self { now: StorageValue::new(), did_update: false }
+	}
+
+	/// Get the timestamp of the current block.
+	pub fn now(&self) -> T::Moment {
+		self.now.get().unwrap_or(T::Moment::zero())
+	}
+
+	/// Whether the timestamp has been set in the current block.
+	pub fn did_update(&self) -> bool {
+		self.did_update
+	}
+
+	/// Check whether the timestamp of the current block can be set to `now`: it must not have been
+	/// set yet in this block, and it must be at least `T::MINIMUM_PERIOD` after the timestamp of the
+	/// last block. The first block can have any timestamp.
+	pub fn check(&self, now: T::Moment) -> DispatchResult {
+		if self.did_update {
+			return Err("timestamp must be set only once per block");
+		}
+		let last = self.now();
+		let earliest = last.checked_add(&T::MINIMUM_PERIOD).ok_or("timestamp overflow")?;
+		if !last.is_zero() && now < earliest {
+			return Err("timestamp must increment by at least the minimum period");
+		}
+		Ok(())
+	}
+}
+
+#[macros::call]
+impl<T: Config> Pallet<T> {
+	/// Set the timestamp of the current block. This is an inherent, which the block author includes
+	/// unsigned at the start of the block.
+	///
+	/// Inherents must be included in every block, so they take up none of its weight.
+	#[call_index(0)]
+	#[unsigned]
+	#[weight(0)]
+	pub fn set(&mut self, now: T::Moment) -> DispatchResult {
+		self.check(now)?;
+		self.now.put(&now);
+		self.did_update = true;
+		Ok(())
+	}
+}
+
+impl<T: Config> crate::support::Hooks<T::BlockNumber> for Pallet<T> {
+	fn on_finalize(&mut self, _block_number: T::BlockNumber) {
+		self.did_update = false;
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::support::{Dispatch, Hooks};
+
+	struct TestConfig;
+
+	impl crate::system::Config for TestConfig {
+		type AccountId = String;
+		type BlockNumber = u32;
+		type Nonce = u32;
+		type Signature = crate::ed25519::Signature;
+	}
+
+	impl super::Config for TestConfig {
+		type Moment = u64;
+		const MINIMUM_PERIOD: u64 = 1_000;
+	}
+
+	#[test]
+	fn set_timestamp() {
+		let mut timestamp = super::Pallet::<TestConfig>::new();
+		assert_eq!(timestamp.now(), 0);
+
+		// The first block can have any timestamp, but only one.
+		assert_eq!(timestamp.set(5_000), Ok(()));
+		assert_eq!(timestamp.set(6_000), Err("timestamp must be set only once per block"));
+		assert_eq!(timestamp.now(), 5_000);
+		timestamp.on_finalize(1);
+
+		assert_eq!(
+			timestamp.check(5_999),
+			Err("timestamp must increment by at least the minimum period")
+		);
+		assert_eq!(timestamp.set(6_000), Ok(()));
+		assert!(timestamp.did_update());
+		timestamp.on_finalize(2);
+		assert!(!timestamp.did_update());
+
+		// The timestamp cannot be set on behalf of an account.
+		let call = super::Call::set { now: 7_000 };
+		let alice = "alice".to_string();
+		assert_eq!(timestamp.dispatch(alice, call), Err("call can only be dispatched unsigned"));
+		assert_eq!(timestamp.now(), 6_000);
+	}
+}
@@ -0,0 +1,246 @@
+use crate::{
+	client::{Client, ForkChoice, ImportOutcome},
+	types, Runtime,
+};
+use std::collections::BTreeMap;
+
+/// An extrinsic waiting in the pool, along with what we learned when validating it.
+#[derive(Debug)]
+struct PooledTransaction {
+	extrinsic: types::Extrinsic,
+	/// The fee the extrinsic pays, including its tip. Higher fees are included first.
+	fee: types::Balance,
+	/// The order the extrinsic was submitted in, used to break ties between equal fees.
+	insertion: u64,
+}
+
+/// An in-memory pool of extrinsics waiting to be included in a block.
+///
+/// Extrinsics are validated against the state of the runtime when they are submitted. Extrinsics
+/// with a nonce ahead of the caller's current nonce are kept until the extrinsics before them are
+/// submitted or included, and only then become ready.
+#[derive(Debug)]
+pub struct TransactionPool {
+	/// All the extrinsics in the pool, keyed by `(caller, nonce)`.
+	transactions: BTreeMap<(types::AccountId, types::Nonce), PooledTransaction>,
+	/// The maximum number of extrinsics the pool holds.
+	limit: usize,
+	/// The insertion counter for the next submitted extrinsic.
+	next_insertion: u64,
+}
+
+impl TransactionPool {
+	/// Create a new, empty pool which holds at most `limit` extrinsics.
+	pub fn new(limit: usize) -> Self {
+		Self { transactions: BTreeMap::new(), limit, next_insertion: 0 }
+	}
+
+	/// The number of extrinsics in the pool, both ready and waiting on earlier nonces.
+	pub fn len(&self) -> usize {
+		self.transactions.len()
+	}
+
+	/// Check whether the pool is empty.
+	#[allow(dead_code, reason = "the node only reports the number of extrinsics in the pool")]
+	pub fn is_empty(&self) -> bool {
+		self.transactions.is_empty()
+	}
+
+	/// Validate `extrinsic` against the current state of `runtime` and add it to the pool.
+	///
+	/// An extrinsic with the same caller and nonce as one already in the pool replaces it only if
+	/// it pays a higher fee. If the pool is full, the extrinsic paying the lowest fee is evicted,
+	/// along with the extrinsics of its caller with higher nonces, which could never become ready.
+	/// The submitted extrinsic may be one of them.
+	pub fn submit(&mut self, runtime: &Runtime, extrinsic: types::Extrinsic) -> Result<(), &'static str> {
+		let fee = runtime.validate_transaction(&extrinsic)?;
+		let key = extrinsic.sender().expect("only signed extrinsics are valid transactions");
+		if let Some(existing) = self.transactions.get(&key) {
+			if existing.fee >= fee {
+				return Err("a transaction with this nonce is already in the pool");
+			}
+		}
+
+		let insertion = self.next_insertion;
+		self.next_insertion += 1;
+		self.transactions.insert(key.clone(), PooledTransaction { extrinsic, fee, insertion });
+
+		if self.transactions.len() > self.limit {
+			let evicted = self.lowest_priority().expect("pool is not empty");
+			self.remove_with_dependents(&evicted);
+			if !self.transactions.contains_key(&key) {
+				return Err("the pool is full");
+			}
+		}
+		Ok(())
+	}
+
+	/// The next nonce `who` should use for a new extrinsic: its current nonce in `runtime`, after
+	/// the extrinsics it already has in the pool.
+	pub fn next_nonce(&self, runtime: &Runtime, who: &types::AccountId) -> types::Nonce {
+		let mut nonce = runtime.system.get_nonce(who);
+		while self.transactions.contains_key(&(who.clone(), nonce)) {
+			nonce += 1;
+		}
+		nonce
+	}
+
+	/// The extrinsics which are ready to be included on top of the current state of `runtime`,
+	/// highest fee first. Extrinsics from the same caller are always returned in nonce order, and
+	/// only if there is no gap between the caller's current nonce and theirs.
+	pub fn ready(&self, runtime: &Runtime) -> Vec<types::Extrinsic> {
+		// The next extrinsic we can include for each caller, given the ones already included.
+		let mut next = BTreeMap::new();
+		for (caller, _) in self.transactions.keys() {
+			next.entry(caller.clone()).or_insert_with(|| runtime.system.get_nonce(caller));
+		}
+
+		let mut ready = Vec::new();
+		loop {
+			let best = next
+				.iter_mut()
+				.filter_map(|(caller, nonce)| Some((self.transactions.get(&(caller.clone(), *nonce))?, nonce)))
+				.max_by(|(a, _), (b, _)| a.fee.cmp(&b.fee).then(b.insertion.cmp(&a.insertion)));
+			let Some((best, nonce)) = best else { break };
+			ready.push(best.extrinsic.clone());
+			*nonce += 1;
+		}
+		ready
+	}
+
+	/// Remove the extrinsics which are no longer valid after a block was imported into `runtime`:
+	/// those which were included, or whose caller can no longer pay the fee. An extrinsic which was
+	/// not included takes the extrinsics of its caller with higher nonces with it, since they could
+	/// never become ready.
+	pub fn prune(&mut self, runtime: &Runtime) {
+		let invalid = self
+			.transactions
+			.iter()
+			.filter(|(_, pooled)| runtime.validate_transaction(&pooled.extrinsic).is_err())
+			.map(|(key, _)| key.clone())
+			.collect::<Vec<_>>();
+		for key in invalid {
+			if key.1 < runtime.system.get_nonce(&key.0) {
+				self.transactions.remove(&key);
+			} else {
+				self.remove_with_dependents(&key);
+			}
+		}
+	}
+
+	/// Update the pool after a block was imported into `client`: the signed extrinsics of blocks
+	/// retracted by a reorg are submitted again, and those which are no longer valid on the new
+	/// best chain are removed.
+	pub fn maintain<F: ForkChoice>(&mut self, client: &Client<F>, outcome: &ImportOutcome) {
+		let retracted = outcome.reorg.iter().flat_map(|route| &route.retracted);
+		let blocks = retracted.filter_map(|hash| client.block(hash));
+		for extrinsic in blocks.flat_map(|block| &block.extrinsics) {
+			if extrinsic.signed.is_some() {
+				let _res = self.submit(client.best_state(), extrinsic.clone());
+			}
+		}
+		self.prune(client.best_state());
+	}
+
+	/// Remove the extrinsic with `key`, and the extrinsics of the same caller with higher nonces.
+	fn remove_with_dependents(&mut self, key: &(types::AccountId, types::Nonce)) {
+		let dependents = self
+			.transactions
+			.range(key.clone()..)
+			.take_while(|((caller, _), _)| *caller == key.0)
+			.map(|(key, _)| key.clone())
+			.collect::<Vec<_>>();
+		for key in dependents {
+			self.transactions.remove(&key);
+		}
+	}
+
+	/// The key of the extrinsic to evict when the pool is full: the lowest fee, and the most recent
+	/// among equal fees.
+	fn lowest_priority(&self) -> Option<(types::AccountId, types::Nonce)> {
+		self.transactions
+			.iter()
+			.min_by(|(_, a), (_, b)| a.fee.cmp(&b.fee).then(b.insertion.cmp(&a.insertion)))
+			.map(|(key, _)| key.clone())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::TransactionPool;
+	use crate::{balances, keystore::DevKey, support, types, Runtime, RuntimeCall};
+
+	fn transfer(caller: DevKey, nonce: types::Nonce, tip: types::Balance) -> types::Extrinsic {
+		let call = RuntimeCall::balances(balances::Call::transfer { to: DevKey::Bob.account(), amount: 1 });
+		support::Extrinsic::new_signed(&caller.pair(), call, nonce, tip, support::Hash::default())
+	}
+
+	fn runtime() -> Runtime {
+		let mut runtime = Runtime::new();
+		runtime.balances.set_balance(&DevKey::Alice.account(), 100);
+		runtime.balances.set_balance(&DevKey::Charlie.account(), 100);
+		runtime
+	}
+
+	fn nonces(extrinsics: &[types::Extrinsic]) -> Vec<(types::AccountId, types::Nonce)> {
+		extrinsics.iter().filter_map(|e| e.sender()).collect()
+	}
+
+	#[test]
+	fn orders_by_fee_and_nonce() {
+		let runtime = runtime();
+		let mut pool = TransactionPool::new(10);
+
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Bob, 0, 0)), Err("cannot pay the fee"));
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 1, 5)), Ok(()));
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Charlie, 0, 2)), Ok(()));
+
+		// Alice's extrinsic is waiting on nonce 0.
+		assert_eq!(nonces(&pool.ready(&runtime)), vec![(DevKey::Charlie.account(), 0)]);
+
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 0, 0)), Ok(()));
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 0, 0)), Err("a transaction with this nonce is already in the pool"));
+		assert_eq!(nonces(&pool.ready(&runtime)), vec![(DevKey::Charlie.account(), 0), (DevKey::Alice.account(), 0), (DevKey::Alice.account(), 1)]);
+
+		// A higher tip replaces the extrinsic with the same nonce.
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 0, 3)), Ok(()));
+		assert_eq!(nonces(&pool.ready(&runtime)), vec![(DevKey::Alice.account(), 0), (DevKey::Alice.account(), 1), (DevKey::Charlie.account(), 0)]);
+		assert_eq!(pool.len(), 3);
+	}
+
+	#[test]
+	fn prunes_included_and_evicts_when_full() {
+		let mut runtime = runtime();
+		let mut pool = TransactionPool::new(2);
+
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 0, 1)), Ok(()));
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Charlie, 0, 2)), Ok(()));
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 1, 0)), Err("the pool is full"));
+		// Evicting Alice's nonce 0 also evicts her nonce 1, which could never become ready.
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 1, 3)), Err("the pool is full"));
+		assert_eq!(nonces(&pool.ready(&runtime)), vec![(DevKey::Charlie.account(), 0)]);
+		assert_eq!(pool.len(), 1);
+
+		assert_eq!(runtime.apply_extrinsic(transfer(DevKey::Alice, 0, 0)), Ok(Ok(())));
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 1, 3)), Ok(()));
+		pool.prune(&runtime);
+		assert_eq!(nonces(&pool.ready(&runtime)), vec![(DevKey::Alice.account(), 1), (DevKey::Charlie.account(), 0)]);
+
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 0, 9)), Err("nonce has already been used"));
+	}
+
+	#[test]
+	fn prunes_extrinsics_which_can_never_be_ready() {
+		let mut runtime = runtime();
+		let mut pool = TransactionPool::new(10);
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Charlie, 0, 50)), Ok(()));
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Charlie, 1, 0)), Ok(()));
+		assert_eq!(pool.submit(&runtime, transfer(DevKey::Alice, 0, 0)), Ok(()));
+
+		// Charlie can still pay for nonce 1, but not for nonce 0, so neither can be included.
+		runtime.balances.set_balance(&DevKey::Charlie.account(), 30);
+		pool.prune(&runtime);
+		assert_eq!(nonces(&pool.ready(&runtime)), vec![(DevKey::Alice.account(), 0)]);
+		assert_eq!(pool.len(), 1);
+	}
+}
@@ -0,0 +1,286 @@
+//! A Merkle-Patricia trie over the runtime storage, which gives the state root of each block and
+//! proofs of individual keys.
+//!
+//! Keys are split into nibbles, and each node of the trie is stored under the hash of its encoding,
+//! so the root hash commits to every pair in storage. A proof of a key is the list of the nodes on
+//! the path from the root to the key. With only the state root from a header, a light client can
+//! check a proof with [`verify_proof`] to learn the value of the key, or that it has none, without
+//! the rest of the state.
+
+use crate::{
+	codec::{Decode, Encode},
+	storage::Storage,
+	support::{hash, Hash},
+};
+use std::collections::BTreeMap;
+
+/// A node of the trie. Partial keys are sequences of nibbles, one nibble per byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+	/// The root of a trie without any keys.
+	Empty,
+	/// The end of the path of a single key.
+	Leaf { partial: Vec<u8>, value: Vec<u8> },
+	/// A part of the path shared by all the keys below it.
+	Extension { partial: Vec<u8>, child: Hash },
+	/// A fork in the path, with a child for each next nibble, and the value of the key which ends
+	/// here, if any.
+	Branch { children: Box<[Option<Hash>; 16]>, value: Option<Vec<u8>> },
+}
+
+impl Encode for Node {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		match self {
+			Node::Empty => 0u8.encode_to(dest),
+			Node::Leaf { partial, value } => {
+				1u8.encode_to(dest);
+				partial.encode_to(dest);
+				value.encode_to(dest);
+			},
+			Node::Extension { partial, child } => {
+				2u8.encode_to(dest);
+				partial.encode_to(dest);
+				child.encode_to(dest);
+			},
+			Node::Branch { children, value } => {
+				3u8.encode_to(dest);
+				children.encode_to(dest);
+				value.encode_to(dest);
+			},
+		}
+	}
+}
+
+impl Decode for Node {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		match u8::decode_from(input)? {
+			0 => Ok(Node::Empty),
+			1 => Ok(Node::Leaf {
+				partial: Decode::decode_from(input)?,
+				value: Decode::decode_from(input)?,
+			}),
+			2 => Ok(Node::Extension {
+				partial: Decode::decode_from(input)?,
+				child: Decode::decode_from(input)?,
+			}),
+			3 => Ok(Node::Branch {
+				children: Decode::decode_from(input)?,
+				value: Decode::decode_from(input)?,
+			}),
+			_ => Err("invalid trie node"),
+		}
+	}
+}
+
+/// Split `key` into nibbles, high nibble first, so the nibbles sort the same way as the bytes.
+fn nibbles(key: &[u8]) -> Vec<u8> {
+	key.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+}
+
+/// A proof of the value of a key: the encoded nodes on the path from the root to the key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageProof {
+	pub nodes: Vec<Vec<u8>>,
+}
+
+impl Encode for StorageProof {
+	fn encode_to(&self, dest: &mut Vec<u8>) {
+		self.nodes.encode_to(dest);
+	}
+}
+
+impl Decode for StorageProof {
+	fn decode_from(input: &mut &[u8]) -> Result<Self, &'static str> {
+		Ok(Self { nodes: Decode::decode_from(input)? })
+	}
+}
+
+/// The trie of some storage, holding every node by its hash.
+#[derive(Debug, Clone)]
+pub struct Trie {
+	root: Hash,
+	nodes: BTreeMap<Hash, Vec<u8>>,
+}
+
+impl Trie {
+	/// Build the trie of all the pairs in `storage`.
+	pub fn new(storage: &Storage) -> Self {
+		let entries = storage.iter().map(|(key, value)| (nibbles(key), value)).collect::<Vec<_>>();
+		let mut trie = Self { root: Hash::default(), nodes: BTreeMap::new() };
+		trie.root = if entries.is_empty() {
+			trie.insert_node(Node::Empty)
+		} else {
+			trie.build(&entries, 0)
+		};
+		trie
+	}
+
+	/// The root hash of the trie, which commits to all the pairs in it.
+	pub fn root(&self) -> Hash {
+		self.root
+	}
+
+	/// Get the value of `key`, if it has one.
+	#[allow(dead_code, reason = "outside of tests, values are read with proofs instead")]
+	pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		lookup(&self.root, key, |hash| self.nodes.get(hash).map(Vec::as_slice))
+			.expect("the trie has all of its nodes")
+	}
+
+	/// Prove the value of `key`, or that it has none.
+	pub fn prove(&self, key: &[u8]) -> StorageProof {
+		let mut proof = StorageProof::default();
+		lookup(&self.root, key, |hash| {
+			let node = self.nodes.get(hash)?;
+			proof.nodes.push(node.clone());
+			Some(node.as_slice())
+		})
+		.expect("the trie has all of its nodes");
+		proof
+	}
+
+	fn insert_node(&mut self, node: Node) -> Hash {
+		let encoded = node.encode();
+		let hash = hash(&encoded);
+		self.nodes.insert(hash, encoded);
+		hash
+	}
+
+	/// Build the node for `entries`, which are sorted and not empty, and whose keys all share their
+	/// first `depth` nibbles. Returns its hash.
+	fn build(&mut self, entries: &[(Vec<u8>, &[u8])], depth: usize) -> Hash {
+		if let [(key, value)] = entries {
+			let node = Node::Leaf { partial: key[depth..].to_vec(), value: value.to_vec() };
+			return self.insert_node(node);
+		}
+
+		// Since the keys are sorted, the prefix shared by the first and last keys is shared by all.
+		let (first, last) = (&entries[0].0, &entries[entries.len() - 1].0);
+		let shared = first[depth..].iter().zip(&last[depth..]).take_while(|(a, b)| a == b).count();
+		if shared > 0 {
+			let partial = first[depth..depth + shared].to_vec();
+			let child = self.build(entries, depth + shared);
+			return self.insert_node(Node::Extension { partial, child });
+		}
+
+		// Only the first key can end here, since it is a prefix of all the others.
+		let (value, mut rest) = match entries {
+			[(key, value), rest @ ..] if key.len() == depth => (Some(value.to_vec()), rest),
+			_ => (None, entries),
+		};
+		let mut children = [None; 16];
+		while let Some((key, _)) = rest.first() {
+			let nibble = key[depth];
+			let len = rest.iter().take_while(|(key, _)| key[depth] == nibble).count();
+			children[nibble as usize] = Some(self.build(&rest[..len], depth + 1));
+			rest = &rest[len..];
+		}
+		self.insert_node(Node::Branch { children: Box::new(children), value })
+	}
+}
+
+/// Follow the path of `key` from the node with hash `root`, getting each node by its hash from
+/// `node`. Returns the value of `key`, if it has one.
+fn lookup<'a>(
+	root: &Hash,
+	key: &[u8],
+	mut node: impl FnMut(&Hash) -> Option<&'a [u8]>,
+) -> Result<Option<Vec<u8>>, &'static str> {
+	let path = nibbles(key);
+	let mut path = path.as_slice();
+	let mut hash = *root;
+	loop {
+		let encoded = node(&hash).ok_or("proof is missing a node")?;
+		match Node::decode(encoded)? {
+			Node::Empty => return Ok(None),
+			Node::Leaf { partial, value } => return Ok((partial == path).then_some(value)),
+			Node::Extension { partial, child } => match path.strip_prefix(partial.as_slice()) {
+				Some(rest) => (path, hash) = (rest, child),
+				None => return Ok(None),
+			},
+			Node::Branch { children, value } => match path.split_first() {
+				None => return Ok(value),
+				Some((nibble, rest)) => match children[*nibble as usize] {
+					Some(child) => (path, hash) = (rest, child),
+					None => return Ok(None),
+				},
+			},
+		}
+	}
+}
+
+/// Check `proof` of `key` against `state_root`, usually the state root of a header. Returns the
+/// value of `key` in that state, or `None` if it has no value. This only needs the nodes in the
+/// proof, not the state itself.
+pub fn verify_proof(
+	state_root: &Hash,
+	key: &[u8],
+	proof: &StorageProof,
+) -> Result<Option<Vec<u8>>, &'static str> {
+	let nodes = proof
+		.nodes
+		.iter()
+		.map(|node| (hash(node), node.as_slice()))
+		.collect::<BTreeMap<_, _>>();
+	lookup(state_root, key, |hash| nodes.get(hash).copied())
+}
+
+#[cfg(test)]
+mod test {
+	use super::{verify_proof, Trie};
+	use crate::storage::Storage;
+
+	fn storage(pairs: &[(&[u8], &[u8])]) -> Storage {
+		pairs.iter().map(|(key, value)| (key.to_vec(), value.to_vec())).collect()
+	}
+
+	#[test]
+	fn root_commits_to_every_pair() {
+		let pairs: &[(&[u8], &[u8])] =
+			&[(b"do", b"verb"), (b"dog", b"puppy"), (b"doge", b"coin"), (b"horse", b"stallion")];
+		let trie = Trie::new(&storage(pairs));
+		for (key, value) in pairs {
+			assert_eq!(trie.get(key), Some(value.to_vec()));
+		}
+		assert_eq!(trie.get(b"d"), None);
+		assert_eq!(trie.get(b"dogs"), None);
+		assert_eq!(trie.get(b"cat"), None);
+
+		let mut changed = storage(pairs);
+		changed.insert(b"doge".to_vec(), b"coins".to_vec());
+		assert_ne!(Trie::new(&changed).root(), trie.root());
+		changed.insert(b"doge".to_vec(), b"coin".to_vec());
+		assert_eq!(Trie::new(&changed).root(), trie.root());
+		changed.remove(b"do");
+		assert_ne!(Trie::new(&changed).root(), trie.root());
+
+		assert_ne!(Trie::new(&Storage::new()).root(), trie.root());
+		assert_eq!(Trie::new(&Storage::new()).get(b"do"), None);
+	}
+
+	#[test]
+	fn proofs_verify_against_the_root() {
+		let pairs: &[(&[u8], &[u8])] =
+			&[(b"do", b"verb"), (b"dog", b"puppy"), (b"horse", b"stallion")];
+		let trie = Trie::new(&storage(pairs));
+		let root = trie.root();
+
+		let proof = trie.prove(b"dog");
+		assert_eq!(verify_proof(&root, b"dog", &proof), Ok(Some(b"puppy".to_vec())));
+		// A proof only proves the keys on its path.
+		assert_eq!(verify_proof(&root, b"horse", &proof), Err("proof is missing a node"));
+
+		// Keys without a value can be proven too.
+		let proof = trie.prove(b"doge");
+		assert_eq!(verify_proof(&root, b"doge", &proof), Ok(None));
+
+		// A proof does not verify against another root, or with a node changed.
+		let other = Trie::new(&storage(&pairs[..2])).root();
+		let proof = trie.prove(b"dog");
+		assert_eq!(verify_proof(&other, b"dog", &proof), Err("proof is missing a node"));
+		let mut tampered = trie.prove(b"dog");
+		let last = tampered.nodes.last_mut().unwrap();
+		*last.last_mut().unwrap() ^= 1;
+		assert_eq!(verify_proof(&root, b"dog", &tampered), Err("proof is missing a node"));
+	}
+}